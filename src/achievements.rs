@@ -0,0 +1,200 @@
+use super::game_state::GameEvent;
+use super::profile;
+use super::sprint::SPRINT_LINES;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A milestone unlocked once and kept across games.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Achievement {
+    FirstTetris,
+    BackToBack3,
+    SprintUnder60,
+    HundredGames,
+    PerfectClear,
+}
+
+pub const ALL: [Achievement; 5] = [
+    Achievement::FirstTetris,
+    Achievement::BackToBack3,
+    Achievement::SprintUnder60,
+    Achievement::HundredGames,
+    Achievement::PerfectClear,
+];
+
+impl Achievement {
+    /// Identifier used in the achievements file.
+    fn key(self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "first-tetris",
+            Achievement::BackToBack3 => "back-to-back-3",
+            Achievement::SprintUnder60 => "sprint-under-60",
+            Achievement::HundredGames => "hundred-games",
+            Achievement::PerfectClear => "perfect-clear",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "Tetris!",
+            Achievement::BackToBack3 => "Hat trick",
+            Achievement::SprintUnder60 => "Sprinter",
+            Achievement::HundredGames => "Regular",
+            Achievement::PerfectClear => "Spotless",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "Clear four lines at once.",
+            Achievement::BackToBack3 => "Clear three tetrises in a row.",
+            Achievement::SprintUnder60 => "Clear 40 lines in under a minute.",
+            Achievement::HundredGames => "Play 100 games.",
+            Achievement::PerfectClear => "Leave the board completely empty.",
+        }
+    }
+}
+
+/// Unlocked achievements and the progress towards them, saved to a
+/// file per player.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+    /// Games started, over all sessions
+    games: u32,
+    /// Tetrises cleared in a row in the current game
+    streak: u32,
+    /// Where to save, or `None` to keep everything in memory.
+    path: Option<PathBuf>,
+}
+
+impl Achievements {
+    /// Load the current player's achievements, starting fresh if none
+    /// were saved yet.
+    pub fn load() -> Result<Self, String> {
+        let path = profile::data_path("achievements")?;
+        let mut achievements = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        achievements.path = Some(path);
+        Ok(achievements)
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut achievements = Self::default();
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("games", n)) => {
+                    achievements.games = n
+                        .parse()
+                        .map_err(|_| format!("invalid game count {:?}", n))?
+                }
+                Some(("unlocked", key)) => {
+                    let achievement = ALL
+                        .iter()
+                        .find(|a| a.key() == key)
+                        .ok_or_else(|| format!("unknown achievement {:?}", key))?;
+                    achievements.unlocked.push(*achievement);
+                }
+                _ => return Err(format!("unexpected line {:?}", line)),
+            }
+        }
+        Ok(achievements)
+    }
+
+    /// Write the achievements back to their file, if they have one.
+    pub fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut contents = format!("games = {}\n", self.games);
+        for achievement in self.unlocked.iter() {
+            contents += &format!("unlocked = {}\n", achievement.key());
+        }
+        path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, contents))
+            .map_err(|e| format!("can't save {}: {}", path.display(), e))
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Count a newly started game. Returns what that unlocked.
+    pub fn start_game(&mut self) -> Vec<Achievement> {
+        self.games += 1;
+        self.streak = 0;
+        let mut new = Vec::new();
+        self.check(Achievement::HundredGames, self.games >= 100, &mut new);
+        new
+    }
+
+    /// Track an engine event, given the game's total lines and playing
+    /// time so far. Returns what that unlocked.
+    pub fn observe(&mut self, event: GameEvent, lines: u32, played: Duration) -> Vec<Achievement> {
+        let mut new = Vec::new();
+        match event {
+            GameEvent::LinesCleared(cleared) => {
+                self.streak = if cleared == 4 { self.streak + 1 } else { 0 };
+                self.check(Achievement::FirstTetris, cleared == 4, &mut new);
+                self.check(Achievement::BackToBack3, self.streak >= 3, &mut new);
+                let sprint = lines >= SPRINT_LINES && played < Duration::from_secs(60);
+                self.check(Achievement::SprintUnder60, sprint, &mut new);
+            }
+            GameEvent::PerfectClear => self.check(Achievement::PerfectClear, true, &mut new),
+            GameEvent::Locked(_) | GameEvent::StackCleared | GameEvent::Blocked(_) => (),
+        }
+        new
+    }
+
+    fn check(&mut self, achievement: Achievement, met: bool, new: &mut Vec<Achievement>) {
+        if met && !self.is_unlocked(achievement) {
+            self.unlocked.push(achievement);
+            new.push(achievement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlocks() {
+        let mut a = Achievements::default();
+        let tetris = GameEvent::LinesCleared(4);
+        let minute = Duration::from_secs(60);
+        assert_eq!(a.observe(tetris, 4, minute), vec![Achievement::FirstTetris]);
+        assert!(a.observe(tetris, 8, minute).is_empty());
+        assert!(a.observe(GameEvent::LinesCleared(1), 9, minute).is_empty());
+        a.observe(tetris, 13, minute);
+        a.observe(tetris, 17, minute);
+        assert_eq!(
+            a.observe(tetris, 21, minute),
+            vec![Achievement::BackToBack3]
+        );
+        assert_eq!(
+            a.observe(GameEvent::LinesCleared(2), 40, minute / 2),
+            vec![Achievement::SprintUnder60]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut a = Achievements::default();
+        a.start_game();
+        a.observe(GameEvent::PerfectClear, 4, Duration::from_secs(5));
+        let saved = format!(
+            "games = 1\nunlocked = {}\n",
+            Achievement::PerfectClear.key()
+        );
+        assert_eq!(Achievements::parse(&saved), Ok(a));
+        assert!(Achievements::parse("unlocked = nothing").is_err());
+    }
+}
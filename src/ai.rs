@@ -0,0 +1,441 @@
+use super::board::{Board, Placement};
+use super::config::GameConfig;
+use super::game_state::{Action, Piece, Tetris};
+#[cfg(feature = "std")]
+use super::profile;
+use super::randomizer::Rng;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How strong the bot plays.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// Names of the difficulty levels, as accepted by
+/// `Difficulty::from_name`.
+pub const DIFFICULTIES: [&str; 3] = ["easy", "medium", "hard"];
+
+/// The knobs that make up a difficulty level.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Strength {
+    /// Moves and rotations the bot makes per gravity tick.
+    pub actions_per_tick: u32,
+    /// Whether the bot hard drops once in place, or waits for gravity.
+    pub hard_drop: bool,
+    /// Amplitude of the random noise added to each placement's score.
+    pub noise: f64,
+    /// Number of pieces to look ahead: 1 for the falling piece only, 2
+    /// to also place the next piece. Capped by the preview queue.
+    pub depth: u32,
+    /// Number of candidate stacks kept at each step of the lookahead.
+    pub beam_width: usize,
+    /// Chance, in percent, of picking a random placement instead of the
+    /// best one.
+    pub misdrop: u64,
+    /// How the bot scores a stack.
+    pub weights: Weights,
+}
+
+/// Scores a settled stack. The default weights favor cleared lines and
+/// a low, flat stack without holes.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Weights {
+    pub lines: f64,
+    pub height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            lines: 0.76,
+            height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+        }
+    }
+}
+
+impl Weights {
+    /// Weights from the file named by a `--bot-weights=<path>` command
+    /// line argument, if any.
+    #[cfg(feature = "std")]
+    pub fn from_args() -> Result<Option<Self>, String> {
+        match profile::args().find_map(|arg| arg.strip_prefix("--bot-weights=").map(String::from)) {
+            Some(path) => Self::load(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Read weights written by `tetris-tune`.
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("can't read {}: {}", path, e))?
+            .parse()
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Score a settled stack, counting the lines cleared since
+    /// `base_lines`.
+    pub fn evaluate(&self, board: &Board, base_lines: u32) -> f64 {
+        let heights = board.column_heights();
+        let aggregate: usize = heights.iter().sum();
+        let bumpiness: usize = heights
+            .windows(2)
+            .map(|pair| (pair[0] as i64 - pair[1] as i64).unsigned_abs() as usize)
+            .sum();
+        let holes = board.holes();
+        let lines = board.lines() - base_lines;
+
+        self.lines * lines as f64
+            + self.height * aggregate as f64
+            + self.holes * holes as f64
+            + self.bumpiness * bumpiness as f64
+    }
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// The level named by a `--bot=<level>` command line argument, if
+    /// any.
+    #[cfg(feature = "std")]
+    pub fn from_args() -> Result<Option<Self>, String> {
+        match profile::args().find_map(|arg| arg.strip_prefix("--bot=").map(String::from)) {
+            Some(name) => name.parse().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn strength(self) -> Strength {
+        match self {
+            Difficulty::Easy => Strength {
+                actions_per_tick: 1,
+                hard_drop: false,
+                noise: 0.5,
+                depth: 1,
+                beam_width: 1,
+                misdrop: 10,
+                weights: Weights::default(),
+            },
+            Difficulty::Medium => Strength {
+                actions_per_tick: 2,
+                hard_drop: true,
+                noise: 0.3,
+                depth: 2,
+                beam_width: 2,
+                misdrop: 3,
+                weights: Weights::default(),
+            },
+            Difficulty::Hard => Strength {
+                actions_per_tick: 4,
+                hard_drop: true,
+                noise: 0.0,
+                depth: 2,
+                beam_width: 8,
+                misdrop: 0,
+                weights: Weights::default(),
+            },
+        }
+    }
+}
+
+/// A computer player. For every new piece it searches the placements
+/// of the falling piece and the pieces in the preview, scores the
+/// resulting stacks, and then plays the chosen placement out through
+/// ordinary game events.
+#[derive(Debug)]
+pub struct Bot {
+    strength: Strength,
+    rng: Rng,
+    /// Events still to play for the current piece, last one first.
+    plan: Vec<Action>,
+    /// `Tetris::pieces` when the plan was made.
+    planned_at: Option<u32>,
+}
+
+impl Bot {
+    pub fn new(strength: Strength, seed: u64) -> Self {
+        Self {
+            strength,
+            rng: Rng::new(seed),
+            plan: Vec::new(),
+            planned_at: None,
+        }
+    }
+
+    /// Make the bot's moves for one gravity tick.
+    pub fn act(&mut self, tetris: &mut Tetris) {
+        if tetris.is_over() {
+            return;
+        }
+        if self.planned_at != Some(tetris.pieces()) {
+            self.plan = self.choose(tetris);
+            self.plan.reverse();
+            self.planned_at = Some(tetris.pieces());
+        }
+        for _ in 0..self.strength.actions_per_tick {
+            match self.plan.pop() {
+                Some(evt) => {
+                    let before = (tetris.anchor(), tetris.rotation());
+                    tetris.event(evt);
+                    if (tetris.anchor(), tetris.rotation()) == before {
+                        // The piece fell into something on the way;
+                        // plan again from where it is now.
+                        self.plan.clear();
+                        self.planned_at = None;
+                        break;
+                    }
+                }
+                None => {
+                    if self.strength.hard_drop {
+                        tetris.hard_drop();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Pick the events that move the falling piece into place.
+    fn choose(&mut self, tetris: &Tetris) -> Vec<Action> {
+        let config = tetris.config();
+        let board = Board::from_tetris(tetris);
+        let mut plans = board.placements(Placement::falling(tetris), config);
+        if plans.is_empty() {
+            return Vec::new();
+        }
+        if self.rng.below(100) < self.strength.misdrop {
+            let pick = self.rng.below(plans.len() as u64) as usize;
+            return plans.swap_remove(pick).0;
+        }
+
+        // Beam search: score every placement of the falling piece, then
+        // repeatedly keep the best few stacks and place the following
+        // piece on each of them, remembering which first placement each
+        // stack grew from.
+        let strength = self.strength;
+        let base_lines = board.lines();
+        let queue = tetris.preview();
+        let depth = strength.depth.min(1 + queue.len() as u32) as usize;
+        let mut beam: Vec<(f64, usize, Board)> = Vec::new();
+        for (first, (_, landing)) in plans.iter().enumerate() {
+            if let Some(after) = lock(&board, *landing, queue.first(), config) {
+                let noise = (2.0 * self.rng.uniform() - 1.0) * strength.noise;
+                let score = strength.weights.evaluate(&after, base_lines) + noise;
+                beam.push((score, first, after));
+            }
+        }
+        for ply in 1..depth {
+            beam.sort_by(|a, b| b.0.total_cmp(&a.0));
+            beam.truncate(strength.beam_width.max(1));
+            let spawn = Placement::spawn(queue[ply - 1], config);
+            let expand = |(_, first, board): &(f64, usize, Board)| {
+                let mut next = Vec::new();
+                for (_, landing) in board.placements(spawn, config) {
+                    if let Some(after) = lock(board, landing, queue.get(ply), config) {
+                        let score = strength.weights.evaluate(&after, base_lines);
+                        next.push((score, *first, after));
+                    }
+                }
+                next
+            };
+            // Each stack of the beam is expanded on its own core. The
+            // results come back in beam order, so the choice is the
+            // same as when searching on one thread.
+            #[cfg(feature = "parallel")]
+            let next: Vec<(f64, usize, Board)> = beam.par_iter().flat_map_iter(expand).collect();
+            #[cfg(not(feature = "parallel"))]
+            let next: Vec<(f64, usize, Board)> = beam.iter().flat_map(expand).collect();
+            if next.is_empty() {
+                break;
+            }
+            beam = next;
+        }
+
+        match beam.into_iter().max_by(|a, b| a.0.total_cmp(&b.0)) {
+            Some((_, first, _)) => plans.swap_remove(first).0,
+            // Every placement tops out; the piece may as well fall.
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Let a bot play a seeded game on its own, as fast as it can, until it
+/// tops out or has locked `pieces` pieces.
+pub fn simulate(strength: Strength, seed: u64, pieces: u32) -> Tetris {
+    let mut tetris = GameConfig::new().seed(seed).build();
+    let mut bot = Bot::new(strength, seed);
+    while tetris.pieces() < pieces && !tetris.is_over() {
+        bot.act(&mut tetris);
+        tetris.tick();
+    }
+    tetris
+}
+
+/// The board after locking a piece at `landing`, or `None` if the `next`
+/// piece, when known, would then have no room to spawn.
+fn lock(
+    board: &Board,
+    landing: Placement,
+    next: Option<&Piece>,
+    config: &GameConfig,
+) -> Option<Board> {
+    let after = board.with_piece_locked(landing);
+    match next {
+        Some(next) if !after.fits(Placement::spawn(*next, config)) => None,
+        _ => Some(after),
+    }
+}
+
+/// One `name = value` line per weight, as read back by `FromStr`.
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lines = {}", self.lines)?;
+        writeln!(f, "height = {}", self.height)?;
+        writeln!(f, "holes = {}", self.holes)?;
+        writeln!(f, "bumpiness = {}", self.bumpiness)
+    }
+}
+
+/// Parses `name = value` lines. Blank lines and `#` comments are
+/// skipped, and missing weights keep their default.
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Self::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown bot difficulty {:?}, expected one of: {}",
+                name,
+                DIFFICULTIES.join(", ")
+            )
+        })
+    }
+}
+
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut weights = Self::default();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name = value`, got {:?}", line))?;
+            let value: f64 = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight {:?}", value.trim()))?;
+            match name.trim() {
+                "lines" => weights.lines = value,
+                "height" => weights.height = value,
+                "holes" => weights.holes = value,
+                "bumpiness" => weights.bumpiness = value,
+                other => return Err(format!("unknown weight {:?}", other)),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hard_bot_clears_lines() {
+        let tetris = simulate(Difficulty::Hard.strength(), 7, 50);
+        assert_eq!(tetris.top_out(), None);
+        assert!(tetris.lines() >= 15, "cleared {}", tetris.lines());
+    }
+
+    #[test]
+    fn test_search_is_thread_safe() {
+        fn thread_safe<T: Send + Sync>() {}
+        thread_safe::<Tetris>();
+        thread_safe::<GameConfig>();
+        thread_safe::<Board>();
+        thread_safe::<Bot>();
+        // However the work is split up, a seed plays out the same game.
+        let strength = Difficulty::Hard.strength();
+        assert_eq!(
+            simulate(strength, 3, 30).state_hash(),
+            simulate(strength, 3, 30).state_hash()
+        );
+    }
+
+    #[test]
+    fn test_weights_punish_holes() {
+        let weights = Weights::default();
+        let flat = crate::board!["##........"];
+        let holey = crate::board![
+            "##........"
+            ".........."
+        ];
+        assert!(weights.evaluate(&flat, 0) > weights.evaluate(&holey, 0));
+    }
+
+    #[test]
+    fn test_weights_round_trip() {
+        let weights = Weights {
+            lines: 1.5,
+            height: -0.25,
+            holes: -2.0,
+            bumpiness: 0.0,
+        };
+        assert_eq!(weights.to_string().parse(), Ok(weights));
+        assert_eq!(
+            "# tuned\nholes = -1\n".parse(),
+            Ok(Weights {
+                holes: -1.0,
+                ..Weights::default()
+            })
+        );
+        assert!("speed = 3".parse::<Weights>().is_err());
+    }
+
+    #[test]
+    fn test_difficulty_names() {
+        for name in DIFFICULTIES.iter() {
+            assert_eq!(
+                Difficulty::from_name(name).map(Difficulty::name),
+                Some(*name)
+            );
+        }
+        assert!(Difficulty::from_name("nightmare").is_none());
+        assert!("nightmare".parse::<Difficulty>().is_err());
+        assert!(Difficulty::Easy.strength().misdrop > Difficulty::Hard.strength().misdrop);
+        // Hard plays the best placement it finds.
+        assert_eq!(Difficulty::Hard.strength().noise, 0.0);
+    }
+}
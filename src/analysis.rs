@@ -0,0 +1,168 @@
+//! A breakdown of how a game was played, gathered from its engine
+//! events and shown once it is over.
+
+use super::game_state::{GameEvent, Piece};
+use super::replay::Replay;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// Tallies of a game's locks and line clears.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    /// Pieces locked, by type
+    pieces: [u32; 7],
+    /// Singles, doubles, triples and tetrises
+    clears: [u32; 4],
+    perfect_clears: u32,
+    /// Pieces in a row that cleared lines, counting the one locked last
+    streak: u32,
+    /// The longest such run
+    longest: u32,
+    /// Whether the last piece locked cleared lines
+    cleared_last: bool,
+}
+
+impl Analysis {
+    /// Take an event into account, in the order the engine sent them.
+    pub fn observe(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::Locked(piece) => {
+                self.pieces[piece as usize] += 1;
+                if !self.cleared_last {
+                    self.streak = 0;
+                }
+                self.cleared_last = false;
+            }
+            GameEvent::LinesCleared(lines) => {
+                self.clears[(lines.clamp(1, 4) - 1) as usize] += 1;
+                self.streak += 1;
+                self.longest = self.longest.max(self.streak);
+                self.cleared_last = true;
+            }
+            GameEvent::PerfectClear => self.perfect_clears += 1,
+            GameEvent::StackCleared | GameEvent::Blocked(_) => (),
+        }
+    }
+
+    /// The analysis of a recorded game, played again from its start.
+    /// A restart starts the tallies over.
+    pub fn of(replay: &Replay) -> Self {
+        let mut tetris = replay.state(0);
+        let mut analysis = Self::default();
+        for step in replay.steps() {
+            let draws = tetris.draws();
+            tetris.apply(step);
+            if tetris.draws() < draws {
+                analysis = Self::default();
+            }
+            for event in tetris.take_events() {
+                analysis.observe(event);
+            }
+        }
+        analysis
+    }
+
+    pub fn pieces(&self) -> u32 {
+        self.pieces.iter().sum()
+    }
+
+    /// How many lines were cleared.
+    pub fn lines(&self) -> u32 {
+        self.clears
+            .iter()
+            .zip(1..)
+            .map(|(clears, lines)| clears * lines)
+            .sum()
+    }
+
+    /// The share of lines cleared four at a time, from 0 to 1.
+    pub fn tetris_rate(&self) -> f64 {
+        match self.lines() {
+            0 => 0.0,
+            lines => (4 * self.clears[3]) as f64 / lines as f64,
+        }
+    }
+
+    /// The largest combo, counted the guideline way: the pieces in a
+    /// row that cleared lines, after the first one.
+    pub fn max_combo(&self) -> u32 {
+        self.longest.saturating_sub(1)
+    }
+
+    /// The analysis as lines of text for the game over screen.
+    pub fn report(&self) -> Vec<String> {
+        let pieces: Vec<String> = (0..7)
+            .filter_map(|index| Piece::try_from(index).ok())
+            .map(|piece| format!("{:?} {}", piece, self.pieces[piece as usize]))
+            .collect();
+        let [singles, doubles, triples, tetrises] = self.clears;
+        let mut report = vec![
+            format!("Pieces: {} ({})", self.pieces(), pieces.join(" ")),
+            format!(
+                "Clears: {} single, {} double, {} triple, {} tetris",
+                singles, doubles, triples, tetrises
+            ),
+            format!("Tetris rate: {:.0}%", 100.0 * self.tetris_rate()),
+            format!("Max combo: {}", self.max_combo()),
+        ];
+        if self.perfect_clears > 0 {
+            report.push(format!("Perfect clears: {}", self.perfect_clears));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::Tetris;
+    use core::time::Duration;
+
+    #[test]
+    fn test_analysis() {
+        let mut analysis = Analysis::default();
+        let events = [
+            GameEvent::Locked(Piece::I),
+            GameEvent::LinesCleared(4),
+            GameEvent::Locked(Piece::T),
+            GameEvent::LinesCleared(2),
+            GameEvent::Locked(Piece::O),
+            GameEvent::LinesCleared(2),
+            GameEvent::PerfectClear,
+            GameEvent::Locked(Piece::I),
+            GameEvent::Locked(Piece::L),
+            GameEvent::LinesCleared(1),
+        ];
+        for event in events {
+            analysis.observe(event);
+        }
+        assert_eq!(analysis.pieces(), 5);
+        assert_eq!(analysis.lines(), 9);
+        assert_eq!(analysis.max_combo(), 2);
+        assert!((analysis.tetris_rate() - 4.0 / 9.0).abs() < 1e-9);
+        let report = analysis.report();
+        assert_eq!(report[0], "Pieces: 5 (O 1 L 1 J 0 T 1 Z 0 S 0 I 2)");
+        assert_eq!(report[2], "Tetris rate: 44%");
+        assert_eq!(report[4], "Perfect clears: 1");
+
+        assert_eq!(Analysis::default().tetris_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_of_replay() {
+        let mut tetris = Tetris::new();
+        tetris.record_inputs(true);
+        let mut replay = Replay::new(&tetris);
+        for _ in 0..5 {
+            tetris.hard_drop();
+        }
+        replay.extend(tetris.take_inputs(), Duration::ZERO);
+        let analysis = Analysis::of(&replay);
+        assert_eq!(analysis.pieces(), 5);
+        assert_eq!(analysis.pieces(), tetris.pieces());
+        assert_eq!(analysis.lines(), tetris.lines());
+    }
+}
@@ -0,0 +1,113 @@
+use super::game_state::{Piece, Tetris};
+use std::process::{Command, Stdio};
+
+/// Turns changes in the game state into short textual announcements,
+/// for players who can't rely on the visual grid.
+pub struct Announcer {
+    piece: Option<Piece>,
+    lines: u32,
+    /// Most recent announcements, oldest first.
+    history: Vec<String>,
+    /// Speak announcements through the system text-to-speech command.
+    speak: bool,
+}
+
+/// How many announcements to keep around for display.
+const HISTORY: usize = 8;
+
+impl Announcer {
+    pub fn new(speak: bool) -> Self {
+        Self {
+            piece: None,
+            lines: 0,
+            history: Vec::new(),
+            speak,
+        }
+    }
+
+    /// Recent announcements, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Compare against the last observed state and announce anything
+    /// worth knowing: lines cleared and newly spawned pieces.
+    pub fn observe(&mut self, tetris: &Tetris) {
+        if tetris.lines() > self.lines {
+            let cleared = tetris.lines() - self.lines;
+            self.lines = tetris.lines();
+            self.announce(format!(
+                "Cleared {} line{}, {} total.",
+                cleared,
+                if cleared == 1 { "" } else { "s" },
+                self.lines
+            ));
+        }
+
+        if self.piece != Some(tetris.piece()) {
+            self.piece = Some(tetris.piece());
+            let heights: Vec<String> = tetris
+                .column_heights()
+                .iter()
+                .map(|h| h.to_string())
+                .collect();
+            self.announce(format!(
+                "{:?} piece. Heights {}.",
+                tetris.piece(),
+                heights.join(" ")
+            ));
+        }
+    }
+
+    fn announce(&mut self, message: String) {
+        if self.speak {
+            speak(&message);
+        }
+        self.history.push(message);
+        if self.history.len() > HISTORY {
+            self.history.remove(0);
+        }
+    }
+}
+
+/// Best-effort text-to-speech through `say` on macOS or `espeak`
+/// elsewhere. Failures are ignored; the text is still displayed.
+fn speak(message: &str) {
+    let program = if cfg!(target_os = "macos") {
+        "say"
+    } else {
+        "espeak"
+    };
+    let _ = Command::new(program)
+        .arg(message)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announce_spawns() {
+        let mut t = Tetris::new();
+        let mut a = Announcer::new(false);
+        a.observe(&t);
+        assert_eq!(a.history(), ["O piece. Heights 0 0 0 0 0 0 0 0 0 0."]);
+
+        // Nothing changed, so nothing new to say.
+        t.tick();
+        a.observe(&t);
+        assert_eq!(a.history().len(), 1);
+
+        for _ in 0..18 {
+            t.tick();
+        }
+        a.observe(&t);
+        assert_eq!(
+            a.history().last().unwrap(),
+            "L piece. Heights 0 0 0 0 2 2 0 0 0 0."
+        );
+    }
+}
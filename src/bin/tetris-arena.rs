@@ -0,0 +1,115 @@
+//! Pits two bot configurations against each other on the same seeded
+//! games and prints a results summary, e.g. to check that an AI change
+//! is actually an improvement.
+
+use std::process;
+use tetris_rs::ai::{self, Difficulty, Strength, Weights};
+use tetris_rs::flags::{self, number};
+use tetris_rs::game_state::Tetris;
+
+const USAGE: &str = "usage: tetris-arena [--a=LEVEL] [--b=LEVEL] [--a-weights=PATH] \
+                     [--b-weights=PATH] [--games=N] [--pieces=N] [--seed=N]";
+
+struct Options {
+    a: Strength,
+    b: Strength,
+    games: u64,
+    /// Pieces each bot gets per game
+    pieces: u32,
+    seed: u64,
+}
+
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut options = Self {
+            a: Difficulty::Hard.strength(),
+            b: Difficulty::Medium.strength(),
+            games: 10,
+            pieces: 500,
+            seed: 1,
+        };
+        // Weights apply on top of the chosen level, whatever the order.
+        let mut weights: [Option<Weights>; 2] = [None, None];
+        for (name, value) in flags::parse(std::env::args().skip(1))? {
+            let name = name.as_str();
+            match name {
+                "--a" => options.a = value.parse::<Difficulty>()?.strength(),
+                "--b" => options.b = value.parse::<Difficulty>()?.strength(),
+                "--a-weights" => weights[0] = Some(Weights::load(&value)?),
+                "--b-weights" => weights[1] = Some(Weights::load(&value)?),
+                "--games" => options.games = number(name, &value)?,
+                "--pieces" => options.pieces = number(name, &value)?,
+                "--seed" => options.seed = number(name, &value)?,
+                _ => return Err(format!("unknown option {:?}", name)),
+            }
+        }
+        if let Some(weights) = weights[0] {
+            options.a.weights = weights;
+        }
+        if let Some(weights) = weights[1] {
+            options.b.weights = weights;
+        }
+        Ok(options)
+    }
+}
+
+/// A game is won by surviving longer, or by clearing more lines when
+/// both bots survive or both top out.
+fn winner(a: &Tetris, b: &Tetris) -> Option<char> {
+    let key = |t: &Tetris| (t.top_out().is_none(), t.pieces(), t.lines());
+    match key(a).cmp(&key(b)) {
+        std::cmp::Ordering::Greater => Some('A'),
+        std::cmp::Ordering::Less => Some('B'),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+fn main() {
+    let options = Options::from_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let mut wins = [0, 0];
+    let mut lines = [0, 0];
+    for game in 0..options.games {
+        let seed = options.seed + game;
+        let a = ai::simulate(options.a, seed, options.pieces);
+        let b = ai::simulate(options.b, seed, options.pieces);
+        let result = winner(&a, &b);
+        match result {
+            Some('A') => wins[0] += 1,
+            Some(_) => wins[1] += 1,
+            None => (),
+        }
+        lines[0] += a.lines();
+        lines[1] += b.lines();
+        println!(
+            "seed {:>4}: A {:>4} lines{}, B {:>4} lines{} -> {}",
+            seed,
+            a.lines(),
+            if a.top_out().is_some() {
+                " (topped out)"
+            } else {
+                ""
+            },
+            b.lines(),
+            if b.top_out().is_some() {
+                " (topped out)"
+            } else {
+                ""
+            },
+            result.map_or(String::from("draw"), |side| side.to_string())
+        );
+    }
+
+    let games = options.games.max(1) as f64;
+    println!(
+        "A won {}, B won {}, {} drawn; average lines A {:.1}, B {:.1}",
+        wins[0],
+        wins[1],
+        options.games - wins[0] - wins[1],
+        lines[0] as f64 / games,
+        lines[1] as f64 / games
+    );
+}
@@ -0,0 +1,142 @@
+//! Tunes the bot's evaluation weights with a small genetic algorithm over
+//! headless self-play games, and writes the best weights found to a file
+//! that `tetris-rs --bot-weights=<file>` loads.
+
+use std::cmp::Reverse;
+use std::process;
+use tetris_rs::ai::{self, Difficulty, Strength, Weights};
+use tetris_rs::flags::{self, number};
+use tetris_rs::randomizer::Rng;
+
+const USAGE: &str = "usage: tetris-tune [--generations=N] [--population=N] [--games=N] \
+                     [--pieces=N] [--seed=N] [--out=PATH]";
+
+struct Options {
+    generations: u32,
+    population: usize,
+    /// Games each candidate plays per generation.
+    games: u64,
+    pieces: u32,
+    seed: u64,
+    out: String,
+}
+
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut options = Self {
+            generations: 20,
+            population: 16,
+            games: 4,
+            pieces: 250,
+            seed: 1,
+            out: String::from("weights.txt"),
+        };
+        for (name, value) in flags::parse(std::env::args().skip(1))? {
+            let name = name.as_str();
+            match name {
+                "--generations" => options.generations = number(name, &value)?,
+                "--population" => options.population = number::<usize>(name, &value)?.max(2),
+                "--games" => options.games = number::<u64>(name, &value)?.max(1),
+                "--pieces" => options.pieces = number(name, &value)?,
+                "--seed" => options.seed = number(name, &value)?,
+                "--out" => options.out = value,
+                _ => return Err(format!("unknown option {:?}", name)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The bot used for tuning: no noise or misdrops, and no lookahead so
+/// games run quickly.
+fn strength(weights: Weights) -> Strength {
+    Strength {
+        actions_per_tick: u32::MAX,
+        depth: 1,
+        beam_width: 1,
+        weights,
+        ..Difficulty::Hard.strength()
+    }
+}
+
+/// Total lines cleared over the same set of seeded games.
+fn fitness(weights: Weights, options: &Options) -> u32 {
+    (0..options.games)
+        .map(|game| ai::simulate(strength(weights), options.seed + game, options.pieces).lines())
+        .sum()
+}
+
+/// Pick each weight from either parent.
+fn crossover(a: &Weights, b: &Weights, rng: &mut Rng) -> Weights {
+    let mut pick = |x: f64, y: f64| if rng.below(2) == 0 { x } else { y };
+    Weights {
+        lines: pick(a.lines, b.lines),
+        height: pick(a.height, b.height),
+        holes: pick(a.holes, b.holes),
+        bumpiness: pick(a.bumpiness, b.bumpiness),
+    }
+}
+
+/// Nudge every weight by up to `scale` in either direction.
+fn mutate(weights: &Weights, scale: f64, rng: &mut Rng) -> Weights {
+    let mut nudge = |x: f64| x + (2.0 * rng.uniform() - 1.0) * scale;
+    Weights {
+        lines: nudge(weights.lines),
+        height: nudge(weights.height),
+        holes: nudge(weights.holes),
+        bumpiness: nudge(weights.bumpiness),
+    }
+}
+
+fn main() {
+    let options = Options::from_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let mut rng = Rng::new(options.seed);
+
+    let mut population = vec![Weights::default()];
+    while population.len() < options.population {
+        population.push(mutate(&Weights::default(), 0.5, &mut rng));
+    }
+
+    let mut best = (0, Weights::default());
+    for generation in 0..options.generations {
+        let mut scored: Vec<(u32, Weights)> = population
+            .iter()
+            .map(|weights| (fitness(*weights, &options), *weights))
+            .collect();
+        scored.sort_by_key(|(lines, _)| Reverse(*lines));
+        eprintln!(
+            "generation {}: best {} lines over {} games",
+            generation, scored[0].0, options.games
+        );
+        if scored[0].0 >= best.0 {
+            best = scored[0];
+        }
+
+        // The top quarter survives and breeds the rest.
+        let parents: Vec<Weights> = scored
+            .iter()
+            .take((options.population / 4).max(2))
+            .map(|(_, weights)| *weights)
+            .collect();
+        population = parents.clone();
+        while population.len() < options.population {
+            let a = &parents[rng.below(parents.len() as u64) as usize];
+            let b = &parents[rng.below(parents.len() as u64) as usize];
+            let child = crossover(a, b, &mut rng);
+            population.push(mutate(&child, 0.1, &mut rng));
+        }
+    }
+
+    let contents = format!(
+        "# {} lines over {} games of {} pieces\n{}",
+        best.0, options.games, options.pieces, best.1
+    );
+    if let Err(e) = std::fs::write(&options.out, contents) {
+        eprintln!("can't write {}: {}", options.out, e);
+        process::exit(1);
+    }
+    eprintln!("wrote {}", options.out);
+}
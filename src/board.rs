@@ -0,0 +1,510 @@
+use super::config::GameConfig;
+use super::game_state::{piece_offsets, Action, Cell, Grid, Piece, Tetris, NCOLS, NROWS};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+/// A row with every cell filled.
+const FULL_ROW: u16 = (1 << NCOLS) - 1;
+
+/// Where a piece is: its type, its rotation, and the (row, col) of its
+/// anchor, as in `Tetris`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Placement {
+    pub piece: Piece,
+    pub rotation: i32,
+    pub row: i32,
+    pub col: i32,
+}
+
+impl Placement {
+    /// The falling piece of `tetris`, where it is now.
+    pub fn falling(tetris: &Tetris) -> Self {
+        let (row, col) = tetris.anchor();
+        Self {
+            piece: tetris.piece(),
+            rotation: tetris.rotation(),
+            row,
+            col,
+        }
+    }
+
+    /// Where `piece` appears under the game's settings.
+    pub fn spawn(piece: Piece, config: &GameConfig) -> Self {
+        let spawn = config.rotation.spawn(piece);
+        Self {
+            piece,
+            rotation: spawn.rotation,
+            row: spawn.row,
+            col: spawn.col,
+        }
+    }
+
+    /// The (row, col) cells the piece covers.
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        piece_offsets(self.piece, self.rotation).map(|(row, col)| (self.row + row, self.col + col))
+    }
+
+    fn shifted(self, rows: i32, cols: i32) -> Self {
+        Self {
+            row: self.row + rows,
+            col: self.col + cols,
+            ..self
+        }
+    }
+}
+
+/// The settled stack of a game, without its falling piece: what fills
+/// each cell, and a bit per cell for quick lookups. Copying one is cheap,
+/// so the bot can try out thousands of placements on them rather than on
+/// clones of the whole game.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Board {
+    /// Bit `col` of `rows[row]` is set when that cell is filled
+    rows: [u16; NROWS],
+    /// The content of each cell, kept in step with `rows`
+    cells: Grid,
+    /// Total number of rows cleared so far
+    lines: u32,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            rows: [0; NROWS],
+            cells: [[Cell::Empty; NCOLS]; NROWS],
+            lines: 0,
+        }
+    }
+}
+
+impl Board {
+    /// The settled stack of `tetris`, which the game keeps as a board.
+    pub fn from_tetris(tetris: &Tetris) -> Self {
+        tetris.board
+    }
+
+    pub fn is_filled(&self, row: usize, col: usize) -> bool {
+        self.rows[row] & (1 << col) != 0
+    }
+
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        self.cells[row][col]
+    }
+
+    /// Every cell, top to bottom.
+    pub fn cells(&self) -> &Grid {
+        &self.cells
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, cell: Cell) {
+        self.cells[row][col] = cell;
+        if cell.is_filled() {
+            self.rows[row] |= 1 << col;
+        } else {
+            self.rows[row] &= !(1 << col);
+        }
+    }
+
+    /// Whether no cell is filled.
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|bits| *bits == 0)
+    }
+
+    /// Empty every cell, keeping the count of rows cleared.
+    pub fn clear(&mut self) {
+        *self = Self {
+            lines: self.lines,
+            ..Self::default()
+        };
+    }
+
+    /// Every row, top to bottom, with bit `col` set where that cell is
+    /// filled.
+    pub fn rows(&self) -> &[u16; NROWS] {
+        &self.rows
+    }
+
+    pub fn is_row_full(&self, row: usize) -> bool {
+        self.rows[row] == FULL_ROW
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Count `lines` as cleared so far.
+    pub fn set_lines(&mut self, lines: u32) {
+        self.lines = lines;
+    }
+
+    /// Whether a piece can be at `placement`: inside the board and clear
+    /// of the stack.
+    pub fn fits(&self, placement: Placement) -> bool {
+        placement.cells().iter().all(|&(row, col)| {
+            (0..NROWS as i32).contains(&row)
+                && (0..NCOLS as i32).contains(&col)
+                && !self.is_filled(row as usize, col as usize)
+        })
+    }
+
+    /// Where a piece at `placement` lands when dropped straight down.
+    pub fn drop(&self, placement: Placement) -> Placement {
+        let mut landed = placement;
+        while self.fits(landed.shifted(1, 0)) {
+            landed = landed.shifted(1, 0);
+        }
+        landed
+    }
+
+    /// Fill the cells a piece at `placement` covers, leaving any rows it
+    /// completes for `clear_full_rows`.
+    pub fn lock(&mut self, placement: Placement) {
+        for (row, col) in placement.cells() {
+            self.set(row as usize, col as usize, Cell::Locked(placement.piece));
+        }
+    }
+
+    /// The board after a piece locks at `placement`, with any rows it
+    /// completes cleared.
+    pub fn with_piece_locked(&self, placement: Placement) -> Self {
+        let mut locked = *self;
+        locked.lock(placement);
+        locked.clear_full_rows();
+        locked
+    }
+
+    /// Remove every complete row, letting the rows above fall into
+    /// place, and return how many were removed.
+    pub fn clear_full_rows(&mut self) -> u32 {
+        let mut cleared = 0;
+        // Next row, counting from the bottom, to receive a kept row.
+        let mut write = NROWS;
+        for read in (0..NROWS).rev() {
+            if self.is_row_full(read) {
+                cleared += 1;
+                continue;
+            }
+            write -= 1;
+            self.rows[write] = self.rows[read];
+            self.cells[write] = self.cells[read];
+        }
+        self.rows[..write].fill(0);
+        self.cells[..write].fill([Cell::Empty; NCOLS]);
+        self.lines += cleared;
+        cleared
+    }
+
+    /// Push the stack up by a garbage row per hole column, full but for
+    /// that column, one row after another so that the last ends up at
+    /// the bottom. Returns false, leaving out the row and those after it,
+    /// once a row would push the stack out of the top.
+    pub fn insert_garbage(&mut self, holes: &[usize]) -> bool {
+        holes.iter().all(|hole| {
+            if self.rows[0] != 0 {
+                return false;
+            }
+            let hole = hole % NCOLS;
+            self.rows.copy_within(1.., 0);
+            self.cells.copy_within(1.., 0);
+            self.rows[NROWS - 1] = FULL_ROW & !(1 << hole);
+            self.cells[NROWS - 1] = [Cell::Garbage; NCOLS];
+            self.cells[NROWS - 1][hole] = Cell::Empty;
+            true
+        })
+    }
+
+    /// How many rows still hold garbage.
+    pub fn garbage_rows(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|row| row.contains(&Cell::Garbage))
+            .count()
+    }
+
+    /// Height of the stack in a column.
+    pub fn column_height(&self, col: usize) -> usize {
+        let top = (0..NROWS).find(|&row| self.is_filled(row, col));
+        top.map_or(0, |row| NROWS - row)
+    }
+
+    /// Height of the stack in each column.
+    pub fn column_heights(&self) -> [usize; NCOLS] {
+        let mut heights = [0; NCOLS];
+        for (col, height) in heights.iter_mut().enumerate() {
+            *height = self.column_height(col);
+        }
+        heights
+    }
+
+    /// Empty cells with a filled cell somewhere above them.
+    pub fn holes(&self) -> usize {
+        let mut covered = 0;
+        let mut holes = 0;
+        for bits in self.rows {
+            holes += (covered & !bits).count_ones() as usize;
+            covered |= bits;
+        }
+        holes
+    }
+
+    /// Every distinct landing spot of a piece starting out `from`, each
+    /// with the shortest rotations and shifts that get it there, moving
+    /// the way `Tetris` would.
+    pub fn placements(
+        &self,
+        from: Placement,
+        config: &GameConfig,
+    ) -> Vec<(Vec<Action>, Placement)> {
+        use Action::*;
+        let mut plans: Vec<(Vec<Action>, Placement)> = Vec::new();
+        let mut landings: Vec<[(i32, i32); 4]> = Vec::new();
+        let mut add = |plan: Vec<Action>, moved: Placement| {
+            let landed = self.drop(moved);
+            let mut cells = landed.cells();
+            cells.sort_unstable();
+            if !landings.contains(&cells) {
+                landings.push(cells);
+                plans.push((plan, landed));
+            }
+        };
+        for turns in [
+            vec![],
+            vec![RotateCw],
+            vec![RotateCcw],
+            vec![RotateCw, RotateCw],
+        ] {
+            let rotated = turns.iter().try_fold(from, |placement, turn| {
+                self.rotate(placement, *turn == RotateCw, config)
+            });
+            let rotated = match rotated {
+                Some(rotated) => rotated,
+                None => continue,
+            };
+            add(turns.clone(), rotated);
+            for (dir, cols) in [(MoveLeft, -1), (MoveRight, 1)] {
+                let mut moved = rotated;
+                let mut plan = turns.clone();
+                while self.fits(moved.shifted(0, cols)) {
+                    moved = moved.shifted(0, cols);
+                    plan.push(dir);
+                    add(plan.clone(), moved);
+                }
+            }
+        }
+        plans
+    }
+
+    /// Turn a piece in place if it fits, or else at the first wall kick
+    /// offset where it does.
+    fn rotate(
+        &self,
+        placement: Placement,
+        clockwise: bool,
+        config: &GameConfig,
+    ) -> Option<Placement> {
+        let turned = Placement {
+            rotation: (placement.rotation + if clockwise { 1 } else { 3 }) % 4,
+            ..placement
+        };
+        core::iter::once(&(0, 0))
+            .chain(config.kicks.offsets(placement.piece, clockwise))
+            .map(|(col, row)| turned.shifted(*row, *col))
+            .find(|kicked| self.fits(*kicked))
+    }
+}
+
+/// A `Board` drawn as ASCII art, one string literal per row in the
+/// `Display` format. The rows given are the bottom of the board:
+///
+/// ```
+/// use tetris_rs::board;
+///
+/// let board = board![
+///     "#........."
+///     "#########."
+/// ];
+/// assert_eq!(board.column_height(0), 2);
+/// assert_eq!(board.holes(), 0);
+/// ```
+///
+/// Panics if a row isn't `NCOLS` cells of `#` and `.`.
+#[macro_export]
+macro_rules! board {
+    ($($row:literal)*) => {
+        <$crate::board::Board as ::core::str::FromStr>::from_str(concat!($($row, "\n"),*))
+            .expect("invalid board literal")
+    };
+}
+
+/// One line per row, `#` for a filled cell and `.` for an empty one,
+/// from the top of the stack down to the floor. The empty rows above the
+/// stack are left out, so an empty board is an empty string.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let top = self.rows.iter().position(|bits| *bits != 0);
+        for bits in &self.rows[top.unwrap_or(NROWS)..] {
+            for col in 0..NCOLS {
+                f.write_str(if bits & (1 << col) != 0 { "#" } else { "." })?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the `Display` format. The rows given are the bottom of the
+/// board and the rest are empty; blank lines and the indentation of the
+/// rows are ignored. Filled cells are garbage.
+impl FromStr for Board {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.len() > NROWS {
+            return Err(format!(
+                "expected at most {} rows, got {}",
+                NROWS,
+                lines.len()
+            ));
+        }
+        let mut board = Self::default();
+        let top = NROWS - lines.len();
+        for (row, line) in lines.into_iter().enumerate() {
+            if line.chars().count() != NCOLS {
+                return Err(format!("expected {} cells in row {:?}", NCOLS, line));
+            }
+            for (col, cell) in line.chars().enumerate() {
+                match cell {
+                    '#' => board.set(top + row, col, Cell::Garbage),
+                    '.' => (),
+                    other => return Err(format!("unexpected {:?} in row {:?}", other, line)),
+                }
+            }
+        }
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_matches_game() {
+        let mut tetris = GameConfig::new().seed(5).build();
+        for i in 0..12 {
+            for _ in 0..i % 5 {
+                tetris.event(Action::MoveLeft);
+            }
+            tetris.event(Action::RotateCw);
+            tetris.hard_drop();
+        }
+        // Let the new piece fall clear of the top so that it can turn.
+        tetris.tick();
+        let board = Board::from_tetris(&tetris);
+        let view = tetris.view();
+        for row in 0..NROWS {
+            for col in 0..NCOLS {
+                assert_eq!(board.is_filled(row, col), view.board[row][col].is_filled());
+            }
+        }
+        assert_eq!(board.column_heights(), tetris.column_heights());
+
+        // Playing each plan lands the piece where the board says it does.
+        let plans = board.placements(Placement::falling(&tetris), tetris.config());
+        assert!(plans.len() >= 9);
+        for (plan, landing) in plans {
+            let mut played = tetris.clone();
+            for evt in plan {
+                played.event(evt);
+            }
+            let mut ghost = played.view().ghost;
+            let mut cells: Vec<(usize, usize)> = landing
+                .cells()
+                .iter()
+                .map(|&(row, col)| (row as usize, col as usize))
+                .collect();
+            ghost.sort_unstable();
+            cells.sort_unstable();
+            assert_eq!(ghost, cells);
+
+            played.hard_drop();
+            let locked = board.with_piece_locked(landing);
+            assert_eq!(locked.lines(), played.lines());
+            assert_eq!(locked.column_heights(), played.column_heights());
+        }
+    }
+
+    #[test]
+    fn test_clear_and_holes() {
+        let board = board![
+            "#........."
+            ".........."
+            "#########."
+        ];
+        assert_eq!(board.holes(), 1);
+
+        // An upright I in the last column completes the bottom row.
+        let i = Placement {
+            piece: Piece::I,
+            rotation: 0,
+            row: 0,
+            col: NCOLS as i32 - 1,
+        };
+        let locked = board.with_piece_locked(board.drop(i));
+        assert_eq!(locked.lines(), 1);
+        assert_eq!(locked.column_heights()[0], 2);
+        assert_eq!(locked.column_heights()[NCOLS - 1], 3);
+        assert_eq!(locked.holes(), 1);
+    }
+
+    #[test]
+    fn test_garbage() {
+        let mut tetris = Tetris::new();
+        let mut board = Board::from_tetris(&tetris);
+        assert!(board.insert_garbage(&[2, 7]));
+        tetris.insert_garbage(&[2, 7]);
+        assert_eq!(board, Board::from_tetris(&tetris));
+        assert_eq!(board.rows()[NROWS - 1], FULL_ROW & !(1 << 7));
+        assert_eq!(board.column_height(2), 1);
+        assert_eq!(board.column_height(0), 2);
+
+        board.set(NROWS - 1, 7, Cell::Locked(Piece::I));
+        assert!(board.is_row_full(NROWS - 1));
+        assert_eq!(board.clear_full_rows(), 1);
+        assert_eq!(board.lines(), 1);
+        assert_eq!(board.column_heights()[0], 1);
+
+        // Garbage stops at the top of the board.
+        assert!(!board.insert_garbage(&[0; NROWS]));
+        assert_eq!(board.column_height(1), NROWS);
+    }
+
+    #[test]
+    fn test_text() {
+        let text = "#.........\n#########.\n.#########\n";
+        let board: Board = text.parse().unwrap();
+        assert_eq!(board.to_string(), text);
+        assert_eq!(board.column_heights()[0], 3);
+        assert_eq!(board.holes(), 1);
+        assert_eq!(
+            "\n  ..#.......\n"
+                .parse::<Board>()
+                .unwrap()
+                .column_height(2),
+            1
+        );
+        assert_eq!("".parse::<Board>().unwrap().to_string(), "");
+        assert!("#.#".parse::<Board>().is_err());
+        assert!("#........x".parse::<Board>().is_err());
+        assert!("..........\n".repeat(NROWS + 1).parse::<Board>().is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use super::game_state::Action;
+use super::profile;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How long a vote stays open unless `--chat-vote=<ms>` says otherwise.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Twitch lets anyone read chat under a `justinfan` nickname, without an
+/// account.
+const ANONYMOUS_NICK: &str = "justinfan31337";
+
+/// An IRC channel whose messages vote on the next move, for "chat plays
+/// tetris". Each chatter has one vote per interval, and the most voted
+/// move is played when the interval ends.
+pub struct Chat {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    channel: String,
+    interval: Duration,
+}
+
+impl Chat {
+    /// Join the channel named by `--chat=<host>:<port>/<#channel>`, if
+    /// given, e.g. `--chat=irc.chat.twitch.tv:6667/#somestreamer`.
+    pub fn from_args() -> Result<Option<Self>, String> {
+        let target =
+            match profile::args().find_map(|arg| arg.strip_prefix("--chat=").map(String::from)) {
+                Some(target) => target,
+                None => return Ok(None),
+            };
+        let interval = match profile::args()
+            .find_map(|arg| arg.strip_prefix("--chat-vote=").map(String::from))
+        {
+            Some(ms) => ms
+                .parse()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis)
+                .ok_or_else(|| format!("invalid vote interval {:?}", ms))?,
+            None => DEFAULT_INTERVAL,
+        };
+        let (server, channel) = target
+            .split_once('/')
+            .filter(|(server, channel)| !server.is_empty() && channel.starts_with('#'))
+            .ok_or_else(|| format!("expected --chat=<host>:<port>/<#channel>, got {:?}", target))?;
+        Self::connect(server, channel, interval).map(Some)
+    }
+
+    fn connect(server: &str, channel: &str, interval: Duration) -> Result<Self, String> {
+        let writer =
+            TcpStream::connect(server).map_err(|e| format!("can't reach {}: {}", server, e))?;
+        let reader = writer
+            .try_clone()
+            .map(BufReader::new)
+            .map_err(|e| e.to_string())?;
+        let mut chat = Self {
+            reader,
+            writer,
+            channel: channel.to_lowercase(),
+            interval,
+        };
+        chat.send(&format!("NICK {}", ANONYMOUS_NICK))
+            .and_then(|_| chat.send(&format!("USER {} 0 * :tetris-rs", ANONYMOUS_NICK)))
+            .map_err(|e| format!("can't log in to {}: {}", server, e))?;
+        info!(server, channel, "connected to chat");
+        Ok(chat)
+    }
+
+    /// Count votes and pass each interval's winning move to `play`,
+    /// until it returns `false` or the connection drops.
+    pub fn run(mut self, mut play: impl FnMut(Action) -> bool) {
+        let mut votes = Votes::default();
+        let mut closes = Instant::now() + self.interval;
+        loop {
+            let now = Instant::now();
+            if now >= closes {
+                if let Some(event) = votes.close() {
+                    if !play(event) {
+                        return;
+                    }
+                }
+                closes = now + self.interval;
+                continue;
+            }
+            if let Err(e) = self.reader.get_ref().set_read_timeout(Some(closes - now)) {
+                warn!(%e, "chat connection failed");
+                return;
+            }
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    warn!("chat server closed the connection");
+                    return;
+                }
+                Ok(_) => {
+                    if let Err(e) = self.handle(line.trim_end(), &mut votes) {
+                        warn!(%e, "chat connection failed");
+                        return;
+                    }
+                }
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    warn!(%e, "chat connection failed");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, line: &str, votes: &mut Votes) -> io::Result<()> {
+        let message = match Message::parse(line) {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        match message.command {
+            "PING" => self.send(&format!("PONG :{}", message.trailing)),
+            // Welcome: only now may we join.
+            "001" => {
+                let join = format!("JOIN {}", self.channel);
+                self.send(&join)
+            }
+            "PRIVMSG" if message.target.eq_ignore_ascii_case(&self.channel) => {
+                if let Some(event) = vote(message.trailing) {
+                    votes.cast(message.nick, event);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        write!(self.writer, "{}\r\n", line)
+    }
+}
+
+/// The parts of an IRC line that chat voting needs.
+#[derive(Debug, PartialEq, Eq)]
+struct Message<'a> {
+    /// Who sent it, if anyone
+    nick: &'a str,
+    command: &'a str,
+    /// The first parameter, such as the channel of a `PRIVMSG`
+    target: &'a str,
+    /// The last parameter, such as the text of a `PRIVMSG`
+    trailing: &'a str,
+}
+
+impl<'a> Message<'a> {
+    /// Parse `[@tags] [:nick!user@host] COMMAND [params] [:trailing]`.
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line;
+        if rest.starts_with('@') {
+            rest = rest.split_once(' ')?.1;
+        }
+        let mut nick = "";
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (prefix, after) = prefixed.split_once(' ')?;
+            nick = prefix.split('!').next().unwrap_or(prefix);
+            rest = after;
+        }
+        let (params, trailing) = match rest.split_once(" :") {
+            Some((params, trailing)) => (params, trailing),
+            None => (rest, ""),
+        };
+        let mut params = params.split(' ').filter(|p| !p.is_empty());
+        let command = params.next()?;
+        Some(Self {
+            nick,
+            command,
+            target: params.next().unwrap_or(""),
+            trailing,
+        })
+    }
+}
+
+/// The move a chat message votes for, if it is one.
+fn vote(text: &str) -> Option<Action> {
+    match text.trim().to_lowercase().as_str() {
+        "left" | "l" | "a" => Some(Action::MoveLeft),
+        "right" | "r" | "d" => Some(Action::MoveRight),
+        "ccw" | "q" => Some(Action::RotateCcw),
+        "cw" | "rotate" | "e" => Some(Action::RotateCw),
+        _ => None,
+    }
+}
+
+/// The votes of the current interval.
+#[derive(Debug, Default)]
+struct Votes {
+    /// Each chatter's latest vote, and when it was cast relative to the
+    /// others
+    ballots: HashMap<String, (Action, usize)>,
+    /// How many votes have been cast, to number the next one
+    cast: usize,
+}
+
+impl Votes {
+    /// Record a vote, replacing the chatter's earlier one.
+    fn cast(&mut self, nick: &str, event: Action) {
+        self.ballots.insert(nick.to_lowercase(), (event, self.cast));
+        self.cast += 1;
+    }
+
+    /// The move with the most votes, ties going to the one voted for
+    /// first, and start a new interval.
+    fn close(&mut self) -> Option<Action> {
+        let mut tally: Vec<(Action, usize, usize)> = Vec::new();
+        for (event, order) in self.ballots.values() {
+            match tally.iter_mut().find(|(e, _, _)| e == event) {
+                Some((_, count, first)) => {
+                    *count += 1;
+                    *first = (*first).min(*order);
+                }
+                None => tally.push((*event, 1, *order)),
+            }
+        }
+        self.ballots.clear();
+        tally
+            .into_iter()
+            .max_by_key(|(_, count, first)| (*count, std::cmp::Reverse(*first)))
+            .map(|(event, _, _)| event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Message::parse("@badges=x :ann!ann@ann.tmi.twitch.tv PRIVMSG #tetris :Left"),
+            Some(Message {
+                nick: "ann",
+                command: "PRIVMSG",
+                target: "#tetris",
+                trailing: "Left",
+            })
+        );
+        assert_eq!(
+            Message::parse("PING :tmi.twitch.tv").map(|m| (m.command, m.trailing)),
+            Some(("PING", "tmi.twitch.tv"))
+        );
+        assert_eq!(vote(" CW "), Some(Action::RotateCw));
+        assert_eq!(vote("left please"), None);
+    }
+
+    #[test]
+    fn test_votes() {
+        let mut votes = Votes::default();
+        assert_eq!(votes.close(), None);
+        votes.cast("ann", Action::MoveLeft);
+        votes.cast("bob", Action::MoveRight);
+        assert_eq!(votes.close(), Some(Action::MoveLeft));
+        votes.cast("ann", Action::MoveLeft);
+        votes.cast("bob", Action::MoveRight);
+        votes.cast("cid", Action::MoveRight);
+        // Changing your mind replaces your vote rather than adding one.
+        votes.cast("ANN", Action::MoveRight);
+        votes.cast("ann", Action::RotateCw);
+        assert_eq!(votes.close(), Some(Action::MoveRight));
+
+        // A vote cast again still comes before a later one.
+        for _ in 0..20 {
+            votes.cast("ann", Action::MoveLeft);
+            votes.cast("ann", Action::MoveLeft);
+            votes.cast("bob", Action::MoveRight);
+            assert_eq!(votes.close(), Some(Action::MoveLeft));
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use super::game_state::Tetris;
+#[cfg(feature = "std")]
+use super::profile;
+#[cfg(feature = "std")]
+use super::rotation::ROTATION_SYSTEMS;
+use super::rotation::{Kicks, RotationSystem};
+use super::trainer::MIN_GRAVITY;
+use core::time::Duration;
+
+/// The most upcoming pieces a game can preview.
+pub const MAX_PREVIEW: usize = 6;
+
+/// Names of the rule presets, as accepted by `Preset::from_name`.
+pub const PRESETS: [&str; 3] = ["classic", "guideline", "competitive"];
+
+/// Lines to clear for each level.
+pub const LEVEL_LINES: u32 = 10;
+
+/// The last level. Games go on at its speed once they reach it.
+pub const MAX_LEVEL: u32 = 19;
+
+/// Names of the gravity curves, as accepted by `GravityCurve::from_name`.
+pub const GRAVITY_CURVES: [&str; 2] = ["fixed", "guideline"];
+
+/// How gravity speeds up as lines are cleared.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum GravityCurve {
+    /// Gravity stays as set.
+    #[default]
+    Fixed,
+    /// Sped up every level as in the guideline games: level `n` ticks
+    /// `(0.8 - 0.007n)^n` times as long as the first.
+    Guideline,
+}
+
+impl GravityCurve {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(GravityCurve::Fixed),
+            "guideline" => Some(GravityCurve::Guideline),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityCurve::Fixed => "fixed",
+            GravityCurve::Guideline => "guideline",
+        }
+    }
+
+    /// The time between two ticks at `level`, in a game that ticks
+    /// every `gravity` at the first level. It never gets below
+    /// `MIN_GRAVITY`, and levels past `MAX_LEVEL` tick as fast as it.
+    pub fn gravity(self, gravity: Duration, level: u32) -> Duration {
+        match self {
+            GravityCurve::Fixed => gravity,
+            GravityCurve::Guideline => {
+                let level = level.min(MAX_LEVEL);
+                let base = 0.8 - 0.007 * level as f64;
+                // `f64::powi` needs std, and the loop is short.
+                let factor = (0..level).fold(1.0, |factor, _| factor * base);
+                Duration::try_from_secs_f64(gravity.as_secs_f64() * factor)
+                    .unwrap_or(gravity)
+                    .max(MIN_GRAVITY)
+            }
+        }
+    }
+}
+
+/// A named rule set, bundling spawns, kicks, speed and preview.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Preset {
+    /// Close to the NES game at its first level: pieces spawn in their
+    /// first rotation and never kick, one piece is previewed, and they
+    /// fall a row every 0.8s.
+    Classic,
+    /// The guideline games at their first level: flat spawns, kicks off
+    /// walls and the floor, three pieces of preview and a row a second.
+    Guideline,
+    /// Modern versus play: guideline rules with five pieces of preview,
+    /// at twice the speed.
+    Competitive,
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Preset::Classic),
+            "guideline" => Some(Preset::Guideline),
+            "competitive" => Some(Preset::Competitive),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Classic => "classic",
+            Preset::Guideline => "guideline",
+            Preset::Competitive => "competitive",
+        }
+    }
+}
+
+/// Settings for a new game. Build one with the chained setters, then
+/// start the game with `Tetris::with_config`:
+///
+/// ```
+/// use tetris_rs::config::GameConfig;
+/// use tetris_rs::game_state::Tetris;
+///
+/// let tetris = Tetris::with_config(GameConfig::new().seed(42));
+/// assert_eq!(tetris.config().seed, Some(42));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameConfig {
+    /// Seed for the shuffled 7-bag randomizer. Without a seed pieces are
+    /// dealt in a fixed cycle.
+    pub seed: Option<u64>,
+    /// Time between two gravity ticks, at the first level of `curve`.
+    pub gravity: Duration,
+    /// The level the game starts at, up to `MAX_LEVEL`. It goes up
+    /// every `LEVEL_LINES` lines cleared.
+    pub level: u32,
+    /// How gravity speeds up as lines are cleared.
+    pub curve: GravityCurve,
+    /// Decides where and in which rotation new pieces spawn.
+    pub rotation: RotationSystem,
+    /// Wall kicks tried when a piece can't rotate in place.
+    pub kicks: Kicks,
+    /// How many upcoming pieces the player gets to see, up to
+    /// `MAX_PREVIEW`.
+    pub preview: usize,
+    /// Zen games never end: when a piece has no room to spawn, the stack
+    /// is cleared away instead.
+    pub zen: bool,
+    /// Whether `Action::Hold` may put the falling piece aside.
+    pub hold: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            gravity: Duration::from_millis(1000 / 6),
+            level: 0,
+            curve: GravityCurve::default(),
+            rotation: RotationSystem::default(),
+            kicks: Kicks::default(),
+            preview: 1,
+            zen: false,
+            hold: true,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rules of a preset. Pieces still come in a fixed cycle until
+    /// a seed is set.
+    pub fn from_preset(preset: Preset) -> Self {
+        let (rotation, kicks, gravity, preview, hold) = match preset {
+            Preset::Classic => (RotationSystem::Classic, Kicks::default(), 800, 1, false),
+            Preset::Guideline => (RotationSystem::Guideline, Kicks::basic(), 1000, 3, true),
+            Preset::Competitive => (RotationSystem::Guideline, Kicks::basic(), 500, 5, true),
+        };
+        Self::new()
+            .rotation(rotation)
+            .kicks(kicks)
+            .gravity(Duration::from_millis(gravity))
+            .preview(preview)
+            .hold(hold)
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn gravity(self, gravity: Duration) -> Self {
+        Self { gravity, ..self }
+    }
+
+    pub fn level(self, level: u32) -> Self {
+        Self {
+            level: level.min(MAX_LEVEL),
+            ..self
+        }
+    }
+
+    pub fn curve(self, curve: GravityCurve) -> Self {
+        Self { curve, ..self }
+    }
+
+    pub fn rotation(self, rotation: RotationSystem) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn kicks(self, kicks: Kicks) -> Self {
+        Self { kicks, ..self }
+    }
+
+    pub fn preview(self, preview: usize) -> Self {
+        Self {
+            preview: preview.min(MAX_PREVIEW),
+            ..self
+        }
+    }
+
+    pub fn zen(self, zen: bool) -> Self {
+        Self { zen, ..self }
+    }
+
+    pub fn hold(self, hold: bool) -> Self {
+        Self { hold, ..self }
+    }
+
+    /// Read settings from `--preset=<name>`, `--seed=<n>`,
+    /// `--gravity=<ms>`, `--gravity-curve=<name>`, `--level=<n>`,
+    /// `--rotation=<system>`, `--kicks=<file>`, `--preview=<n>`, `--zen`
+    /// and `--no-hold` on the command line. The other options override the preset's rules, and a preset game without a
+    /// seed gets a random one.
+    #[cfg(feature = "std")]
+    pub fn from_args() -> Result<Self, String> {
+        let arg = |prefix: &str| -> Result<Option<u64>, String> {
+            match profile::args().find_map(|arg| arg.strip_prefix(prefix).map(String::from)) {
+                Some(value) => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("invalid {}{:?}, expected a number", prefix, value)),
+                None => Ok(None),
+            }
+        };
+
+        let preset =
+            match profile::args().find_map(|arg| arg.strip_prefix("--preset=").map(String::from)) {
+                Some(name) => Some(Preset::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown preset {:?}, expected one of: {}",
+                        name,
+                        PRESETS.join(", ")
+                    )
+                })?),
+                None => None,
+            };
+        let mut config = preset.map_or_else(Self::new, Self::from_preset);
+        match arg("--seed=")? {
+            Some(seed) => config = config.seed(seed),
+            // Every preset deals its pieces at random.
+            None if preset.is_some() => {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+                config = config.seed(now.map_or(0, |elapsed| elapsed.as_nanos() as u64));
+            }
+            None => (),
+        }
+        if let Some(ms) = arg("--gravity=")? {
+            config = config.gravity(Duration::from_millis(ms.max(1)));
+        }
+        if let Some(name) =
+            profile::args().find_map(|arg| arg.strip_prefix("--gravity-curve=").map(String::from))
+        {
+            let curve = GravityCurve::from_name(&name).ok_or_else(|| {
+                format!(
+                    "unknown gravity curve {:?}, expected one of: {}",
+                    name,
+                    GRAVITY_CURVES.join(", ")
+                )
+            })?;
+            config = config.curve(curve);
+        }
+        if let Some(level) = arg("--level=")? {
+            if level > MAX_LEVEL as u64 {
+                return Err(format!(
+                    "invalid --level={}, the last level is {}",
+                    level, MAX_LEVEL
+                ));
+            }
+            config = config.level(level as u32);
+        }
+        if let Some(name) =
+            profile::args().find_map(|arg| arg.strip_prefix("--rotation=").map(String::from))
+        {
+            let rotation = RotationSystem::from_name(&name).ok_or_else(|| {
+                format!(
+                    "unknown rotation system {:?}, expected one of: {}",
+                    name,
+                    ROTATION_SYSTEMS.join(", ")
+                )
+            })?;
+            config = config.rotation(rotation);
+        }
+        if let Some(path) =
+            profile::args().find_map(|arg| arg.strip_prefix("--kicks=").map(String::from))
+        {
+            config = config.kicks(Kicks::load(&path)?);
+        }
+        if let Some(preview) = arg("--preview=")? {
+            if preview > MAX_PREVIEW as u64 {
+                return Err(format!(
+                    "invalid --preview={}, at most {} pieces can be previewed",
+                    preview, MAX_PREVIEW
+                ));
+            }
+            config = config.preview(preview as usize);
+        }
+        if profile::args().any(|arg| arg == "--zen") {
+            config = config.zen(true);
+        }
+        if profile::args().any(|arg| arg == "--no-hold") {
+            config = config.hold(false);
+        }
+        Ok(config)
+    }
+
+    /// Start a game with these settings.
+    pub fn build(self) -> Tetris {
+        Tetris::with_config(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for name in PRESETS.iter() {
+            assert_eq!(Preset::from_name(name).map(Preset::name), Some(*name));
+        }
+        let classic = GameConfig::from_preset(Preset::Classic);
+        assert_eq!(classic.rotation, RotationSystem::Classic);
+        assert_eq!(classic.kicks, Kicks::default());
+        assert!(!classic.hold);
+        let competitive = GameConfig::from_preset(Preset::Competitive);
+        assert_eq!(competitive.kicks, Kicks::basic());
+        assert_eq!(competitive.preview, 5);
+        assert!(competitive.gravity < GameConfig::from_preset(Preset::Guideline).gravity);
+        assert_eq!(competitive.seed, None);
+    }
+
+    #[test]
+    fn test_gravity_curves() {
+        for name in GRAVITY_CURVES.iter() {
+            assert_eq!(
+                GravityCurve::from_name(name).map(GravityCurve::name),
+                Some(*name)
+            );
+        }
+        let second = Duration::from_secs(1);
+        assert_eq!(GravityCurve::Fixed.gravity(second, 10), second);
+        let curve = GravityCurve::Guideline;
+        assert_eq!(curve.gravity(second, 0), second);
+        let level = |n: u32| curve.gravity(second, n).as_secs_f64();
+        assert!((level(1) - 0.793).abs() < 1e-6);
+        assert!((level(10) - 0.73f64.powi(10)).abs() < 1e-6);
+        let slow = Duration::from_secs(10);
+        let last = curve.gravity(slow, MAX_LEVEL);
+        assert!(last > MIN_GRAVITY);
+        for n in [115, 257, 301, u32::MAX] {
+            assert_eq!(curve.gravity(slow, n), last);
+        }
+        let slowest = Duration::from_secs(u64::MAX);
+        assert_eq!(curve.gravity(slowest, 0), slowest);
+        assert!(curve.gravity(slowest, MAX_LEVEL) < slowest);
+        let tetris = GameConfig::new().curve(curve).level(3).build();
+        assert_eq!(tetris.level(), 3);
+        assert_eq!(tetris.gravity(), curve.gravity(tetris.config().gravity, 3));
+        assert_eq!(GameConfig::new().level(u32::MAX).level, MAX_LEVEL);
+    }
+}
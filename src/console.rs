@@ -0,0 +1,119 @@
+//! The developer console: commands typed in during a game to set up a
+//! situation, for testing, making videos or writing puzzles.
+
+use super::config::MAX_PREVIEW;
+use super::game_state::{Tetris, NROWS};
+use super::garbage::{Garbage, DEFAULT_MESSINESS};
+use super::rotation::piece_from_name;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// What `help` says.
+pub const HELP: &str = "spawn <piece>, garbage <rows>, clearboard, set lines|preview <n>, seed <n>";
+
+/// Runs console commands on a game. Garbage rows are dealt from a
+/// generator of its own, so that asking for them doesn't disturb the
+/// pieces to come.
+#[derive(Debug, Clone)]
+pub struct Console {
+    garbage: Garbage,
+}
+
+impl Console {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            garbage: Garbage::new(seed, DEFAULT_MESSINESS),
+        }
+    }
+
+    /// Carry out `line` on `tetris`, returning what happened to show the
+    /// player, or why nothing did.
+    pub fn run(&mut self, tetris: &mut Tetris, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str| {
+            word.parse::<u64>()
+                .map_err(|_| format!("expected a number, got {:?}", word))
+        };
+        match words.as_slice() {
+            ["help"] => Ok(String::from(HELP)),
+            ["spawn", name] => {
+                let piece = piece_from_name(&name.to_uppercase())
+                    .ok_or_else(|| format!("unknown piece {:?}", name))?;
+                if tetris.swap_piece(piece) {
+                    Ok(format!("spawned {:?}", piece))
+                } else {
+                    Err(format!("no room to spawn {:?}", piece))
+                }
+            }
+            ["garbage", rows] => {
+                let rows = number(rows)?.min(NROWS as u64) as usize;
+                let holes = self.garbage.rows(rows);
+                if tetris.insert_garbage(&holes) {
+                    Ok(format!("added {} garbage rows", rows))
+                } else {
+                    Ok(String::from("the garbage topped out the game"))
+                }
+            }
+            ["clearboard"] => {
+                tetris.clear_stack();
+                Ok(String::from("board cleared"))
+            }
+            ["set", "lines", n] => {
+                let lines =
+                    u32::try_from(number(n)?).map_err(|_| format!("too many lines: {}", n))?;
+                tetris.set_lines(lines);
+                Ok(format!("lines set to {}", tetris.lines()))
+            }
+            ["set", "preview", n] => {
+                tetris.set_preview(number(n)?.min(MAX_PREVIEW as u64) as usize);
+                Ok(format!("preview set to {}", tetris.config().preview))
+            }
+            ["set", "level", _] => Err(String::from(
+                "the game has no levels; try --gravity=<ms> or a --preset",
+            )),
+            ["seed", seed] => {
+                let seed = number(seed)?;
+                tetris.reseed(seed);
+                Ok(format!("dealing from seed {}", seed))
+            }
+            [] => Ok(String::new()),
+            _ => Err(format!("unknown command {:?}, try help", line.trim())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::game_state::Piece;
+
+    #[test]
+    fn test_commands() {
+        let mut console = Console::new(1);
+        let mut tetris = Tetris::new();
+        assert!(console.run(&mut tetris, "spawn t").is_ok());
+        assert_eq!(tetris.piece(), Piece::T);
+        assert!(console.run(&mut tetris, "spawn X").is_err());
+
+        console.run(&mut tetris, "garbage 4").unwrap();
+        assert_eq!(tetris.garbage_rows(), 4);
+        console.run(&mut tetris, "clearboard").unwrap();
+        assert_eq!(tetris.stack_height(), 0);
+        assert_eq!(tetris.piece(), Piece::T);
+
+        console.run(&mut tetris, "set lines 30").unwrap();
+        assert_eq!(tetris.lines(), 30);
+        console.run(&mut tetris, "set preview 9").unwrap();
+        assert_eq!(tetris.preview().len(), MAX_PREVIEW);
+        assert!(console.run(&mut tetris, "set level 15").is_err());
+
+        console.run(&mut tetris, "seed 42").unwrap();
+        let seeded = GameConfig::new().seed(42).build();
+        assert_eq!(tetris.preview()[0], seeded.piece());
+        assert!(console.run(&mut tetris, "seed many").is_err());
+        assert!(console.run(&mut tetris, "fly").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Crash reports: when the game panics, what it was doing is written to
+//! a file, with the inputs to play it again up to the crash, and the
+//! file's path is printed once the terminal is back to normal.
+
+use super::game_state::{GameEvent, Step, Tetris};
+use super::profile;
+use super::tas::Movie;
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many of the last engine events a report lists.
+pub const EVENTS: usize = 50;
+
+/// The game being played, kept up to date for a report.
+#[derive(Debug, Default)]
+struct BlackBox {
+    /// The game as it started
+    start: Option<Tetris>,
+    /// Every input since the start, to play the game up to now again
+    steps: Vec<Step>,
+    /// The last `EVENTS` engine events, oldest first
+    events: VecDeque<GameEvent>,
+}
+
+static BLACK_BOX: Mutex<Option<BlackBox>> = Mutex::new(None);
+
+/// A panic as the hook saw it, until the report on it is written.
+struct Panic {
+    message: String,
+    backtrace: String,
+}
+
+/// The first panic, once there has been one.
+static PANIC: Mutex<Option<Panic>> = Mutex::new(None);
+
+/// Keep any panic for a report, instead of printing it over the game's
+/// screen. The report is written by `take`, once the game has unwound.
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        if let Ok(mut panic) = PANIC.lock() {
+            panic.get_or_insert_with(|| Panic {
+                message: info.to_string(),
+                backtrace: Backtrace::force_capture().to_string(),
+            });
+        }
+    }));
+}
+
+/// Write the report on a panic, if there was one, and say what to tell
+/// the player about it.
+pub fn take() -> Option<String> {
+    let panic = PANIC.lock().ok()?.take()?;
+    Some(match save(&panic) {
+        Ok(path) => format!(
+            "tetris-rs crashed: {}\nA crash report was saved to {}, please attach it to a bug report.",
+            panic.message,
+            path.display()
+        ),
+        Err(e) => format!("tetris-rs crashed: {}\nNo crash report: {}", panic.message, e),
+    })
+}
+
+/// Start keeping track of a new game, or of a game put back to an
+/// earlier state without the steps that led there.
+pub fn start(tetris: &Tetris) {
+    let mut start = tetris.clone();
+    start.record_inputs(false);
+    start.take_inputs();
+    start.take_events();
+    if let Ok(mut black_box) = BLACK_BOX.lock() {
+        *black_box = Some(BlackBox {
+            start: Some(start),
+            ..BlackBox::default()
+        });
+    }
+}
+
+/// Take note of the inputs taken from the game since the last call.
+pub fn record(steps: &[Step]) {
+    if let Ok(mut black_box) = BLACK_BOX.lock() {
+        if let Some(black_box) = black_box.as_mut() {
+            black_box.steps.extend_from_slice(steps);
+        }
+    }
+}
+
+/// Forget the inputs after the first `len`, when the game is rewound
+/// to the state they led to.
+pub fn truncate(len: usize) {
+    if let Ok(mut black_box) = BLACK_BOX.lock() {
+        if let Some(black_box) = black_box.as_mut() {
+            black_box.steps.truncate(len);
+        }
+    }
+}
+
+/// Take note of an engine event.
+pub fn observe(event: GameEvent) {
+    if let Ok(mut black_box) = BLACK_BOX.lock() {
+        if let Some(black_box) = black_box.as_mut() {
+            if black_box.events.len() == EVENTS {
+                black_box.events.pop_front();
+            }
+            black_box.events.push_back(event);
+        }
+    }
+}
+
+/// Write the report on a panic to a new file in the temporary directory.
+fn save(panic: &Panic) -> Result<PathBuf, String> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = std::env::temp_dir().join(format!("tetris-rs-crash-{}.txt", time));
+    std::fs::write(&path, report(panic))
+        .map_err(|e| format!("can't save {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn report(panic: &Panic) -> String {
+    let mut report = format!(
+        "tetris-rs {} crash report\n\n{}\n\nOptions: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        panic.message,
+        profile::args().skip(1).collect::<Vec<_>>().join(" ")
+    );
+    // A panic while the black box was being updated poisons it, but
+    // what it holds is still worth reporting.
+    let black_box = BLACK_BOX.lock().unwrap_or_else(PoisonError::into_inner);
+    match black_box.as_ref() {
+        Some(black_box) => {
+            if let Some(start) = black_box.start.as_ref() {
+                let _ = writeln!(report, "Seed: {:?}", start.config().seed);
+                let _ = writeln!(report, "Config: {:?}", start.config());
+                let _ = write!(report, "\nStart:\n{:?}", start);
+                // The steps led to the crash once, and may well do so
+                // again.
+                let now = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let mut now = start.clone();
+                    for step in &black_box.steps {
+                        now.apply(*step);
+                    }
+                    now
+                }));
+                match now {
+                    Ok(now) => {
+                        let _ = writeln!(report, "\nState (hash {:016x}):", now.state_hash());
+                        let _ = write!(report, "{:?}", now);
+                        let _ =
+                            writeln!(report, "Lines: {}, pieces: {}", now.lines(), now.pieces());
+                    }
+                    Err(_) => report.push_str("\nPlaying the inputs again panics too.\n"),
+                }
+            }
+            let _ = writeln!(report, "\nLast events, oldest first:");
+            for event in &black_box.events {
+                let _ = writeln!(report, "{:?}", event);
+            }
+            let movie = Movie::from_steps(black_box.steps.iter().copied());
+            let moves_only = black_box
+                .steps
+                .iter()
+                .all(|step| matches!(step, Step::Tick | Step::Event(_)));
+            let _ = if moves_only {
+                write!(report, "\nInputs, to play with --tas=<file>:\n{}", movie)
+            } else {
+                write!(
+                    report,
+                    "\nInputs, as a movie without the garbage, speed changes and \
+                     console commands it can't keep:\n{}",
+                    movie
+                )
+            };
+        }
+        None => report.push_str("\nNo game was being played.\n"),
+    }
+    let _ = write!(report, "\nBacktrace:\n{}", panic.backtrace);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::{Action, Piece};
+
+    #[test]
+    fn test_black_box() {
+        let mut tetris = Tetris::new();
+        start(&tetris);
+        tetris.record_inputs(true);
+        tetris.hard_drop();
+        tetris.hard_drop();
+        let inputs = tetris.take_inputs();
+        record(&inputs);
+        truncate(1);
+        for _ in 0..EVENTS + 5 {
+            observe(GameEvent::Locked(Piece::T));
+        }
+        observe(GameEvent::LinesCleared(1));
+        {
+            let black_box = BLACK_BOX.lock().unwrap();
+            let black_box = black_box.as_ref().unwrap();
+            assert_eq!(black_box.steps, vec![Step::Event(Action::HardDrop)]);
+            assert_eq!(black_box.events.len(), EVENTS);
+            assert_eq!(black_box.events.back(), Some(&GameEvent::LinesCleared(1)));
+        }
+
+        // The report plays the inputs again for the state at the crash.
+        let mut now = Tetris::new();
+        now.hard_drop();
+        let panic = Panic {
+            message: String::from("test"),
+            backtrace: String::new(),
+        };
+        assert!(report(&panic).contains(&format!("State (hash {:016x})", now.state_hash())));
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Discord accepts a handful of activity updates per 20 seconds; stay
+/// well below that.
+const MIN_INTERVAL: Duration = Duration::from_secs(15);
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+/// A connection to the local Discord client, publishing what is being
+/// played as Rich Presence. Activities are sent from a thread of their
+/// own, so that a slow client never holds up a frame.
+pub struct Presence {
+    /// Activities for the thread to send, `None` once it has given up
+    tx: Option<Sender<(String, String)>>,
+    /// The last activity handed to the thread
+    shown: Option<(String, String)>,
+}
+
+impl Presence {
+    /// Connect to the running Discord client as the Discord application
+    /// `client_id`.
+    pub fn connect(client_id: &str) -> Result<Self, String> {
+        let mut socket = candidates()
+            .find_map(|path| UnixStream::connect(path).ok())
+            .ok_or("can't find a running Discord client")?;
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .map_err(|e| e.to_string())?;
+        let handshake = format!(r#"{{"v":1,"client_id":{}}}"#, json_string(client_id));
+        send(&mut socket, OP_HANDSHAKE, &handshake)
+            .and_then(|_| receive(&mut socket))
+            .map_err(|e| format!("Discord handshake failed: {}", e))?;
+        info!("connected to Discord");
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || publish(socket, started, rx));
+        Ok(Self {
+            tx: Some(tx),
+            shown: None,
+        })
+    }
+
+    /// Show `details` and `state` as the current activity. Call this as
+    /// often as convenient: only changes are passed on, and after an
+    /// error the connection goes quiet for good.
+    pub fn update(&mut self, details: &str, state: &str) {
+        if let Some((d, s)) = &self.shown {
+            if d == details && s == state {
+                return;
+            }
+        }
+        if let Some(tx) = self.tx.as_ref() {
+            if tx.send((details.to_string(), state.to_string())).is_err() {
+                self.tx = None;
+            }
+        }
+        self.shown = Some((details.to_string(), state.to_string()));
+    }
+}
+
+/// Send the activities received on `rx` until the session ends or the
+/// client stops answering. Each goes out at least `MIN_INTERVAL` after
+/// the last, and when several are waiting only the latest is sent.
+fn publish(mut socket: UnixStream, started: u64, rx: Receiver<(String, String)>) {
+    let mut nonce = 0;
+    let mut sent: Option<Instant> = None;
+    while let Ok(activity) = rx.recv() {
+        if let Some(at) = sent {
+            thread::sleep(MIN_INTERVAL.saturating_sub(at.elapsed()));
+        }
+        let (details, state) = rx.try_iter().last().unwrap_or(activity);
+        nonce += 1;
+        let payload = format!(
+            r#"{{"cmd":"SET_ACTIVITY","args":{{"pid":{},"activity":{{"details":{},"state":{},"timestamps":{{"start":{}}}}}}},"nonce":"{}-{}"}}"#,
+            std::process::id(),
+            json_string(&details),
+            json_string(&state),
+            started,
+            started,
+            nonce
+        );
+        if let Err(e) = send(&mut socket, OP_FRAME, &payload).and_then(|_| receive(&mut socket)) {
+            warn!(%e, "Discord update failed, giving up");
+            return;
+        }
+        sent = Some(Instant::now());
+    }
+}
+
+/// Where the Discord client may be listening.
+fn candidates() -> impl Iterator<Item = PathBuf> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|dir| !dir.is_empty()))
+        .map_or_else(|| PathBuf::from("/tmp"), PathBuf::from);
+    (0..10).map(move |i| dir.join(format!("discord-ipc-{}", i)))
+}
+
+/// A frame is a little-endian opcode and length, then the JSON payload.
+fn frame(opcode: u32, payload: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + payload.len());
+    out.extend_from_slice(&opcode.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload.as_bytes());
+    out
+}
+
+fn send(socket: &mut UnixStream, opcode: u32, payload: &str) -> io::Result<()> {
+    socket.write_all(&frame(opcode, payload))
+}
+
+/// Read and discard the reply to a frame.
+fn receive(socket: &mut UnixStream) -> io::Result<()> {
+    let mut header = [0; 8];
+    socket.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    io::copy(&mut socket.take(len as u64), &mut io::sink())?;
+    Ok(())
+}
+
+/// Quote a string for JSON.
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(json_string("a \"b\"\\\n"), r#""a \"b\"\\\u000a""#);
+        assert_eq!(frame(1, "{}"), vec![1, 0, 0, 0, 2, 0, 0, 0, b'{', b'}']);
+    }
+}
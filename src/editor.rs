@@ -0,0 +1,174 @@
+//! The board editor (`--edit=<file>`): paint cells with a cursor and
+//! line up the piece queue to make a puzzle.
+
+use super::game_state::{Cell, GameView, Piece, NCOLS, NROWS};
+use super::puzzle::Puzzle;
+use super::rotation::piece_from_name;
+use std::path::Path;
+
+/// A puzzle being edited, and where the cursor is.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    pub puzzle: Puzzle,
+    /// (row, col) of the cell under the cursor
+    cursor: (usize, usize),
+    /// Where the puzzle is saved
+    path: String,
+    /// Whether there are changes since the puzzle was last saved
+    modified: bool,
+}
+
+impl Editor {
+    /// Edit the puzzle at `path`, or a new one if there's no file there
+    /// yet. The cursor starts at the bottom left.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let puzzle = if Path::new(path).exists() {
+            Puzzle::load(path)?
+        } else {
+            Puzzle::default()
+        };
+        Ok(Self {
+            puzzle,
+            cursor: (NROWS - 1, 0),
+            path: String::from(path),
+            modified: false,
+        })
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    /// Move the cursor by `rows` down and `cols` right, stopping at the
+    /// edges of the board.
+    pub fn move_cursor(&mut self, rows: i32, cols: i32) {
+        let (row, col) = self.cursor;
+        self.cursor = (
+            (row as i32 + rows).clamp(0, NROWS as i32 - 1) as usize,
+            (col as i32 + cols).clamp(0, NCOLS as i32 - 1) as usize,
+        );
+    }
+
+    /// Fill the cell under the cursor, or empty it if it is filled.
+    pub fn toggle(&mut self) {
+        let (row, col) = self.cursor;
+        let filled = self.puzzle.board.is_filled(row, col);
+        let cell = if filled { Cell::Empty } else { Cell::Garbage };
+        self.puzzle.board.set(row, col, cell);
+        self.modified = true;
+    }
+
+    /// The piece a letter key adds to the queue, if any.
+    pub fn piece_for_key(key: char) -> Option<Piece> {
+        piece_from_name(&key.to_ascii_uppercase().to_string())
+    }
+
+    /// Add `piece` to the end of the queue.
+    pub fn push(&mut self, piece: Piece) {
+        self.puzzle.queue.push(piece);
+        self.modified = true;
+    }
+
+    /// Take the last piece off the queue.
+    pub fn pop(&mut self) {
+        self.modified |= self.puzzle.queue.pop().is_some();
+    }
+
+    /// Empty the board, keeping the queue.
+    pub fn clear(&mut self) {
+        self.puzzle.board = Default::default();
+        self.modified = true;
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        self.puzzle.save(&self.path)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// The board to draw, with the cursor as a T-colored cell.
+    pub fn view(&self) -> GameView {
+        let mut board = [[Cell::Empty; NCOLS]; NROWS];
+        for (row, cells) in board.iter_mut().enumerate() {
+            for (col, cell) in cells.iter_mut().enumerate() {
+                if self.puzzle.board.is_filled(row, col) {
+                    *cell = Cell::Garbage;
+                }
+            }
+        }
+        let stack_height = (0..NCOLS)
+            .map(|col| self.puzzle.board.column_height(col))
+            .max()
+            .unwrap_or(0);
+        GameView {
+            board,
+            piece: Piece::T,
+            active: vec![self.cursor],
+            ghost: Vec::new(),
+            queue: self.puzzle.queue.clone(),
+            hold: None,
+            lines: 0,
+            stack_height,
+        }
+    }
+
+    /// The text beside the board: the file, the queue and the keys.
+    pub fn side_panel(&self) -> Vec<String> {
+        let queue: String = self
+            .puzzle
+            .queue
+            .iter()
+            .map(|piece| format!("{:?}", piece))
+            .collect();
+        let (row, col) = self.cursor;
+        vec![
+            format!("{}{}", self.path, if self.modified { " *" } else { "" }),
+            format!("Queue: {}", if queue.is_empty() { "-" } else { &queue }),
+            format!(
+                "Cell: row {}, column {}, {}",
+                NROWS - row,
+                col + 1,
+                if self.puzzle.board.is_filled(row, col) {
+                    "filled"
+                } else {
+                    "empty"
+                }
+            ),
+            String::new(),
+            String::from("Arrows: move    Space: fill/empty"),
+            String::from("o l j t z s i: add to the queue"),
+            String::from("Backspace: remove from the queue"),
+            String::from("c: clear the board    Enter: save"),
+            String::from("Esc: quit"),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit() {
+        let path = std::env::temp_dir().join(format!("tetris-rs-editor-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut editor = Editor::open(path).unwrap();
+        editor.move_cursor(5, -3);
+        assert_eq!(editor.cursor(), (NROWS - 1, 0));
+        editor.toggle();
+        editor.move_cursor(0, 1);
+        editor.toggle();
+        editor.toggle();
+        editor.push(Editor::piece_for_key('i').unwrap());
+        editor.push(Piece::O);
+        editor.pop();
+        assert_eq!(Editor::piece_for_key('x'), None);
+        assert_eq!(editor.view().cell(NROWS - 1, 0), Cell::Garbage);
+        assert_eq!(editor.view().stack_height, 1);
+        editor.save().unwrap();
+
+        let reopened = Editor::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(reopened.puzzle.to_string(), "queue I\n#.........\n");
+    }
+}
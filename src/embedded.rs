@@ -0,0 +1,160 @@
+use super::game_state::{Cell, GameView, GARBAGE_COLOR, GUIDELINE_COLORS, NCOLS, NROWS};
+use embedded_graphics::pixelcolor::{BinaryColor, Rgb888};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+/// The colors a game is drawn in on an embedded display.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Palette<C> {
+    /// Color of each piece, indexed by `Piece as usize`.
+    pub pieces: [C; 7],
+    /// Color of garbage cells.
+    pub garbage: C,
+    /// Color of the cells where the falling piece would land, or `None`
+    /// to leave them empty.
+    pub ghost: Option<C>,
+    /// Color of empty cells.
+    pub background: C,
+}
+
+impl Palette<Rgb888> {
+    /// The guideline piece colors of the terminal's default theme, for
+    /// RGB LED matrices and color displays. Convert with `into_color`
+    /// for displays with fewer bits per pixel.
+    pub fn guideline() -> Self {
+        let rgb = |(r, g, b): (u8, u8, u8)| Rgb888::new(r, g, b);
+        Self {
+            pieces: GUIDELINE_COLORS.map(rgb),
+            garbage: rgb(GARBAGE_COLOR),
+            // LEDs are bright enough without lighting the ghost fully.
+            ghost: Some(Rgb888::new(0x30, 0x30, 0x30)),
+            background: Rgb888::BLACK,
+        }
+    }
+}
+
+impl Palette<BinaryColor> {
+    /// Every block lit and nothing else, for monochrome displays such
+    /// as the SSD1306.
+    pub fn monochrome() -> Self {
+        Self {
+            pieces: [BinaryColor::On; 7],
+            garbage: BinaryColor::On,
+            ghost: None,
+            background: BinaryColor::Off,
+        }
+    }
+}
+
+impl<C: Copy> Palette<C> {
+    /// The same palette in another color type, e.g. `Rgb565`.
+    pub fn into_color<D: From<C>>(self) -> Palette<D> {
+        Palette {
+            pieces: self.pieces.map(D::from),
+            garbage: self.garbage.into(),
+            ghost: self.ghost.map(D::from),
+            background: self.background.into(),
+        }
+    }
+
+    fn color(&self, view: &GameView, row: usize, col: usize) -> C {
+        match view.cell(row, col) {
+            Cell::Locked(piece) => self.pieces[piece as usize],
+            Cell::Garbage => self.garbage,
+            Cell::Empty => match self.ghost {
+                Some(ghost) if view.is_ghost(row, col) => ghost,
+                _ => self.background,
+            },
+        }
+    }
+}
+
+/// The board of a game as an embedded-graphics drawable, one pixel per
+/// cell by default: a 10x20 RGB LED matrix shows it as is, and larger
+/// displays can scale it up with `cell_size`.
+pub struct GridDrawable<'a, C> {
+    view: &'a GameView,
+    palette: &'a Palette<C>,
+    /// Where the top left cell goes
+    origin: Point,
+    /// Side length of a cell, in pixels
+    cell_size: u32,
+}
+
+impl<'a, C> GridDrawable<'a, C> {
+    pub fn new(view: &'a GameView, palette: &'a Palette<C>) -> Self {
+        Self {
+            view,
+            palette,
+            origin: Point::zero(),
+            cell_size: 1,
+        }
+    }
+
+    pub fn origin(self, origin: Point) -> Self {
+        Self { origin, ..self }
+    }
+
+    pub fn cell_size(self, cell_size: u32) -> Self {
+        Self {
+            cell_size: cell_size.max(1),
+            ..self
+        }
+    }
+
+    /// The area the board covers.
+    pub fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(
+            self.origin,
+            Size::new(NCOLS as u32, NROWS as u32) * self.cell_size,
+        )
+    }
+}
+
+impl<C: PixelColor> Drawable for GridDrawable<'_, C> {
+    type Color = C;
+    type Output = ();
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let size = self.cell_size;
+        for row in 0..NROWS {
+            for col in 0..NCOLS {
+                let corner = self.origin + Point::new(col as i32, row as i32) * size as i32;
+                let cell = Rectangle::new(corner, Size::new_equal(size));
+                target.fill_solid(&cell, self.palette.color(self.view, row, col))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::pixelcolor::Rgb565;
+
+    #[test]
+    fn test_draw_grid() {
+        let view = GameConfig::new().build().view();
+        let palette = Palette::monochrome();
+        let mut display = MockDisplay::new();
+        let grid = GridDrawable::new(&view, &palette)
+            .origin(Point::new(1, 2))
+            .cell_size(2);
+        grid.draw(&mut display).unwrap();
+        assert_eq!(display.affected_area(), grid.bounding_box());
+        for &(row, col) in &view.active {
+            let corner = Point::new(1 + 2 * col as i32, 2 + 2 * row as i32);
+            assert_eq!(display.get_pixel(corner), Some(BinaryColor::On));
+        }
+        assert_eq!(display.get_pixel(Point::new(1, 41)), Some(BinaryColor::Off));
+
+        let rgb565: Palette<Rgb565> = Palette::guideline().into_color();
+        assert_eq!(rgb565.background, Rgb565::BLACK);
+    }
+}
@@ -0,0 +1,207 @@
+//! `tetris-rs eval`: plays many seeded bot games without a terminal and
+//! reports how they went as JSON, so that changes to the bot or the
+//! rules can be tracked over time, e.g. in CI.
+
+use super::ai::{self, Difficulty, Strength, Weights};
+use super::flags::{self, number};
+use std::ops::Range;
+
+pub const USAGE: &str = "usage: tetris-rs eval [--games=N] [--bot=LEVEL] [--ai=PATH] \
+                         [--seed-range=START..END] [--pieces=N] [--out=PATH]";
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    /// Name of the bot's level, for the report
+    pub level: &'static str,
+    pub strength: Strength,
+    /// One game is played on each seed.
+    pub seeds: Range<u64>,
+    /// Pieces per game; a game also ends when the bot tops out.
+    pub pieces: u32,
+    /// Where to write the report, instead of standard output
+    pub out: Option<String>,
+}
+
+impl Options {
+    /// Parse the arguments after `eval`. Without a seed range, games are
+    /// played on seeds counting up from 0.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut difficulty = Difficulty::Hard;
+        let mut weights = None;
+        let mut games = None;
+        let mut seeds = None;
+        let mut pieces = 500;
+        let mut out = None;
+        for (name, value) in flags::parse(args)? {
+            let name = name.as_str();
+            match name {
+                "--games" => games = Some(number::<u64>(name, &value)?),
+                "--bot" => difficulty = value.parse()?,
+                "--ai" => weights = Some(Weights::load(&value)?),
+                "--seed-range" => {
+                    let (start, end) = value.split_once("..").ok_or_else(|| {
+                        format!("invalid --seed-range={:?}, expected START..END", value)
+                    })?;
+                    seeds = Some(number(name, start)?..number(name, end)?);
+                }
+                "--pieces" => pieces = number(name, &value)?,
+                "--out" => out = Some(value),
+                _ => return Err(format!("unknown option {:?}", name)),
+            }
+        }
+        let mut seeds = seeds.unwrap_or(0..games.unwrap_or(100));
+        if let Some(games) = games {
+            seeds.end = seeds.end.min(seeds.start.saturating_add(games));
+        }
+        let mut strength = difficulty.strength();
+        if let Some(weights) = weights {
+            strength.weights = weights;
+        }
+        Ok(Self {
+            level: difficulty.name(),
+            strength,
+            seeds,
+            pieces,
+            out,
+        })
+    }
+}
+
+/// How one game went.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Outcome {
+    pub seed: u64,
+    pub lines: u32,
+    /// Pieces locked before the game ended
+    pub pieces: u32,
+    pub topped_out: bool,
+}
+
+/// Play a game on every seed.
+pub fn run(options: &Options) -> Vec<Outcome> {
+    options
+        .seeds
+        .clone()
+        .map(|seed| {
+            let tetris = ai::simulate(options.strength, seed, options.pieces);
+            Outcome {
+                seed,
+                lines: tetris.lines(),
+                pieces: tetris.pieces(),
+                topped_out: tetris.top_out().is_some(),
+            }
+        })
+        .collect()
+}
+
+/// The mean and quartiles of some counts, as a JSON object.
+fn distribution(mut values: Vec<u32>) -> String {
+    if values.is_empty() {
+        return String::from("null");
+    }
+    values.sort_unstable();
+    let at = |fraction: f64| values[((values.len() - 1) as f64 * fraction).round() as usize];
+    let mean = values.iter().map(|v| *v as f64).sum::<f64>() / values.len() as f64;
+    format!(
+        r#"{{"mean":{:.2},"min":{},"p25":{},"median":{},"p75":{},"max":{}}}"#,
+        mean,
+        at(0.0),
+        at(0.25),
+        at(0.5),
+        at(0.75),
+        at(1.0)
+    )
+}
+
+/// The report on a batch of games: the settings, the distributions of
+/// lines cleared and pieces survived, and every game on its own line.
+pub fn report(options: &Options, outcomes: &[Outcome]) -> String {
+    let Weights {
+        lines,
+        height,
+        holes,
+        bumpiness,
+    } = options.strength.weights;
+    let topped_out = outcomes.iter().filter(|o| o.topped_out).count();
+    let games: Vec<String> = outcomes
+        .iter()
+        .map(|o| {
+            format!(
+                r#"    {{"seed":{},"lines":{},"pieces":{},"topped_out":{}}}"#,
+                o.seed, o.lines, o.pieces, o.topped_out
+            )
+        })
+        .collect();
+    format!(
+        concat!(
+            "{{\n",
+            r#"  "bot":{{"level":"{}","weights":{{"lines":{},"height":{},"holes":{},"bumpiness":{}}}}},"#,
+            "\n",
+            r#"  "pieces_per_game":{},"#,
+            "\n",
+            r#"  "games":{},"#,
+            "\n",
+            r#"  "topped_out":{},"#,
+            "\n",
+            r#"  "lines":{},"#,
+            "\n",
+            r#"  "pieces":{},"#,
+            "\n",
+            r#"  "results":["#,
+            "\n{}\n  ]\n}}\n"
+        ),
+        options.level,
+        lines,
+        height,
+        holes,
+        bumpiness,
+        options.pieces,
+        outcomes.len(),
+        topped_out,
+        distribution(outcomes.iter().map(|o| o.lines).collect()),
+        distribution(outcomes.iter().map(|o| o.pieces).collect()),
+        games.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn test_options() {
+        let options = Options::parse(args(&["--games=3", "--seed-range=10..20"])).unwrap();
+        assert_eq!(options.seeds, 10..13);
+        assert_eq!(options.level, "hard");
+        let options = Options::parse(args(&["--games=5", "--bot=easy"])).unwrap();
+        assert_eq!(options.seeds, 0..5);
+        assert_eq!(options.strength, Difficulty::Easy.strength());
+        let options = Options::parse(args(&["--games", "5", "--pieces", "20"])).unwrap();
+        assert_eq!((options.seeds, options.pieces), (0..5, 20));
+        assert!(Options::parse(args(&["--pieces=4294967296"])).is_err());
+        assert!(Options::parse(args(&["--bot=expert"])).is_err());
+        assert!(Options::parse(args(&["--seed-range=5"])).is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        assert_eq!(
+            distribution(vec![4, 1, 3, 2, 5]),
+            r#"{"mean":3.00,"min":1,"p25":2,"median":3,"p75":4,"max":5}"#
+        );
+
+        let options = Options::parse(args(&["--seed-range=1..3", "--pieces=20"])).unwrap();
+        let outcomes = run(&options);
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(|o| o.pieces == 20 && !o.topped_out));
+        let report = report(&options, &outcomes);
+        assert!(report.starts_with("{\n  \"bot\":{\"level\":\"hard\",\"weights\":{\"lines\":"));
+        assert!(report.contains(r#""games":2,"#));
+        assert!(report.contains(r#"{"seed":2,"lines":"#));
+        assert!(report.ends_with("  ]\n}\n"));
+    }
+}
@@ -0,0 +1,53 @@
+use super::game_state::{GameView, NCOLS, NROWS};
+use super::graphics;
+use super::theme::{Rgb, Theme};
+
+/// The board drawn with 24-bit ANSI background colors, two spaces per
+/// cell, with `stats` beside it: something to paste into a terminal or
+/// a chat that keeps escape sequences.
+pub fn ansi(view: &GameView, theme: &Theme, stats: &[String]) -> String {
+    let mut out = String::new();
+    for row in 0..NROWS {
+        for col in 0..NCOLS {
+            let Rgb(r, g, b) = theme.view_rgb(view, row, col);
+            out += &format!("\x1b[48;2;{};{};{}m  ", r, g, b);
+        }
+        out += "\x1b[0m";
+        if let Some(line) = stats.get(row) {
+            out += "  ";
+            out += line;
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// Save a summary of a finished game to `path`: a PNG of the board with
+/// the stats as metadata if the name ends in `.png`, the ANSI art
+/// otherwise.
+pub fn save(path: &str, view: &GameView, theme: &Theme, stats: &[String]) -> Result<(), String> {
+    let contents = if path.to_ascii_lowercase().ends_with(".png") {
+        graphics::png_file(view, theme, &[("Comment", &stats.join("\n"))])
+    } else {
+        ansi(view, theme, stats).into_bytes()
+    };
+    std::fs::write(path, contents).map_err(|e| format!("can't save {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::Tetris;
+
+    #[test]
+    fn test_ansi() {
+        let view = Tetris::new().view();
+        let stats = vec![String::from("Lines: 0")];
+        let art = ansi(&view, &Theme::unicode(), &stats);
+        let lines: Vec<&str> = art.lines().collect();
+        assert_eq!(lines.len(), NROWS);
+        assert!(lines[0].ends_with("\x1b[0m  Lines: 0"));
+        assert!(lines[1].ends_with("\x1b[0m"));
+        assert_eq!(lines[0].matches("  ").count(), NCOLS + 1);
+    }
+}
@@ -0,0 +1,56 @@
+//! Options of the headless tools, `tetris-rs eval`, `tetris-arena` and
+//! `tetris-tune`, given as `--name=value` or `--name value`.
+
+use std::str::FromStr;
+
+/// Pair every option name in `args` with its value.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Vec<(String, String)>, String> {
+    let mut args = args.into_iter();
+    let mut options = Vec::new();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(format!("unexpected argument {:?}", arg));
+        }
+        let option = match arg.split_once('=') {
+            Some((name, value)) => (String::from(name), String::from(value)),
+            None => match args.next() {
+                Some(value) => (arg, value),
+                None => return Err(format!("missing value for {}", arg)),
+            },
+        };
+        options.push(option);
+    }
+    Ok(options)
+}
+
+/// The value of option `name` as a number, which has to fit in `T`.
+pub fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {}={:?}, expected a number", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = parse(args(&["--games=3", "--pieces", "20"])).unwrap();
+        assert_eq!(
+            options,
+            [
+                (String::from("--games"), String::from("3")),
+                (String::from("--pieces"), String::from("20"))
+            ]
+        );
+        assert!(parse(args(&["games=3"])).is_err());
+        assert!(parse(args(&["--games"])).is_err());
+        assert_eq!(number::<u32>("--pieces", "20"), Ok(20));
+        assert!(number::<u32>("--pieces", "4294967296").is_err());
+    }
+}
@@ -1,9 +1,14 @@
-use lazy_static::lazy_static;
-use std::cmp::{max, min};
-use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::fmt;
+use super::board::{Board, Placement};
+use super::config::{GameConfig, LEVEL_LINES, MAX_LEVEL, MAX_PREVIEW};
+use super::objective::{Goal, Objective, Verdict};
+use super::randomizer::Randomizer;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::time::Duration;
+use tracing::{debug, info};
 
 type Offsets = (i32, i32);
 
@@ -14,74 +19,70 @@ type Offsets = (i32, i32);
 /// degrees.
 type Offsets4 = [Offsets; 4];
 
-lazy_static! {
-    static ref ROTATION_OFFSETS: HashMap<Piece, [Offsets4; 4]> = {
-        let mut data = HashMap::new();
-        // ##
-        // ##
-        data.insert(Piece::O,
-                    [[(0, 0), (0, 1), (1, 0), (1, 1)],
-                     [(0, 0), (0, 1), (1, 0), (1, 1)],
-                     [(0, 0), (0, 1), (1, 0), (1, 1)],
-                     [(0, 0), (0, 1), (1, 0), (1, 1)]]);
-
-        // #        ##
-        // #   ###   #    #
-        // ##, #  ,  #, ###
-        data.insert(Piece::L,
-                    [[(0, 0), (1, 0), (2, 0), (2, 1)],
-                     [(1, 0), (1, 1), (1, 2), (2, 0)],
-                     [(0, 0), (0, 1), (1, 1), (2, 1)],
-                     [(2, 0), (2, 1), (2, 2), (1, 2)]]);
-
-        //  #       ##
-        //  #  #    #   ###
-        // ##, ###, # ,   #
-        data.insert(Piece::J,
-                    [[(2, 0), (2, 1), (0, 1), (1, 1)],
-                     [(1, 0), (2, 0), (2, 1), (2, 2)],
-                     [(0, 0), (1, 0), (2, 0), (0, 1)],
-                     [(1, 0), (1, 1), (1, 2), (2, 2)]]);
-
-        // ###   #   #   #
-        //  #   ##  ###  ##
-        //    ,  #,    , #
-        data.insert(Piece::T,
-                    [[(0, 0), (0, 1), (0, 2), (1, 1)],
-                     [(1, 0), (0, 1), (1, 1), (2, 1)],
-                     [(1, 0), (0, 1), (1, 1), (1, 2)],
-                     [(0, 0), (1, 0), (2, 0), (1, 1)]]);
-
-        // ##     #   ##     #
-        //  ##   ##    ##   ##
-        //    ,  #  ,     , #
-        data.insert(Piece::Z,
-                    [[(0, 0), (0, 1), (1, 1), (1, 2)],
-                     [(1, 0), (0, 1), (1, 1), (2, 0)],
-                     [(0, 0), (0, 1), (1, 1), (1, 2)],
-                     [(1, 0), (0, 1), (1, 1), (2, 0)]]);
-
-        //  ##  #     ##   #
-        // ##   ##   ##    ##
-        //    ,  # ,     ,  #
-        data.insert(Piece::S,
-                    [[(1, 0), (0, 1), (1, 1), (0, 2)],
-                     [(0, 0), (1, 0), (1, 1), (2, 1)],
-                     [(1, 0), (0, 1), (1, 1), (0, 2)],
-                     [(0, 0), (1, 0), (1, 1), (2, 1)]]);
-
-        // #         #
-        // #  #####  #  ####
-        // #         #
-        // #,      , #,
-        data.insert(Piece::I,
-                    [[(0, 0), (1, 0), (2, 0), (3, 0)],
-                     [(1, 0), (1, 1), (1, 2), (1, 3)],
-                     [(0, 0), (1, 0), (2, 0), (3, 0)],
-                     [(1, 0), (1, 1), (1, 2), (1, 3)]]);
-
-        data
-    };
+/// The offsets of each piece, indexed by `Piece as usize`.
+#[rustfmt::skip]
+const ROTATION_OFFSETS: [[Offsets4; 4]; 7] = [
+    // ##
+    // ##
+    [[(0, 0), (0, 1), (1, 0), (1, 1)],
+     [(0, 0), (0, 1), (1, 0), (1, 1)],
+     [(0, 0), (0, 1), (1, 0), (1, 1)],
+     [(0, 0), (0, 1), (1, 0), (1, 1)]],
+
+    // #        ##
+    // #   ###   #    #
+    // ##, #  ,  #, ###
+    [[(0, 0), (1, 0), (2, 0), (2, 1)],
+     [(1, 0), (1, 1), (1, 2), (2, 0)],
+     [(0, 0), (0, 1), (1, 1), (2, 1)],
+     [(2, 0), (2, 1), (2, 2), (1, 2)]],
+
+    //  #       ##
+    //  #  #    #   ###
+    // ##, ###, # ,   #
+    [[(2, 0), (2, 1), (0, 1), (1, 1)],
+     [(1, 0), (2, 0), (2, 1), (2, 2)],
+     [(0, 0), (1, 0), (2, 0), (0, 1)],
+     [(1, 0), (1, 1), (1, 2), (2, 2)]],
+
+    // ###   #   #   #
+    //  #   ##  ###  ##
+    //    ,  #,    , #
+    [[(0, 0), (0, 1), (0, 2), (1, 1)],
+     [(1, 0), (0, 1), (1, 1), (2, 1)],
+     [(1, 0), (0, 1), (1, 1), (1, 2)],
+     [(0, 0), (1, 0), (2, 0), (1, 1)]],
+
+    // ##     #   ##     #
+    //  ##   ##    ##   ##
+    //    ,  #  ,     , #
+    [[(0, 0), (0, 1), (1, 1), (1, 2)],
+     [(1, 0), (0, 1), (1, 1), (2, 0)],
+     [(0, 0), (0, 1), (1, 1), (1, 2)],
+     [(1, 0), (0, 1), (1, 1), (2, 0)]],
+
+    //  ##  #     ##   #
+    // ##   ##   ##    ##
+    //    ,  # ,     ,  #
+    [[(1, 0), (0, 1), (1, 1), (0, 2)],
+     [(0, 0), (1, 0), (1, 1), (2, 1)],
+     [(1, 0), (0, 1), (1, 1), (0, 2)],
+     [(0, 0), (1, 0), (1, 1), (2, 1)]],
+
+    // #         #
+    // #  #####  #  ####
+    // #         #
+    // #,      , #,
+    [[(0, 0), (1, 0), (2, 0), (3, 0)],
+     [(1, 0), (1, 1), (1, 2), (1, 3)],
+     [(0, 0), (1, 0), (2, 0), (3, 0)],
+     [(1, 0), (1, 1), (1, 2), (1, 3)]],
+];
+
+/// The cells a piece covers in a rotation, as (row, col) offsets from
+/// its anchor.
+pub(crate) fn piece_offsets(piece: Piece, rotation: i32) -> [(i32, i32); 4] {
+    ROTATION_OFFSETS[piece as usize][rotation as usize]
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
@@ -95,14 +96,47 @@ pub enum Piece {
     I,
 }
 
+/// The guideline color of each piece as `(red, green, blue)`, indexed
+/// by `Piece as usize`, for the frontends to draw pieces in.
+pub const GUIDELINE_COLORS: [(u8, u8, u8); 7] = [
+    (0xf0, 0xf0, 0x00),
+    (0xf0, 0xa0, 0x00),
+    (0x00, 0x00, 0xf0),
+    (0xa0, 0x00, 0xf0),
+    (0xf0, 0x00, 0x00),
+    (0x00, 0xf0, 0x00),
+    (0x00, 0xf0, 0xf0),
+];
+/// The color garbage goes with `GUIDELINE_COLORS`.
+pub const GARBAGE_COLOR: (u8, u8, u8) = (0x80, 0x80, 0x80);
+
+/// Something the player does to the game.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum Event {
-    Left = 0,
-    Right,
-    CounterClock,
-    Clock,
+pub enum Action {
+    MoveLeft = 0,
+    MoveRight,
+    RotateCcw,
+    RotateCw,
+    /// Turn the falling piece half way round, where it stands
+    Rotate180,
+    /// Move the falling piece down a row, without locking it
+    SoftDrop,
+    /// Drop the falling piece as far as it goes and lock it
+    HardDrop,
+    /// Swap the falling piece for the held one, or for the next piece
+    /// if none is held yet. Once per piece.
+    Hold,
+    /// Stop the game, or carry on once stopped
+    Pause,
+    /// Start over with a new game on the same settings
+    Restart,
 }
 
+/// The name of `Action` from when the player could only move and turn
+/// pieces.
+#[deprecated(note = "renamed to `Action`")]
+pub type Event = Action;
+
 impl TryFrom<i32> for Piece {
     type Error = ();
 
@@ -120,14 +154,205 @@ impl TryFrom<i32> for Piece {
     }
 }
 
+/// The content of a single grid cell.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Cell {
+    Empty,
+    /// Occupied by (part of) the given piece
+    Locked(Piece),
+    /// Occupied by a garbage block not belonging to any piece
+    Garbage,
+}
+
+impl Cell {
+    pub fn is_filled(self) -> bool {
+        self != Cell::Empty
+    }
+}
+
 pub const NCOLS: usize = 10;
 pub const NROWS: usize = 20;
-pub type Grid = [[i32; NCOLS]; NROWS];
+/// The content of every cell, top to bottom.
+pub type Grid = [[Cell; NCOLS]; NROWS];
+/// The stack is in danger of topping out once it reaches into this
+/// many rows at the top of the board.
+pub const DANGER_ROWS: usize = 4;
+
+/// The rule that ended a game.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TopOut {
+    /// The next piece overlaps the stack where it spawns.
+    BlockOut,
+    /// Garbage pushed the stack or the falling piece out of the top.
+    GarbageOut,
+    /// The game's objective can no longer be met.
+    Failed,
+}
+
+impl fmt::Display for TopOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopOut::BlockOut => f.write_str("block out: no room to spawn the next piece"),
+            TopOut::GarbageOut => f.write_str("garbage pushed the stack out of the top"),
+            TopOut::Failed => f.write_str("objective failed"),
+        }
+    }
+}
+
+/// Something noteworthy that happened in the engine, for frontends to
+/// react to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum GameEvent {
+    /// A piece locked, before any rows it completed are cleared.
+    Locked(Piece),
+    /// A piece locked and completed this many rows.
+    LinesCleared(u32),
+    /// A line clear left the board completely empty.
+    PerfectClear,
+    /// A zen game cleared the stack away instead of ending.
+    StackCleared,
+    /// A move or turn was asked for, but the piece didn't fit there.
+    Blocked(Action),
+}
+
+/// How a game ended.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Ending {
+    /// The game's objective was met.
+    Completed,
+    /// The game was lost, by this rule.
+    TopOut(TopOut),
+}
+
+/// What a gravity tick did, as returned by `Tetris::tick`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TickOutcome {
+    /// The falling piece dropped a row, or nothing moved because the
+    /// game is paused.
+    Continued,
+    /// The falling piece locked, completing `cleared` rows, and the
+    /// next one spawned.
+    PieceLocked { cleared: u32 },
+    /// The game is over, on this tick or an earlier one.
+    GameOver(Ending),
+}
+
+impl TickOutcome {
+    /// Whether the game goes on after the tick.
+    pub fn continues(self) -> bool {
+        !matches!(self, TickOutcome::GameOver(_))
+    }
+}
+
+/// One input to the engine, as recorded for replays.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Step {
+    Tick,
+    Event(Action),
+    /// A garbage row rose with its hole in this column.
+    Garbage(usize),
+    /// The falling piece was swapped for this one, see
+    /// `Tetris::swap_piece`.
+    Spawn(Piece),
+    /// The board was emptied but for the falling piece.
+    ClearStack,
+    /// Gravity changed to tick this often, see `Tetris::set_gravity`.
+    Gravity(Duration),
+    /// The count of lines cleared was set, see `Tetris::set_lines`.
+    SetLines(u32),
+    /// The preview was set to this many pieces, see
+    /// `Tetris::set_preview`.
+    SetPreview(usize),
+    /// The pieces to come were dealt from a new seed, see
+    /// `Tetris::reseed`.
+    Reseed(u64),
+}
+
+/// 64-bit FNV-1a, for `Tetris::state_hash`. Unlike `DefaultHasher` its
+/// output is specified, so it is the same on every platform and Rust
+/// version.
+pub(crate) struct StateHasher(u64);
+
+impl StateHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// A read-only snapshot of everything a frontend needs to draw a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameView {
+    /// The settled stack, without the falling piece
+    pub board: Grid,
+    /// The type of the falling piece
+    pub piece: Piece,
+    /// (row, col) of each cell of the falling piece
+    pub active: Vec<(usize, usize)>,
+    /// (row, col) of each cell where the falling piece would land
+    pub ghost: Vec<(usize, usize)>,
+    /// Upcoming pieces, the next one first
+    pub queue: Vec<Piece>,
+    /// The piece put aside with `Action::Hold`, if any
+    pub hold: Option<Piece>,
+    /// Total number of rows cleared so far
+    pub lines: u32,
+    /// Height of the settled stack in its highest column
+    pub stack_height: usize,
+}
+
+impl GameView {
+    /// Whether the stack has grown into the top `DANGER_ROWS` rows.
+    pub fn in_danger(&self) -> bool {
+        self.stack_height > NROWS - DANGER_ROWS
+    }
+
+    /// The content of a cell, including the falling piece.
+    pub fn cell(&self, row: usize, col: usize) -> Cell {
+        if self.active.contains(&(row, col)) {
+            Cell::Locked(self.piece)
+        } else {
+            self.board[row][col]
+        }
+    }
+
+    /// Whether the cell is empty but covered by the ghost piece.
+    pub fn is_ghost(&self, row: usize, col: usize) -> bool {
+        !self.cell(row, col).is_filled() && self.ghost.contains(&(row, col))
+    }
+
+    /// The view as seen in a mirror, its columns right to left.
+    pub fn mirrored(&self) -> Self {
+        let flip = |cells: &[(usize, usize)]| {
+            cells
+                .iter()
+                .map(|&(row, col)| (row, NCOLS - 1 - col))
+                .collect()
+        };
+        let mut board = self.board;
+        for row in board.iter_mut() {
+            row.reverse();
+        }
+        Self {
+            board,
+            active: flip(&self.active),
+            ghost: flip(&self.ghost),
+            queue: self.queue.clone(),
+            ..*self
+        }
+    }
+}
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct Tetris {
-    /// The current NROWS x NCOLS tetris
-    pub(super) grid: Grid,
+    /// The settled stack, with the count of rows cleared
+    pub(super) board: Board,
     /// The type of current falling piece
     pub(super) piece: Piece,
     /// The rotation state of current falling piece
@@ -136,6 +361,40 @@ pub struct Tetris {
     pub(super) anchor_row: i32,
     /// The anchor col coordinate of falling piece
     pub(super) anchor_col: i32,
+    /// Number of pieces locked so far
+    pub(super) pieces: u32,
+    /// How many of each piece have spawned, indexed by `Piece as usize`
+    pub(super) dealt: [u32; 7],
+    /// The piece that spawns after the falling one locks
+    pub(super) next: Piece,
+    /// The piece put aside with `Action::Hold`
+    pub(super) hold: Option<Piece>,
+    /// Whether the falling piece may still be held
+    pub(super) can_hold: bool,
+    /// While paused, gravity stops and only `Action::Pause` and
+    /// `Action::Restart` do anything
+    pub(super) paused: bool,
+    /// Source of the pieces after `next`
+    pub(super) randomizer: Randomizer,
+    /// The settings this game was started with, with the gravity it
+    /// has now
+    pub(super) config: GameConfig,
+    /// Gravity ticks played, hard drops and pauses aside
+    pub(super) ticks: u32,
+    /// Game time played: the gravity interval summed over the ticks
+    pub(super) time: Duration,
+    /// What it takes to win, judged after each lock
+    pub(super) objective: Option<Goal>,
+    /// Whether the objective was met, which ends the game
+    pub(super) completed: bool,
+    /// Why the game ended, once it has
+    pub(super) top_out: Option<TopOut>,
+    /// Events not yet collected by `take_events`
+    pub(super) events: Vec<GameEvent>,
+    /// Whether inputs are logged for `take_inputs`
+    pub(super) recording: bool,
+    /// Inputs not yet collected by `take_inputs`
+    pub(super) inputs: Vec<Step>,
 }
 
 impl fmt::Debug for Tetris {
@@ -148,7 +407,11 @@ impl fmt::Debug for Tetris {
         for row in 0usize..NROWS {
             let mut row_str = String::new();
             for col in 0usize..NCOLS {
-                row_str += &self.grid[row][col].to_string();
+                row_str += if self.cell(row, col).is_filled() {
+                    "1"
+                } else {
+                    "0"
+                };
             }
             row_str += "\n";
             f.write_str(row_str.as_str())?;
@@ -157,99 +420,393 @@ impl fmt::Debug for Tetris {
     }
 }
 
-/// Paint or clear the grid for the given piece with given rotation
-/// and anchor location.
-fn update(
-    grid: &mut Grid,
-    piece: Piece,
-    rotation: i32,
-    anchor_row: i32,
-    anchor_col: i32,
-    fill: bool,
-) {
-    let rotation_offsets: &[Offsets4; 4] = ROTATION_OFFSETS.get(&piece).unwrap();
-    let offsets: &Offsets4 = &rotation_offsets[rotation as usize];
-    for (off_row, off_col) in offsets.iter() {
-        let row: usize = (anchor_row + off_row) as usize;
-        let col: usize = (anchor_col + off_col) as usize;
-        if fill {
-            grid[row][col] = 1;
-        } else {
-            grid[row][col] = 0;
-        }
+impl Default for Tetris {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Tetris {
     /// Create a new tetris game state object.
     pub fn new() -> Self {
-        let mut grid: Grid = [[0; 10]; 20];
-        update(&mut grid, Piece::O, 0, 0, 4, true);
+        Self::with_config(GameConfig::default())
+    }
+
+    /// Create a new game with the given settings.
+    pub fn with_config(config: GameConfig) -> Self {
+        let randomizer = match config.seed {
+            Some(seed) => Randomizer::bag(seed),
+            None => Randomizer::cycle(),
+        };
+        Self::with_randomizer(config, randomizer)
+    }
+
+    /// Create a new game with the given settings, dealing its pieces
+    /// from `randomizer` whatever the settings say.
+    pub(crate) fn with_randomizer(config: GameConfig, mut randomizer: Randomizer) -> Self {
+        let piece = randomizer.deal();
+        let next = randomizer.deal();
+
+        let spawn = config.rotation.spawn(piece);
+
+        let mut dealt = [0; 7];
+        dealt[piece as usize] += 1;
         Self {
-            grid,
-            piece: Piece::O,
-            rotation: 0,
-            anchor_row: 0,
-            anchor_col: 4,
+            board: Board::default(),
+            piece,
+            rotation: spawn.rotation,
+            anchor_row: spawn.row,
+            anchor_col: spawn.col,
+            pieces: 0,
+            dealt,
+            next,
+            hold: None,
+            can_hold: true,
+            paused: false,
+            randomizer,
+            config,
+            ticks: 0,
+            time: Duration::ZERO,
+            objective: None,
+            completed: false,
+            top_out: None,
+            events: Vec::new(),
+            recording: false,
+            inputs: Vec::new(),
         }
     }
 
-    /// Fetch the current grid state
-    pub fn grid(&self) -> &Grid {
-        &self.grid
+    /// Why the game ended, or `None` while it is still running.
+    pub fn top_out(&self) -> Option<TopOut> {
+        self.top_out
+    }
+
+    /// Whether the game ended with its objective met.
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+
+    /// Whether the game has ended, either way.
+    pub fn is_over(&self) -> bool {
+        self.ending().is_some()
+    }
+
+    /// How the game ended, once it has.
+    pub fn ending(&self) -> Option<Ending> {
+        match self.top_out {
+            Some(top_out) => Some(Ending::TopOut(top_out)),
+            None if self.completed => Some(Ending::Completed),
+            None => None,
+        }
+    }
+
+    /// Give the game something to win or lose by, judged every time a
+    /// piece locks. Restarts keep it.
+    pub fn set_objective(&mut self, objective: impl Objective + 'static) {
+        self.objective = Some(Goal(Arc::new(objective)));
+    }
+
+    /// The game's objective, if it has one.
+    pub fn objective(&self) -> Option<&dyn Objective> {
+        self.objective.as_ref().map(|goal| &*goal.0)
+    }
+
+    /// Game time played so far: the gravity interval of each tick added
+    /// up, so it stands still while paused.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// The level the game is at: the config's starting level, plus one
+    /// for every `LEVEL_LINES` lines cleared, up to `MAX_LEVEL`.
+    pub fn level(&self) -> u32 {
+        let level = self.config.level.saturating_add(self.lines() / LEVEL_LINES);
+        level.min(MAX_LEVEL)
+    }
+
+    /// Time between two gravity ticks now: the config's gravity, sped
+    /// up by its curve for the level reached.
+    pub fn gravity(&self) -> Duration {
+        self.config.curve.gravity(self.config.gravity, self.level())
+    }
+
+    /// Tick every `gravity` from now on, sped up by the config's curve,
+    /// as when the game speeds up.
+    /// Time already played keeps the interval it was played at.
+    pub fn set_gravity(&mut self, gravity: Duration) {
+        if self.recording {
+            self.inputs.push(Step::Gravity(gravity));
+        }
+        self.config.gravity = gravity;
+    }
+
+    /// Collect the events that happened since the last call, oldest
+    /// first.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// Start or stop logging every tick and event, to be collected with
+    /// `take_inputs`.
+    pub fn record_inputs(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    /// Collect the inputs logged since the last call, oldest first.
+    /// Applying them to a copy of the game as it was then brings the
+    /// copy to the same state.
+    pub fn take_inputs(&mut self) -> Vec<Step> {
+        core::mem::take(&mut self.inputs)
+    }
+
+    pub fn apply(&mut self, step: Step) {
+        match step {
+            Step::Tick => {
+                self.tick();
+            }
+            Step::Event(evt) => self.event(evt),
+            Step::Garbage(hole) => {
+                self.rise(hole);
+            }
+            Step::Spawn(piece) => {
+                self.swap_piece(piece);
+            }
+            Step::ClearStack => self.clear_stack(),
+            Step::Gravity(gravity) => self.set_gravity(gravity),
+            Step::SetLines(lines) => self.set_lines(lines),
+            Step::SetPreview(preview) => self.set_preview(preview),
+            Step::Reseed(seed) => self.reseed(seed),
+        }
+    }
+
+    /// A fingerprint of the game's state: the board, the falling piece,
+    /// the pieces to come, the counters, and the time played and the
+    /// gravity. Two games with the same settings hash alike exactly when
+    /// they will play out alike, so comparing hashes is a cheap way to
+    /// spot a desync. Stable across platforms and builds.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        let cells = (0..NROWS).flat_map(|row| (0..NCOLS).map(move |col| (row, col)));
+        for cell in cells.map(|(row, col)| self.cell(row, col)) {
+            hasher.write(&[match cell {
+                Cell::Empty => 0,
+                Cell::Locked(piece) => 1 + piece as u8,
+                Cell::Garbage => 8,
+            }]);
+        }
+        hasher.write(&[
+            self.piece as u8,
+            self.next as u8,
+            self.hold.map_or(7, |piece| piece as u8),
+            self.can_hold as u8,
+            self.paused as u8,
+            self.completed as u8,
+        ]);
+        for n in [self.rotation, self.anchor_row, self.anchor_col] {
+            hasher.write(&n.to_le_bytes());
+        }
+        for n in [self.board.lines(), self.pieces, self.ticks]
+            .iter()
+            .chain(&self.dealt)
+        {
+            hasher.write(&n.to_le_bytes());
+        }
+        for duration in [self.time, self.config.gravity] {
+            hasher.write(&duration.as_nanos().to_le_bytes());
+        }
+        self.randomizer.hash_into(&mut hasher);
+        hasher.write(&[match self.top_out {
+            None => 0,
+            Some(TopOut::BlockOut) => 1,
+            Some(TopOut::GarbageOut) => 2,
+            Some(TopOut::Failed) => 3,
+        }]);
+        hasher.0
+    }
+
+    /// The settings this game was started with, with the gravity it
+    /// plays at now.
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Take a snapshot of the game for rendering.
+    pub fn view(&self) -> GameView {
+        let to_cells = |positions: Vec<(i32, i32)>| -> Vec<(usize, usize)> {
+            positions
+                .into_iter()
+                .map(|(row, col)| (row as usize, col as usize))
+                .collect()
+        };
+        let active = self.falling_piece_positions();
+        let ghost = self.board.drop(self.falling()).cells().to_vec();
+
+        GameView {
+            board: *self.board.cells(),
+            piece: self.piece,
+            active: to_cells(active),
+            ghost: to_cells(ghost),
+            queue: self.preview(),
+            hold: self.hold,
+            lines: self.lines(),
+            stack_height: self.stack_height(),
+        }
+    }
+
+    /// Fetch the type of the current falling piece
+    pub fn piece(&self) -> Piece {
+        self.piece
+    }
+
+    /// Fetch the rotation state of the current falling piece
+    pub fn rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Fetch the (row, col) anchor of the current falling piece
+    pub fn anchor(&self) -> (i32, i32) {
+        (self.anchor_row, self.anchor_col)
+    }
+
+    /// The upcoming pieces, the next one first, as many as the game's
+    /// settings let the player see.
+    pub fn preview(&self) -> Vec<Piece> {
+        let mut randomizer = self.randomizer.clone();
+        core::iter::once(self.next)
+            .chain(core::iter::from_fn(|| Some(randomizer.deal())))
+            .take(self.config.preview)
+            .collect()
+    }
+
+    /// The piece put aside with `Action::Hold`, if any.
+    pub fn held(&self) -> Option<Piece> {
+        self.hold
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Fetch the total number of rows cleared so far
+    pub fn lines(&self) -> u32 {
+        self.board.lines()
+    }
+
+    /// Fetch the number of pieces locked so far
+    pub fn pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    /// How many of each piece have appeared this game, the falling one
+    /// included, indexed by `Piece as usize`.
+    pub fn piece_counts(&self) -> [u32; 7] {
+        self.dealt
+    }
+
+    /// How many pieces the game has drawn from its randomizer since it
+    /// started, the next one included.
+    pub(crate) fn draws(&self) -> u32 {
+        self.dealt.iter().sum::<u32>() + 1
+    }
+
+    /// Height of the settled stack in each column, ignoring the
+    /// current falling piece.
+    pub fn column_heights(&self) -> [usize; NCOLS] {
+        self.board.column_heights()
+    }
+
+    /// Height of the settled stack in its highest column.
+    pub fn stack_height(&self) -> usize {
+        self.column_heights().iter().copied().max().unwrap_or(0)
+    }
+
+    /// How many rows of the settled stack still hold garbage.
+    pub fn garbage_rows(&self) -> usize {
+        self.board.garbage_rows()
+    }
+
+    /// Push the stack up by a garbage row per hole column, full but for
+    /// that column, one row after another so that the last ends up at
+    /// the bottom. The falling piece stays where it is unless the
+    /// garbage reaches it, in which case it is lifted onto the stack. If
+    /// a row would push the stack or the piece out of the top of the
+    /// board the game ends instead, with that row left out. Returns
+    /// false once the game is over.
+    pub fn insert_garbage(&mut self, holes: &[usize]) -> bool {
+        holes.iter().all(|hole| self.rise(*hole))
+    }
+
+    /// Insert a single garbage row with a hole at `hole`.
+    fn rise(&mut self, hole: usize) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        if self.recording {
+            self.inputs.push(Step::Garbage(hole));
+        }
+        let mut board = self.board;
+        let risen = board.insert_garbage(&[hole]);
+        let falling = self.falling();
+        let lifted = [falling.row, falling.row - 1].iter().copied().find(|row| {
+            board.fits(Placement {
+                row: *row,
+                ..falling
+            })
+        });
+        match lifted {
+            Some(row) if risen => {
+                debug!(hole, lifted = row != self.anchor_row, "garbage inserted");
+                self.board = board;
+                self.anchor_row = row;
+                true
+            }
+            _ => {
+                self.top_out = Some(TopOut::GarbageOut);
+                info!(lines = self.lines(), "game over: garbage out");
+                false
+            }
+        }
     }
 
     /// Fetch all positions of the current falling piece.
     fn falling_piece_positions(&self) -> Vec<(i32, i32)> {
         // TODO: a length-4 slice is fine, and we avoid allocation.
         let mut results = Vec::new();
-        let rotation_offsets: &[Offsets4; 4] = ROTATION_OFFSETS.get(&self.piece).unwrap();
+        let rotation_offsets: &[Offsets4; 4] = &ROTATION_OFFSETS[self.piece as usize];
         for (off_row, off_col) in rotation_offsets[self.rotation as usize].iter() {
             results.push((self.anchor_row + off_row, self.anchor_col + off_col));
         }
         results
     }
 
-    /// Checks if a new piece at the given row, col, and rotation
-    /// overlaps with any existing cells.
-    fn fits(grid: &Grid, piece: Piece, row: i32, col: i32, rotation: i32) -> bool {
-        let rotation_offsets: &[Offsets4; 4] = ROTATION_OFFSETS.get(&piece).unwrap();
-        for (off_row, off_col) in rotation_offsets[rotation as usize].iter() {
-            let this_row = off_row + row;
-            let this_col = off_col + col;
-            if grid[this_row as usize][this_col as usize] == 1 {
-                return false;
-            }
+    /// The falling piece, where it is now.
+    fn falling(&self) -> Placement {
+        Placement::falling(self)
+    }
+
+    /// The content of a cell, including the falling piece.
+    fn cell(&self, row: usize, col: usize) -> Cell {
+        if self.falling().cells().contains(&(row as i32, col as i32)) {
+            Cell::Locked(self.piece)
+        } else {
+            self.board.cell(row, col)
         }
-        true
+    }
+
+    /// Whether `piece` fits where it spawns.
+    fn fits_at_spawn(&self, piece: Piece) -> bool {
+        self.board.fits(Placement::spawn(piece, &self.config))
     }
 
     /// Check if the current falling piece fits into the new position
     /// and the given rotation.
     fn falling_fits(&self, row: i32, col: i32, rotation: i32) -> bool {
-        let positions = self.falling_piece_positions();
-
-        let rotation_offsets: &[Offsets4; 4] = &ROTATION_OFFSETS.get(&self.piece).unwrap();
-        let offsets: &Offsets4 = &rotation_offsets[rotation as usize];
-        for (off_row, off_col) in offsets {
-            let new_row = row + off_row;
-            let new_col = col + off_col;
-
-            if new_row < 0 || new_row >= NROWS as i32 {
-                return false;
-            }
-            if new_col < 0 || new_col >= NCOLS as i32 {
-                return false;
-            }
-
-            if !positions.contains(&(new_row, new_col))
-                && self.grid[new_row as usize][new_col as usize] > 0
-            {
-                return false;
-            }
-        }
-
-        true
+        self.board.fits(Placement {
+            piece: self.piece,
+            rotation,
+            row,
+            col,
+        })
     }
 
     /// Tests whether current falling piece can drop one more unit or
@@ -258,191 +815,341 @@ impl Tetris {
         self.falling_fits(self.anchor_row + 1, self.anchor_col, self.rotation)
     }
 
-    /// Shift everything above row down by 1.
-    fn shift_down(&mut self, row: i32) {
-        for r in (1..=row as usize).rev() {
-            for c in 0..NCOLS {
-                self.grid[r][c] = self.grid[r - 1][c];
-            }
+    /// Simulate "gravity" for 1 unit of time, and say what came of it.
+    pub fn tick(&mut self) -> TickOutcome {
+        if let Some(ending) = self.ending() {
+            return TickOutcome::GameOver(ending);
+        }
+        if self.recording {
+            self.inputs.push(Step::Tick);
         }
-        for c in 0..NCOLS {
-            self.grid[0][c] = 0;
+        if self.paused {
+            return TickOutcome::Continued;
         }
+        self.ticks += 1;
+        self.time += self.gravity();
+        self.fall()
+    }
+
+    /// `tick`, from before it said what happened. Returns true if the
+    /// game can still continue, otherwise false.
+    #[deprecated(note = "use `tick`, which returns a `TickOutcome`")]
+    pub fn tick_bool(&mut self) -> bool {
+        self.tick().continues()
     }
 
-    /// Simulate "gravity" for 1 unit of time. Returns true if the game can still continue
-    /// otherwise returns false.
-    pub fn tick(&mut self) -> bool {
-        let should_continue: bool;
+    /// Drop the falling piece a row, or lock it and spawn the next one
+    /// if it has landed.
+    fn fall(&mut self) -> TickOutcome {
         // 1. if we can drop, then just drop
         // 2. if we cannot drop, then check if there are complete rows
         // 3. check if there is enough space for new piece
-        if self.can_drop() {
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                false,
-            );
-            self.anchor_row += 1;
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                true,
-            );
-            should_continue = true;
+        if self.soft_drop() {
+            TickOutcome::Continued
         } else {
             // 2. check for complete rows
-            let mut min_row: i32 = NROWS as i32;
-            let mut max_row: i32 = 0;
-
-            for (row, _) in self.falling_piece_positions().iter() {
-                min_row = min(min_row, *row);
-                max_row = max(max_row, *row);
-            }
+            debug!(
+                piece = ?self.piece,
+                rotation = self.rotation,
+                row = self.anchor_row,
+                col = self.anchor_col,
+                "piece locked"
+            );
 
-            // shift things down by 1 if there are complete rows.
-            for row in min_row..=max_row {
-                if self.grid[row as usize].iter().sum::<i32>() == NCOLS as i32 {
-                    self.shift_down(row);
+            self.pieces += 1;
+            self.events.push(GameEvent::Locked(self.piece));
+            self.board.lock(self.falling());
+            let cleared = self.board.clear_full_rows();
+            if cleared > 0 {
+                debug!(cleared, total = self.lines(), "rows cleared");
+                self.events.push(GameEvent::LinesCleared(cleared));
+                if self.board.is_empty() {
+                    self.events.push(GameEvent::PerfectClear);
                 }
             }
 
-            let new_piece: Piece = ((self.piece as i32 + 1) % 7).try_into().unwrap();
-            if Self::fits(&self.grid, new_piece, 0, 4, 0) {
+            let new_piece = self.next;
+            let spawn = self.config.rotation.spawn(new_piece);
+            if !self.fits_at_spawn(new_piece) && self.config.zen {
+                info!(next = ?new_piece, lines = self.lines(), "zen: stack cleared");
+                self.board.clear();
+                self.events.push(GameEvent::StackCleared);
+            }
+            if self.fits_at_spawn(new_piece) {
                 self.piece = new_piece;
-                self.rotation = 0;
-                self.anchor_row = 0;
-                self.anchor_col = 4;
-                should_continue = true;
-
-                update(
-                    &mut self.grid,
-                    self.piece,
-                    self.rotation,
-                    self.anchor_row,
-                    self.anchor_col,
-                    true,
-                );
+                self.dealt[new_piece as usize] += 1;
+                self.next = self.randomizer.deal();
+                self.rotation = spawn.rotation;
+                self.anchor_row = spawn.row;
+                self.anchor_col = spawn.col;
+                self.can_hold = true;
+                debug!(piece = ?self.piece, "piece spawned");
             } else {
-                should_continue = false;
+                self.top_out = Some(TopOut::BlockOut);
+                info!(next = ?new_piece, lines = self.lines(), "game over: block out");
+            }
+
+            if let Some(Goal(objective)) = self.objective.clone().filter(|_| !self.is_over()) {
+                match objective.judge(self) {
+                    Some(Verdict::Won) => {
+                        self.completed = true;
+                        info!(
+                            objective = objective.name(),
+                            lines = self.lines(),
+                            "objective met"
+                        );
+                    }
+                    Some(Verdict::Lost) => {
+                        self.top_out = Some(TopOut::Failed);
+                        info!(
+                            objective = objective.name(),
+                            lines = self.lines(),
+                            "objective failed"
+                        );
+                    }
+                    None => (),
+                }
+            }
+
+            match self.ending() {
+                Some(ending) => TickOutcome::GameOver(ending),
+                None => TickOutcome::PieceLocked { cleared },
             }
         }
+    }
+
+    /// Drop the falling piece as far as it goes and lock it right
+    /// away, logged as a single `Action::HardDrop` and taking no game
+    /// time. Returns false once the game is over.
+    pub fn hard_drop(&mut self) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        if self.recording {
+            self.inputs.push(Step::Event(Action::HardDrop));
+        }
+        if self.paused {
+            return true;
+        }
+        self.drop_and_lock()
+    }
 
-        should_continue
+    fn drop_and_lock(&mut self) -> bool {
+        while self.soft_drop() {}
+        self.fall().continues()
+    }
+
+    /// Move the falling piece down a row if it fits there, without
+    /// locking it. Returns whether it moved.
+    pub fn soft_drop(&mut self) -> bool {
+        if !self.can_drop() {
+            return false;
+        }
+        self.anchor_row += 1;
+        true
+    }
+
+    /// Put the falling piece aside and bring in the held one, or the
+    /// next piece the first time, where pieces spawn. Only once until
+    /// the next piece locks, and not when there's no room at the top.
+    /// Logged as `Action::Hold`. Does nothing, and isn't logged, in
+    /// games without hold.
+    pub fn hold(&mut self) {
+        if !self.config.hold {
+            return;
+        }
+        if self.recording {
+            self.inputs.push(Step::Event(Action::Hold));
+        }
+        if !self.paused {
+            self.hold_piece();
+        }
+    }
+
+    fn hold_piece(&mut self) {
+        if !self.can_hold {
+            return;
+        }
+        let incoming = self.hold.unwrap_or(self.next);
+        let spawn = self.config.rotation.spawn(incoming);
+        if !self.fits_at_spawn(incoming) {
+            return;
+        }
+        if self.hold.is_none() {
+            self.next = self.randomizer.deal();
+            self.dealt[incoming as usize] += 1;
+        }
+        debug!(held = ?self.piece, piece = ?incoming, "hold");
+        self.hold = Some(self.piece);
+        self.piece = incoming;
+        self.rotation = spawn.rotation;
+        self.anchor_row = spawn.row;
+        self.anchor_col = spawn.col;
+        self.can_hold = false;
+    }
+
+    /// Replace the falling piece with `piece`, where pieces spawn.
+    /// Returns false, leaving the game as it was, if there's no room
+    /// for it there.
+    pub fn swap_piece(&mut self, piece: Piece) -> bool {
+        if self.recording {
+            self.inputs.push(Step::Spawn(piece));
+        }
+        let spawn = self.config.rotation.spawn(piece);
+        let fits = self.fits_at_spawn(piece);
+        if fits {
+            self.piece = piece;
+            self.rotation = spawn.rotation;
+            self.anchor_row = spawn.row;
+            self.anchor_col = spawn.col;
+        }
+        fits
+    }
+
+    /// Empty the board but for the falling piece.
+    pub fn clear_stack(&mut self) {
+        if self.recording {
+            self.inputs.push(Step::ClearStack);
+        }
+        self.board.clear();
+    }
+
+    /// Count `lines` as cleared so far.
+    pub fn set_lines(&mut self, lines: u32) {
+        if self.recording {
+            self.inputs.push(Step::SetLines(lines));
+        }
+        self.board.set_lines(lines);
+    }
+
+    /// Show `preview` pieces to come, at most `MAX_PREVIEW`.
+    pub fn set_preview(&mut self, preview: usize) {
+        if self.recording {
+            self.inputs.push(Step::SetPreview(preview));
+        }
+        self.config.preview = preview.min(MAX_PREVIEW);
+    }
+
+    /// Deal the pieces after the falling one from a new bag shuffled by
+    /// `seed`, and those of a restart too. The next piece is dealt again,
+    /// so the one it replaces never spawns.
+    pub fn reseed(&mut self, seed: u64) {
+        if self.recording {
+            self.inputs.push(Step::Reseed(seed));
+        }
+        self.config.seed = Some(seed);
+        self.randomizer = Randomizer::bag(seed);
+        self.next = self.randomizer.deal();
+    }
+
+    /// Stop the game, or carry on if it is stopped.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Start over with a fresh game on the same settings. Input logging
+    /// carries on as it was.
+    pub fn restart(&mut self) {
+        let mut fresh = match self.randomizer {
+            // A game dealt from a record deals on from it.
+            Randomizer::Recorded { .. } => {
+                Self::with_randomizer(self.config.clone(), self.randomizer.clone())
+            }
+            _ => Self::with_config(self.config.clone()),
+        };
+        fresh.objective = self.objective.take();
+        fresh.recording = self.recording;
+        fresh.inputs = core::mem::take(&mut self.inputs);
+        *self = fresh;
+        info!("game restarted");
     }
 
     pub fn move_left(&mut self) {
         if self.falling_fits(self.anchor_row, self.anchor_col - 1, self.rotation) {
-            // clear current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                false,
-            );
             self.anchor_col -= 1;
-            // re-paint current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                true,
-            );
         }
     }
 
     pub fn move_right(&mut self) {
         if self.falling_fits(self.anchor_row, self.anchor_col + 1, self.rotation) {
-            // clear current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                false,
-            );
             self.anchor_col += 1;
-            // re-paint current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                true,
-            );
         }
     }
 
     pub fn counter_clock_rotate(&mut self) {
-        let new_rotation = (self.rotation + 3) % 4;
+        self.rotate(false);
+    }
+
+    pub fn clock_rotate(&mut self) {
+        self.rotate(true);
+    }
 
+    /// Turn the falling piece half way round if it fits where it
+    /// stands. No kicks are tried.
+    pub fn rotate_180(&mut self) {
+        let new_rotation = (self.rotation + 2) % 4;
         if self.falling_fits(self.anchor_row, self.anchor_col, new_rotation) {
-            // clear current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                false,
-            );
             self.rotation = new_rotation;
-            // re-paint current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                true,
-            );
         }
     }
 
-    pub fn clock_rotate(&mut self) {
-        let new_rotation = (self.rotation + 1) % 4;
+    /// Turn the falling piece in place if it fits, or else at the first
+    /// wall kick offset where it does.
+    fn rotate(&mut self, clockwise: bool) {
+        let new_rotation = (self.rotation + if clockwise { 1 } else { 3 }) % 4;
+        let kicks = self.config.kicks.offsets(self.piece, clockwise);
+        let target = core::iter::once(&(0, 0))
+            .chain(kicks)
+            .map(|(col, row)| (self.anchor_row + row, self.anchor_col + col))
+            .find(|(row, col)| self.falling_fits(*row, *col, new_rotation));
 
-        if self.falling_fits(self.anchor_row, self.anchor_col, new_rotation) {
-            // clear current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                false,
-            );
+        if let Some((row, col)) = target {
             self.rotation = new_rotation;
-            // re-paint current piece.
-            update(
-                &mut self.grid,
-                self.piece,
-                self.rotation,
-                self.anchor_row,
-                self.anchor_col,
-                true,
-            );
+            self.anchor_row = row;
+            self.anchor_col = col;
         }
     }
 
-    pub fn event(&mut self, evt: Event) {
+    pub fn event(&mut self, evt: Action) {
+        if evt == Action::Hold && !self.config.hold {
+            return;
+        }
+        if self.recording {
+            self.inputs.push(Step::Event(evt));
+        }
+        if self.paused && !matches!(evt, Action::Pause | Action::Restart) {
+            return;
+        }
+        let before = (self.anchor_row, self.anchor_col, self.rotation);
         match evt {
-            Event::Left => self.move_left(),
-            Event::Right => self.move_right(),
-            Event::CounterClock => self.counter_clock_rotate(),
-            Event::Clock => self.clock_rotate(),
+            Action::MoveLeft => self.move_left(),
+            Action::MoveRight => self.move_right(),
+            Action::RotateCcw => self.counter_clock_rotate(),
+            Action::RotateCw => self.clock_rotate(),
+            Action::Rotate180 => self.rotate_180(),
+            Action::SoftDrop => {
+                self.soft_drop();
+            }
+            Action::HardDrop => {
+                if !self.is_over() {
+                    self.drop_and_lock();
+                }
+            }
+            Action::Hold => self.hold_piece(),
+            Action::Pause => self.toggle_pause(),
+            Action::Restart => self.restart(),
+        }
+        let steers = matches!(
+            evt,
+            Action::MoveLeft
+                | Action::MoveRight
+                | Action::RotateCcw
+                | Action::RotateCw
+                | Action::Rotate180
+        );
+        if steers && !self.is_over() && (self.anchor_row, self.anchor_col, self.rotation) == before
+        {
+            self.events.push(GameEvent::Blocked(evt));
         }
     }
 }
@@ -450,99 +1157,534 @@ impl Tetris {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::board::{Board, Placement};
+    use crate::rotation::{Kicks, RotationSystem};
+
+    /// The board with the falling piece in it, as text.
+    fn snapshot(t: &Tetris) -> String {
+        Board::from_tetris(t)
+            .with_piece_locked(Placement::falling(t))
+            .to_string()
+    }
+
+    const EMPTY_ROW: &str = "..........\n";
 
     #[test]
     fn test_init_tetris() {
         let t = Tetris::new();
-        let repr = format!("{:?}", t);
-        let expected = r#"========TETRIS========
-Piece: O, Rotation: 0, ARow: 0, ACol: 4
-0000110000
-0000110000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-"#;
-        assert_eq!(repr, expected);
+        assert_eq!((t.piece(), t.rotation(), t.anchor()), (Piece::O, 0, (0, 4)));
+        assert_eq!(
+            snapshot(&t),
+            format!("....##....\n....##....\n{}", EMPTY_ROW.repeat(18))
+        );
     }
 
     #[test]
     fn test_tick() {
         let mut t = Tetris::new();
         t.tick();
-        let repr = format!("{:?}", t);
-        println!("{}", repr);
-        let expected = r#"========TETRIS========
-Piece: O, Rotation: 0, ARow: 1, ACol: 4
-0000000000
-0000110000
-0000110000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-"#;
-        assert_eq!(repr, expected);
+        assert_eq!(t.anchor(), (1, 4));
+        assert_eq!(
+            snapshot(&t),
+            format!("....##....\n....##....\n{}", EMPTY_ROW.repeat(17))
+        );
+
+        // Time played keeps the gravity it was played at.
+        let gravity = t.config().gravity;
+        t.set_gravity(gravity / 2);
+        t.tick();
+        assert_eq!(t.time(), gravity + gravity / 2);
     }
 
     #[test]
     fn test_tick_until_bottom() {
+        let mut t = Tetris::new();
+        for _ in 0..18 {
+            assert_eq!(t.tick(), TickOutcome::Continued);
+        }
+        assert_eq!(t.tick(), TickOutcome::PieceLocked { cleared: 0 });
+        assert_eq!((t.piece(), t.rotation(), t.anchor()), (Piece::L, 3, (0, 3)));
+        assert_eq!(
+            snapshot(&t),
+            format!(
+                ".....#....\n...###....\n{}....##....\n....##....\n",
+                EMPTY_ROW.repeat(15)
+            )
+        );
+    }
+
+    #[test]
+    fn test_column_heights() {
+        let mut t = Tetris::new();
+        assert_eq!(t.column_heights(), [0; NCOLS]);
+        for _ in 0..19 {
+            assert!(t.tick().continues());
+        }
+        assert_eq!(t.column_heights(), [0, 0, 0, 0, 2, 2, 0, 0, 0, 0]);
+        assert_eq!(t.stack_height(), 2);
+        assert!(!t.view().in_danger());
+        t.board.set(DANGER_ROWS, 0, Cell::Garbage);
+        assert!(!t.view().in_danger());
+        t.board.set(DANGER_ROWS - 1, 0, Cell::Garbage);
+        assert!(t.view().in_danger());
+    }
+
+    #[test]
+    fn test_locked_cells_remember_piece() {
+        let mut t = Tetris::new();
+        for _ in 0..19 {
+            assert!(t.tick().continues());
+        }
+        assert_eq!(t.cell(19, 4), Cell::Locked(Piece::O));
+        assert_eq!(t.cell(1, 5), Cell::Locked(Piece::L));
+        assert_eq!(t.cell(19, 3), Cell::Empty);
+    }
+
+    #[test]
+    fn test_view() {
         let mut t = Tetris::new();
         for _ in 0..19 {
-            assert!(t.tick());
-        }
-        let repr = format!("{:?}", t);
-        let expected = r#"========TETRIS========
-Piece: L, Rotation: 0, ARow: 0, ACol: 4
-0000100000
-0000100000
-0000110000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000000000
-0000110000
-0000110000
-"#;
-        assert_eq!(repr, expected);
+            assert!(t.tick().continues());
+        }
+        let view = t.view();
+        assert_eq!(view.piece, Piece::L);
+        assert_eq!(view.active, vec![(2, 3), (2, 4), (2, 5), (1, 5)]);
+        assert_eq!(view.ghost, vec![(17, 3), (17, 4), (17, 5), (16, 5)]);
+        assert_eq!(view.queue, vec![Piece::J]);
+        t.config.preview = 3;
+        assert_eq!(t.view().queue, vec![Piece::J, Piece::T, Piece::Z]);
+        t.config.preview = 0;
+        assert!(t.view().queue.is_empty());
+        // The falling piece is not part of the board, but is a cell.
+        assert_eq!(view.board[1][5], Cell::Empty);
+        assert_eq!(view.cell(1, 5), Cell::Locked(Piece::L));
+        assert_eq!(view.cell(19, 4), Cell::Locked(Piece::O));
+        assert!(view.is_ghost(17, 4));
+        assert!(!view.is_ghost(19, 4));
+
+        let mirrored = view.mirrored();
+        assert_eq!(mirrored.cell(1, NCOLS - 1 - 5), Cell::Locked(Piece::L));
+        assert!(mirrored.is_ghost(17, NCOLS - 1 - 4));
+        assert_eq!(mirrored.mirrored(), view);
+    }
+
+    #[test]
+    fn test_seeded_games_are_reproducible() {
+        let a = Tetris::with_config(GameConfig::new().seed(3));
+        let b = GameConfig::new().seed(3).build();
+        assert_eq!(a, b);
+        assert_eq!(a.config().seed, Some(3));
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut a = GameConfig::new().seed(3).build();
+        let mut b = a.clone();
+        // Only the game itself counts, not what is waiting to be
+        // collected from it.
+        b.record_inputs(true);
+        for t in [&mut a, &mut b] {
+            t.event(Action::MoveLeft);
+            t.tick();
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b.event(Action::MoveRight);
+        assert_ne!(a.state_hash(), b.state_hash());
+        b.event(Action::MoveLeft);
+        assert_eq!(a.state_hash(), b.state_hash());
+        // So does the speed the game is played at.
+        b.set_gravity(Duration::from_millis(100));
+        assert_ne!(a.state_hash(), b.state_hash());
+
+        let c = GameConfig::new().seed(4).build();
+        assert_ne!(
+            GameConfig::new().seed(3).build().state_hash(),
+            c.state_hash()
+        );
+        // Pinned, so that a change to the hash is a deliberate one.
+        assert_eq!(
+            GameConfig::new().build().state_hash(),
+            0xb155_24a7_a36c_2743
+        );
+    }
+
+    #[test]
+    fn test_piece_counts() {
+        let mut tetris = GameConfig::new().seed(2).build();
+        let mut counts = [0; 7];
+        counts[tetris.piece() as usize] += 1;
+        for _ in 0..8 {
+            tetris.hard_drop();
+            counts[tetris.piece() as usize] += 1;
+        }
+        assert!(!tetris.is_over());
+        assert_eq!(tetris.piece_counts(), counts);
+        assert_eq!(counts.iter().sum::<u32>(), tetris.pieces() + 1);
+    }
+
+    #[test]
+    fn test_guideline_spawns() {
+        let all = [
+            Piece::O,
+            Piece::L,
+            Piece::J,
+            Piece::T,
+            Piece::Z,
+            Piece::S,
+            Piece::I,
+        ];
+        for piece in all.iter() {
+            let spawn = RotationSystem::Guideline.spawn(*piece);
+            let offsets = &ROTATION_OFFSETS[*piece as usize][spawn.rotation as usize];
+            let cells: Vec<(i32, i32)> = offsets
+                .iter()
+                .map(|(row, col)| (spawn.row + row, spawn.col + col))
+                .collect();
+            // Lying flat near the top, inside columns 3-6, with the box
+            // on the board.
+            assert_eq!(spawn.row, 0, "{:?}", piece);
+            let top = cells.iter().map(|c| c.0).min().unwrap();
+            assert!(top <= 1, "{:?}", piece);
+            assert!(cells.iter().all(|c| c.0 <= top + 1), "{:?}", piece);
+            assert!(cells.iter().all(|c| (3..=6).contains(&c.1)), "{:?}", piece);
+
+            // Every piece can turn either way as soon as it spawns, and
+            // after its first tick.
+            for ticks in 0..2 {
+                for turn in [Action::RotateCw, Action::RotateCcw, Action::Rotate180] {
+                    let mut t = Tetris::with_randomizer(
+                        GameConfig::new(),
+                        Randomizer::recorded(vec![*piece]),
+                    );
+                    for _ in 0..ticks {
+                        t.tick();
+                    }
+                    t.event(turn);
+                    assert_ne!(t.rotation(), spawn.rotation, "{:?} {:?}", piece, turn);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_classic_spawns() {
+        let t = Tetris::with_config(GameConfig::new().rotation(RotationSystem::Classic));
+        assert_eq!(t.anchor(), (0, 4));
+        assert_eq!(RotationSystem::Classic.spawn(Piece::I).rotation, 0);
+    }
+
+    /// Build a game whose board has the given rows at the bottom, using
+    /// `#` for filled and `.` for empty cells.
+    fn with_bottom_rows(rows: &[&str]) -> Tetris {
+        let mut t = Tetris::new();
+        t.board = rows.join("\n").parse().unwrap();
+        t
+    }
+
+    fn bottom_rows(t: &Tetris, n: usize) -> Vec<String> {
+        t.board.cells()[NROWS - n..]
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| if cell.is_filled() { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_clear_adjacent_rows() {
+        let mut t = with_bottom_rows(&["#.........", "##########", "##########", "..#......."]);
+        assert_eq!(t.board.clear_full_rows(), 2);
+        assert_eq!(
+            bottom_rows(&t, 4),
+            vec!["..........", "..........", "#.........", "..#......."]
+        );
+    }
+
+    #[test]
+    fn test_clear_non_adjacent_rows() {
+        let mut t = with_bottom_rows(&[
+            ".#........",
+            "##########",
+            "..#.......",
+            "##########",
+            "...#......",
+        ]);
+        assert_eq!(t.board.clear_full_rows(), 2);
+        assert_eq!(
+            bottom_rows(&t, 5),
+            vec![
+                "..........",
+                "..........",
+                ".#........",
+                "..#.......",
+                "...#......"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_four_rows() {
+        let mut t = with_bottom_rows(&[
+            "....#.....",
+            "##########",
+            "##########",
+            "##########",
+            "##########",
+        ]);
+        assert_eq!(t.board.clear_full_rows(), 4);
+        assert_eq!(
+            bottom_rows(&t, 5),
+            vec![
+                "..........",
+                "..........",
+                "..........",
+                "..........",
+                "....#....."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tick_counts_multi_line_clear() {
+        // Two rows complete except for columns 4-5, which the falling O
+        // piece fills when it lands.
+        let mut t = with_bottom_rows(&["####..####", "####..####"]);
+        for _ in 0..19 {
+            assert!(t.tick().continues());
+        }
+        assert_eq!(t.lines(), 2);
+        assert_eq!(t.column_heights(), [0; NCOLS]);
+        assert_eq!(
+            t.take_events(),
+            vec![
+                GameEvent::Locked(Piece::O),
+                GameEvent::LinesCleared(2),
+                GameEvent::PerfectClear
+            ]
+        );
+        assert!(t.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_hard_drop() {
+        let mut t = Tetris::new();
+        assert!(t.hard_drop());
+        assert_eq!(t.pieces(), 1);
+        assert_eq!(t.board.cell(19, 4), Cell::Locked(Piece::O));
+        assert_eq!(t.piece(), Piece::L);
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut t = Tetris::new();
+        t.event(Action::SoftDrop);
+        assert_eq!(t.anchor(), (1, 4));
+
+        // The first hold brings in the next piece, the second is refused
+        // until a piece locks, and then the held piece comes back.
+        t.event(Action::Hold);
+        assert_eq!((t.piece(), t.held()), (Piece::L, Some(Piece::O)));
+        assert_eq!(t.preview(), vec![Piece::J]);
+        t.event(Action::Hold);
+        assert_eq!(t.piece(), Piece::L);
+        t.event(Action::HardDrop);
+        assert_eq!((t.pieces(), t.piece()), (1, Piece::J));
+        t.event(Action::Hold);
+        assert_eq!((t.piece(), t.held()), (Piece::O, Some(Piece::J)));
+        assert_eq!(t.view().hold, Some(Piece::J));
+
+        t.tick();
+        t.tick();
+        t.event(Action::Rotate180);
+        assert_eq!(t.rotation(), 2);
+
+        // Nothing moves while paused.
+        t.event(Action::Pause);
+        let hash = t.state_hash();
+        t.tick();
+        t.hard_drop();
+        t.event(Action::MoveLeft);
+        t.toggle_pause();
+        t.toggle_pause();
+        assert_eq!(t.state_hash(), hash);
+        t.event(Action::Pause);
+        assert!(!t.is_paused());
+
+        t.event(Action::Restart);
+        assert_eq!(t.state_hash(), Tetris::new().state_hash());
+
+        // A move into the wall is reported, and one that works isn't.
+        t.take_events();
+        t.event(Action::MoveLeft);
+        assert!(t.take_events().is_empty());
+        for _ in 0..NCOLS {
+            t.event(Action::MoveLeft);
+        }
+        let events = t.take_events();
+        assert_eq!(events.last(), Some(&GameEvent::Blocked(Action::MoveLeft)));
+        assert!(!events.contains(&GameEvent::Blocked(Action::MoveRight)));
+    }
+
+    #[test]
+    fn test_hold_disabled() {
+        let mut t = GameConfig::new().hold(false).build();
+        t.record_inputs(true);
+        let hash = t.state_hash();
+        t.event(Action::Hold);
+        t.hold();
+        assert_eq!(t.state_hash(), hash);
+        assert_eq!(t.view().hold, None);
+        assert!(t.take_inputs().is_empty());
+        assert!(t.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_actions_replay() {
+        use Action::*;
+        let mut t = GameConfig::new().seed(9).build();
+        let mut copy = t.clone();
+        t.record_inputs(true);
+        for action in [
+            Hold, SoftDrop, Rotate180, MoveLeft, HardDrop, Hold, Pause, Pause,
+        ] {
+            t.event(action);
+            t.tick();
+        }
+        let inputs = t.take_inputs();
+        // A hard drop is logged as itself, not as the ticks it takes.
+        assert_eq!(
+            inputs[8..11],
+            [Step::Event(HardDrop), Step::Tick, Step::Event(Hold)]
+        );
+        for step in inputs {
+            copy.apply(step);
+        }
+        assert_eq!(copy.state_hash(), t.state_hash());
+
+        // So are holds, swaps and clears made outside of `event`.
+        t.hard_drop();
+        t.hold();
+        t.swap_piece(Piece::I);
+        t.insert_garbage(&[0]);
+        t.clear_stack();
+        t.set_gravity(Duration::from_millis(300));
+        t.set_lines(12);
+        t.set_preview(2);
+        t.reseed(9);
+        t.tick();
+        let inputs = t.take_inputs();
+        assert_eq!(
+            inputs[1..],
+            [
+                Step::Event(Hold),
+                Step::Spawn(Piece::I),
+                Step::Garbage(0),
+                Step::ClearStack,
+                Step::Gravity(Duration::from_millis(300)),
+                Step::SetLines(12),
+                Step::SetPreview(2),
+                Step::Reseed(9),
+                Step::Tick
+            ]
+        );
+        for step in inputs {
+            copy.apply(step);
+        }
+        assert_eq!(copy.state_hash(), t.state_hash());
+    }
+
+    #[test]
+    fn test_wall_kicks() {
+        // A vertical I against the right wall can only turn flat if it
+        // is kicked three columns to the left.
+        let right = NCOLS as i32 - 1;
+        let kicks = Kicks::parse("I cw = -3,0").unwrap();
+        let cases = [
+            (GameConfig::new(), (0, right)),
+            (GameConfig::new().kicks(kicks), (1, right - 3)),
+        ];
+        for (config, (rotation, col)) in cases {
+            let mut t = config.build();
+            t.piece = Piece::I;
+            t.rotation = 0;
+            t.anchor_row = 5;
+            t.anchor_col = right;
+            t.clock_rotate();
+            assert_eq!((t.rotation(), t.anchor()), (rotation, (5, col)));
+        }
+    }
+
+    #[test]
+    fn test_block_out() {
+        // The O lands on the block at row 5, the L locks on top of it
+        // right away, and the J has no room left to spawn.
+        let mut t = Tetris::new();
+        t.board.set(5, 4, Cell::Garbage);
+        while t.tick().continues() {}
+        assert_eq!(t.top_out(), Some(TopOut::BlockOut));
+        let over = TickOutcome::GameOver(Ending::TopOut(TopOut::BlockOut));
+        assert_eq!(t.tick(), over);
+        assert!(!over.continues());
+    }
+
+    #[test]
+    fn test_insert_garbage() {
+        let mut t = with_bottom_rows(&["#........."]);
+        let anchor = t.anchor();
+        assert!(t.insert_garbage(&[3, 7]));
+        assert_eq!(
+            bottom_rows(&t, 3),
+            vec!["#.........", "###.######", "#######.##"]
+        );
+        assert_eq!(t.garbage_rows(), 3);
+        assert_eq!(t.anchor(), anchor);
+
+        // A piece resting on the stack is lifted along with it.
+        while t.can_drop() {
+            t.tick();
+        }
+        let (row, col) = t.anchor();
+        assert!(t.insert_garbage(&[0]));
+        assert_eq!(t.anchor(), (row - 1, col));
+        assert_eq!(t.top_out(), None);
+
+        // Garbage can't push the stack out of the top.
+        let mut t = Tetris::new();
+        t.board.set(1, 0, Cell::Garbage);
+        assert!(!t.insert_garbage(&[0, 0, 0]));
+        assert_eq!(t.top_out(), Some(TopOut::GarbageOut));
+        assert_eq!(t.board.cell(0, 0), Cell::Garbage);
+        assert_eq!(t.garbage_rows(), 2);
+
+        // Garbage is part of the recorded inputs.
+        let mut t = Tetris::new();
+        let mut copy = t.clone();
+        t.record_inputs(true);
+        t.tick();
+        t.insert_garbage(&[2, 5]);
+        for step in t.take_inputs() {
+            copy.apply(step);
+        }
+        assert_eq!(copy.state_hash(), t.state_hash());
+    }
+
+    #[test]
+    fn test_zen_clears_stack() {
+        let mut t = GameConfig::new().zen(true).build();
+        t.board.set(5, 4, Cell::Garbage);
+        for _ in 0..20 {
+            assert!(t.tick().continues());
+        }
+        assert_eq!(t.top_out(), None);
+        let events = t.take_events();
+        assert_eq!(events.last(), Some(&GameEvent::StackCleared));
+        assert!(events[..events.len() - 1]
+            .iter()
+            .all(|event| matches!(event, GameEvent::Locked(_))));
+        // Only the falling piece is left.
+        assert!(t.board.is_empty());
     }
 }
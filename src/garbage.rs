@@ -0,0 +1,293 @@
+use super::game_state::{Tetris, DANGER_ROWS, NCOLS, NROWS};
+use super::objective::ClearGarbage;
+#[cfg(feature = "std")]
+use super::profile;
+use super::randomizer::Rng;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+/// The most messiness a generator takes: the hole moves on every row.
+pub const MAX_MESSINESS: u32 = 100;
+/// Rows of garbage in a cheese race unless `--cheese=<rows>` says
+/// otherwise.
+pub const DEFAULT_CHEESE_ROWS: usize = 10;
+/// Messiness of a cheese race unless `--messiness=<percent>` says
+/// otherwise.
+pub const DEFAULT_MESSINESS: u32 = 30;
+/// The most rows a cheese race starts with, leaving the top of the
+/// board clear.
+pub const MAX_CHEESE_ROWS: usize = NROWS - DANGER_ROWS;
+/// Rows of garbage in a garbage climb: the bottom half of the board.
+pub const CLIMB_ROWS: usize = NROWS / 2;
+/// How often the floor rises unless `--rising=<seconds>` says
+/// otherwise.
+pub const DEFAULT_RISE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The messiness set by `--messiness=<percent>`, or `DEFAULT_MESSINESS`.
+#[cfg(feature = "std")]
+fn messiness_from_args() -> Result<u32, String> {
+    match profile::args().find_map(|arg| arg.strip_prefix("--messiness=").map(String::from)) {
+        Some(percent) => percent
+            .parse()
+            .ok()
+            .filter(|percent| *percent <= MAX_MESSINESS)
+            .ok_or_else(|| {
+                format!(
+                    "invalid --messiness={:?}, expected 0 to {}",
+                    percent, MAX_MESSINESS
+                )
+            }),
+        None => Ok(DEFAULT_MESSINESS),
+    }
+}
+
+/// Deals garbage rows, each full but for one hole, for cheese races and
+/// training. Messiness is the chance, in percent, that a row's hole is
+/// in a different column than the one below it: at 0 the stack is
+/// clean, with a single well to dig down, and at `MAX_MESSINESS` it is
+/// cheesy, with the hole moving every row. Generators with the same
+/// seed and messiness deal the same rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Garbage {
+    rng: Rng,
+    messiness: u32,
+    /// Column of the last hole dealt, if any
+    hole: Option<usize>,
+}
+
+impl Garbage {
+    /// A generator with the given messiness, capped at `MAX_MESSINESS`.
+    pub fn new(seed: u64, messiness: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            messiness: messiness.min(MAX_MESSINESS),
+            hole: None,
+        }
+    }
+
+    pub fn messiness(&self) -> u32 {
+        self.messiness
+    }
+
+    /// The hole column of the next row. The first hole is anywhere.
+    pub fn next_hole(&mut self) -> usize {
+        let hole = match self.hole {
+            None => self.rng.below(NCOLS as u64) as usize,
+            Some(hole) if self.rng.below(MAX_MESSINESS as u64) < self.messiness as u64 => {
+                // Any other column, all equally likely.
+                (hole + 1 + self.rng.below(NCOLS as u64 - 1) as usize) % NCOLS
+            }
+            Some(hole) => hole,
+        };
+        self.hole = Some(hole);
+        hole
+    }
+
+    /// The hole columns of the next `count` rows, to be passed to
+    /// `Tetris::insert_garbage`.
+    pub fn rows(&mut self, count: usize) -> Vec<usize> {
+        (0..count).map(|_| self.next_hole()).collect()
+    }
+}
+
+/// A cheese race: the game starts on a stack of garbage, and is over
+/// once all of it is cleared.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct CheeseRace {
+    pub rows: usize,
+    pub messiness: u32,
+    /// A garbage climb, for downstacking practice: scored on the pieces
+    /// it takes to dig out rather than the time.
+    pub climb: bool,
+}
+
+impl CheeseRace {
+    /// The race set up by `--cheese` or `--cheese=<rows>`, or the climb
+    /// set up by `--climb`, with `--messiness=<percent>` from 0 (clean)
+    /// to 100 (cheesy), if any.
+    #[cfg(feature = "std")]
+    pub fn from_args() -> Result<Option<Self>, String> {
+        let climb = profile::args().any(|arg| arg == "--climb");
+        let rows = match profile::args()
+            .find(|arg| arg == "--cheese" || arg.starts_with("--cheese="))
+        {
+            Some(_) if climb => return Err(String::from("--climb and --cheese don't go together")),
+            None if climb => CLIMB_ROWS,
+            Some(arg) if arg == "--cheese" => DEFAULT_CHEESE_ROWS,
+            Some(arg) => {
+                let rows = arg.strip_prefix("--cheese=").unwrap_or(&arg);
+                rows.parse()
+                    .ok()
+                    .filter(|rows| (1..=MAX_CHEESE_ROWS).contains(rows))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid cheese rows {:?}, expected 1 to {}",
+                            rows, MAX_CHEESE_ROWS
+                        )
+                    })?
+            }
+            None => return Ok(None),
+        };
+        let messiness = messiness_from_args()?;
+        Ok(Some(Self {
+            rows,
+            messiness,
+            climb,
+        }))
+    }
+
+    /// Lay the race's garbage, dealt from `seed`, under `tetris`, and
+    /// make clearing it the game's objective.
+    pub fn setup(&self, tetris: &mut Tetris, seed: u64) {
+        let holes = Garbage::new(seed, self.messiness).rows(self.rows);
+        tetris.insert_garbage(&holes);
+        tetris.set_objective(ClearGarbage);
+    }
+}
+
+/// The rising floor challenge: every so often a garbage row rises
+/// under the stack, whatever the player is doing, so they have to keep
+/// digging down to stay alive. The floor keeps time in gravity ticks,
+/// so it stops while the game is suspended and keeps pace with a
+/// practice game's speed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RisingFloor {
+    garbage: Garbage,
+    /// Gravity ticks between two rows
+    interval: u32,
+    /// Gravity ticks since the last row
+    ticks: u32,
+    gravity: Duration,
+}
+
+impl RisingFloor {
+    /// A row every `interval`, or as close to it as a game ticking every
+    /// `gravity` gets, with holes dealt from `seed`. At most `u32::MAX`
+    /// ticks go by between rows.
+    pub fn new(interval: Duration, gravity: Duration, seed: u64, messiness: u32) -> Self {
+        let ticks = interval.as_nanos() / gravity.as_nanos().max(1);
+        Self {
+            garbage: Garbage::new(seed, messiness),
+            interval: u32::try_from(ticks).unwrap_or(u32::MAX).max(1),
+            ticks: 0,
+            gravity,
+        }
+    }
+
+    /// The floor set up by `--rising` or `--rising=<seconds>`, with
+    /// `--messiness=<percent>`, if any.
+    #[cfg(feature = "std")]
+    pub fn from_args(gravity: Duration, seed: u64) -> Result<Option<Self>, String> {
+        let interval =
+            match profile::args().find(|arg| arg == "--rising" || arg.starts_with("--rising=")) {
+                Some(arg) if arg == "--rising" => DEFAULT_RISE_INTERVAL,
+                Some(arg) => {
+                    let seconds = arg.strip_prefix("--rising=").unwrap_or(&arg);
+                    seconds
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                        .filter(|interval| !interval.is_zero())
+                        .ok_or_else(|| format!("invalid rise interval {:?}", seconds))?
+                }
+                None => return Ok(None),
+            };
+        let messiness = messiness_from_args()?;
+        Ok(Some(Self::new(interval, gravity, seed, messiness)))
+    }
+
+    /// Count a gravity tick of `tetris`, raising the floor under it when
+    /// a row is due. Returns false once the game is over.
+    pub fn tick(&mut self, tetris: &mut Tetris) -> bool {
+        self.ticks += 1;
+        if self.ticks < self.interval {
+            return !tetris.is_over();
+        }
+        self.ticks = 0;
+        let hole = self.garbage.next_hole();
+        tetris.insert_garbage(&[hole])
+    }
+
+    /// Game time between two rows.
+    pub fn interval(&self) -> Duration {
+        self.gravity.saturating_mul(self.interval)
+    }
+
+    pub fn messiness(&self) -> u32 {
+        self.garbage.messiness()
+    }
+
+    /// Game time until the next row rises.
+    pub fn until_next(&self) -> Duration {
+        self.gravity.saturating_mul(self.interval - self.ticks)
+    }
+
+    /// Start the countdown to the next row over, for a new game.
+    pub fn restart(&mut self) {
+        self.ticks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::TopOut;
+
+    /// How many times the hole moves over `rows` rows.
+    fn changes(garbage: &mut Garbage, rows: usize) -> usize {
+        let holes = garbage.rows(rows);
+        holes.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+
+    #[test]
+    fn test_messiness() {
+        assert_eq!(changes(&mut Garbage::new(1, 0), 100), 0);
+        assert_eq!(changes(&mut Garbage::new(1, MAX_MESSINESS), 100), 99);
+        assert_eq!(Garbage::new(1, 500).messiness(), MAX_MESSINESS);
+        let some = changes(&mut Garbage::new(1, 30), 1000);
+        assert!((200..400).contains(&some), "{} changes", some);
+
+        let holes = Garbage::new(7, 50).rows(20);
+        assert_eq!(Garbage::new(7, 50).rows(20), holes);
+        assert!(holes.iter().all(|hole| *hole < NCOLS));
+    }
+
+    #[test]
+    fn test_cheese_race() {
+        let race = CheeseRace {
+            rows: 8,
+            messiness: 100,
+            climb: false,
+        };
+        let mut tetris = Tetris::new();
+        race.setup(&mut tetris, 3);
+        assert_eq!(tetris.garbage_rows(), 8);
+        assert_eq!(tetris.stack_height(), 8);
+        assert_eq!(tetris.top_out(), None);
+    }
+
+    #[test]
+    fn test_rising_floor() {
+        let gravity = Duration::from_millis(100);
+        let mut floor = RisingFloor::new(Duration::from_millis(500), gravity, 1, 0);
+        let mut tetris = Tetris::new();
+        for _ in 0..4 {
+            assert!(floor.tick(&mut tetris));
+        }
+        assert_eq!(floor.until_next(), gravity);
+        assert_eq!(tetris.garbage_rows(), 0);
+        assert!(floor.tick(&mut tetris));
+        assert_eq!(tetris.garbage_rows(), 1);
+        assert_eq!(floor.until_next(), 5 * gravity);
+
+        // Left alone, the floor ends the game.
+        while floor.tick(&mut tetris) {}
+        assert_eq!(tetris.top_out(), Some(TopOut::GarbageOut));
+
+        // Intervals too long to count in ticks wait as long as they can.
+        let nanos = Duration::from_nanos(1);
+        let floor = RisingFloor::new(Duration::from_secs(u64::MAX), nanos, 1, 0);
+        assert_eq!(floor.interval(), nanos * u32::MAX);
+    }
+}
@@ -0,0 +1,78 @@
+//! Ghost races: a recorded run played back beside the live game, a
+//! gravity tick at a time, like the time-trial ghosts of racing games.
+
+use super::game_state::Tetris;
+use super::tas::Movie;
+
+/// A past run, kept in step with the live game.
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    tetris: Tetris,
+    movie: Movie,
+    /// How many frames of the movie have been played
+    frame: usize,
+}
+
+impl Ghost {
+    /// Race `movie`, recorded from a game that started as `start`.
+    pub fn new(start: Tetris, movie: Movie) -> Self {
+        Self {
+            tetris: start,
+            movie,
+            frame: 0,
+        }
+    }
+
+    /// Play the next frame: its actions, then the tick that ends it.
+    /// Once the movie runs out the ghost stays where it is.
+    pub fn tick(&mut self) {
+        if let Some(actions) = self.movie.frames.get(self.frame) {
+            for action in actions {
+                self.tetris.event(*action);
+            }
+            self.tetris.tick();
+            self.frame += 1;
+        }
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.tetris
+    }
+
+    /// Whether the whole movie has been played.
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::game_state::Action;
+
+    #[test]
+    fn test_ghost_follows_movie() {
+        let start = GameConfig::new().seed(3).build();
+        let mut live = start.clone();
+        live.record_inputs(true);
+        for frame in 0..60 {
+            if frame % 4 == 0 {
+                live.event(Action::MoveLeft);
+            }
+            live.tick();
+        }
+        let movie = Movie::from_steps(live.take_inputs());
+
+        let mut ghost = Ghost::new(start, movie);
+        for _ in 0..30 {
+            ghost.tick();
+        }
+        assert!(!ghost.is_done());
+        for _ in 0..40 {
+            ghost.tick();
+        }
+        assert!(ghost.is_done());
+        assert_eq!(ghost.tetris().state_hash(), live.state_hash());
+    }
+}
@@ -0,0 +1,218 @@
+use super::game_state::{GameView, NCOLS, NROWS};
+use super::theme::{Rgb, Theme};
+
+/// Side length of a single grid cell in the transmitted bitmap, in
+/// pixels.
+const CELL: usize = 16;
+/// Gap between neighbouring cells, in pixels.
+const GAP: usize = 1;
+const WIDTH: usize = NCOLS * CELL;
+const HEIGHT: usize = NROWS * CELL;
+
+/// Kitty caps the payload of a single escape sequence at 4096 bytes.
+const KITTY_CHUNK: usize = 4096;
+
+/// Terminal image protocols we know how to speak.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Protocol {
+    Kitty,
+    ITerm2,
+}
+
+impl Protocol {
+    /// Guess the image protocol supported by the hosting terminal from
+    /// the environment, if any.
+    pub fn detect() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        if !var("KITTY_WINDOW_ID").is_empty() || var("TERM") == "xterm-kitty" {
+            Some(Protocol::Kitty)
+        } else if var("TERM_PROGRAM") == "iTerm.app" || !var("ITERM_SESSION_ID").is_empty() {
+            Some(Protocol::ITerm2)
+        } else {
+            None
+        }
+    }
+
+    /// Build the escape sequence that draws the board as a bitmap
+    /// spanning `cols` x `rows` terminal cells at the cursor position.
+    pub fn render(self, view: &GameView, theme: &Theme, cols: u16, rows: u16) -> String {
+        let pixels = rasterize(view, theme);
+        match self {
+            Protocol::Kitty => kitty(&pixels, cols, rows),
+            Protocol::ITerm2 => iterm2(&pixels, cols, rows),
+        }
+    }
+}
+
+/// The board as a standalone PNG file, with `text` entries such as
+/// `("Comment", "40 lines")` stored as metadata.
+pub fn png_file(view: &GameView, theme: &Theme, text: &[(&str, &str)]) -> Vec<u8> {
+    png(&rasterize(view, theme), WIDTH as u32, HEIGHT as u32, text)
+}
+
+/// Rasterize the board into packed 8-bit RGB pixels.
+fn rasterize(view: &GameView, theme: &Theme) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 3);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let on_gap = x % CELL < GAP || y % CELL < GAP;
+            let Rgb(r, g, b) = if on_gap {
+                theme.background
+            } else {
+                theme.view_rgb(view, y / CELL, x / CELL)
+            };
+            pixels.extend_from_slice(&[r, g, b]);
+        }
+    }
+    pixels
+}
+
+fn kitty(pixels: &[u8], cols: u16, rows: u16) -> String {
+    let payload = base64(pixels);
+    // Drop the previous frame, then transmit and place the new one
+    // without moving the cursor.
+    let mut out = String::from("\x1b_Ga=d,d=i,i=1,q=2\x1b\\");
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            out += &format!(
+                "\x1b_Ga=T,f=24,i=1,q=2,C=1,s={},v={},c={},r={},m={};",
+                WIDTH, HEIGHT, cols, rows, more
+            );
+        } else {
+            out += &format!("\x1b_Gm={};", more);
+        }
+        out += std::str::from_utf8(chunk).expect("base64 is ascii");
+        out += "\x1b\\";
+    }
+    out
+}
+
+fn iterm2(pixels: &[u8], cols: u16, rows: u16) -> String {
+    let png = png(pixels, WIDTH as u32, HEIGHT as u32, &[]);
+    format!(
+        "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=0;size={}:{}\x07",
+        cols,
+        rows,
+        png.len(),
+        base64(&png)
+    )
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// Encode packed RGB pixels as a PNG, with `(keyword, text)` metadata.
+/// The image data is stored without compression, which keeps this
+/// dependency-free; the frames are small enough that it doesn't matter.
+fn png(pixels: &[u8], width: u32, height: u32, text: &[(&str, &str)]) -> Vec<u8> {
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    let stride = width as usize * 3;
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in pixels.chunks(stride) {
+        // filter type: none
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push(if i + 1 == blocks.len() { 1 } else { 0 });
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8-bit depth, truecolor, default compression/filter, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    for (keyword, text) in text {
+        chunk(
+            &mut out,
+            b"tEXt",
+            format!("{}\0{}", keyword, text).as_bytes(),
+        );
+    }
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
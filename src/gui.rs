@@ -0,0 +1,68 @@
+use super::config::GameConfig;
+use super::game_state::{Action, Tetris, NCOLS, NROWS};
+use super::theme::{Rgb, Theme};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use std::time::Instant;
+
+/// Side length of a single grid cell, in pixels.
+const CELL: usize = 24;
+/// Gap between neighbouring cells, in pixels.
+const GAP: usize = 1;
+const WIDTH: usize = NCOLS * CELL;
+const HEIGHT: usize = NROWS * CELL;
+
+fn pixel(Rgb(r, g, b): Rgb) -> u32 {
+    (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+/// Paint the board of the given game state into a 0RGB pixel buffer.
+fn draw(tetris: &Tetris, theme: &Theme, buffer: &mut [u32]) {
+    let view = tetris.view();
+    for (idx, out) in buffer.iter_mut().enumerate() {
+        let (x, y) = (idx % WIDTH, idx / WIDTH);
+        let (row, col) = (y / CELL, x / CELL);
+        let on_gap = x % CELL < GAP || y % CELL < GAP;
+        *out = if on_gap {
+            pixel(theme.background)
+        } else {
+            pixel(theme.view_rgb(&view, row, col))
+        };
+    }
+}
+
+/// Run the game in a pixel-based window instead of the terminal.
+/// Returns when the window is closed, Esc is pressed, or the game
+/// is over.
+pub fn run(config: GameConfig) -> Result<(), minifb::Error> {
+    let mut window = Window::new("tetris-rs", WIDTH, HEIGHT, WindowOptions::default())?;
+    window.set_target_fps(60);
+
+    let mut tetris = config.build();
+    let theme = Theme::unicode();
+    let mut buffer = vec![pixel(theme.background); WIDTH * HEIGHT];
+    let mut last_tick = Instant::now();
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::A => tetris.event(Action::MoveLeft),
+                Key::D => tetris.event(Action::MoveRight),
+                Key::Q => tetris.event(Action::RotateCcw),
+                Key::E => tetris.event(Action::RotateCw),
+                _ => (),
+            }
+        }
+
+        if last_tick.elapsed() >= tetris.gravity() {
+            last_tick = Instant::now();
+            if !tetris.tick().continues() {
+                break;
+            }
+        }
+
+        draw(&tetris, &theme, &mut buffer);
+        window.update_with_buffer(&buffer, WIDTH, HEIGHT)?;
+    }
+
+    Ok(())
+}
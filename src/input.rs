@@ -0,0 +1,708 @@
+use super::game_state::Action;
+use super::profile;
+use super::tas::action_named;
+use std::time::{Duration, Instant};
+use termion::event::Key;
+
+/// Ask the terminal to report every key as an escape code, including
+/// press/release event types (kitty keyboard protocol flags 1, 2 and
+/// 8). Terminals that don't speak the protocol ignore it.
+pub const ENABLE_KEY_EVENTS: &str = "\x1b[>11u";
+/// Restore the terminal's previous keyboard mode.
+pub const DISABLE_KEY_EVENTS: &str = "\x1b[<u";
+/// Ask the terminal to report when it gains or loses focus.
+pub const ENABLE_FOCUS_EVENTS: &str = "\x1b[?1004h";
+/// Stop focus reports.
+pub const DISABLE_FOCUS_EVENTS: &str = "\x1b[?1004l";
+
+/// Default delay before a held movement key starts repeating.
+const DAS: Duration = Duration::from_millis(170);
+/// Default interval between repeats of a held movement key.
+const ARR: Duration = Duration::from_millis(50);
+
+/// How held movement keys repeat, and how hard drops are guarded.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Handling {
+    /// Delayed auto shift: how long a key is held before it repeats
+    pub das: Duration,
+    /// Auto repeat rate: the time between repeats. At zero, a repeat
+    /// shifts the piece all the way to the wall at once.
+    pub arr: Duration,
+    /// How long hard drops are ignored after a piece spawns, so that a
+    /// key meant for the last piece doesn't drop the next one too
+    pub misdrop_guard: Duration,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self {
+            das: DAS,
+            arr: ARR,
+            misdrop_guard: Duration::ZERO,
+        }
+    }
+}
+
+impl Handling {
+    /// Read `--das=<ms>`, `--arr=<ms>` and `--misdrop-guard=<ms>` from the
+    /// command line, defaulting to the usual timing and no guard.
+    pub fn from_args() -> Result<Self, String> {
+        let arg = |prefix: &str, default: Duration| -> Result<Duration, String> {
+            match profile::args().find_map(|arg| arg.strip_prefix(prefix).map(String::from)) {
+                Some(value) => value
+                    .parse()
+                    .map(Duration::from_millis)
+                    .map_err(|_| format!("invalid {}{:?}, expected milliseconds", prefix, value)),
+                None => Ok(default),
+            }
+        };
+        Ok(Self {
+            das: arg("--das=", DAS)?,
+            arr: arg("--arr=", ARR)?,
+            misdrop_guard: arg("--misdrop-guard=", Duration::ZERO)?,
+        })
+    }
+}
+
+/// A physical key that can be bound to a game event.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum KeyCode {
+    Char(char),
+    Ctrl(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Esc,
+    Backspace,
+    F(u8),
+}
+
+impl KeyCode {
+    /// Convert a key decoded by termion, if it's one we can bind.
+    pub fn from_termion(key: Key) -> Option<Self> {
+        match key {
+            Key::Char(c) => Some(KeyCode::Char(c)),
+            Key::Ctrl(c) => Some(KeyCode::Ctrl(c)),
+            Key::Left => Some(KeyCode::Left),
+            Key::Right => Some(KeyCode::Right),
+            Key::Up => Some(KeyCode::Up),
+            Key::Down => Some(KeyCode::Down),
+            Key::Esc => Some(KeyCode::Esc),
+            Key::Backspace => Some(KeyCode::Backspace),
+            Key::F(n) => Some(KeyCode::F(n)),
+            _ => None,
+        }
+    }
+
+    /// A key by name, as given on the command line: a single character,
+    /// `left`, `right`, `up`, `down`, or `f1` to `f12`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(KeyCode::Char(c));
+        }
+        match name {
+            "left" => Some(KeyCode::Left),
+            "right" => Some(KeyCode::Right),
+            "up" => Some(KeyCode::Up),
+            "down" => Some(KeyCode::Down),
+            _ => name
+                .strip_prefix('f')
+                .and_then(|n| n.parse().ok())
+                .filter(|n| (1..=12).contains(n))
+                .map(KeyCode::F),
+        }
+    }
+}
+
+/// Maps keys to game events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyCode, Action)>,
+    /// Keys that send several events at once, taking over any binding
+    /// of the same key
+    macros: Vec<(KeyCode, Vec<Action>)>,
+}
+
+/// The action on a mirrored board that looks like `action` on a normal
+/// one.
+fn mirror(action: Action) -> Action {
+    match action {
+        Action::MoveLeft => Action::MoveRight,
+        Action::MoveRight => Action::MoveLeft,
+        Action::RotateCcw => Action::RotateCw,
+        Action::RotateCw => Action::RotateCcw,
+        action => action,
+    }
+}
+
+/// Names of the built-in keymap presets, as accepted by
+/// `Keymap::preset`.
+pub const PRESETS: [&str; 5] = ["wasd", "arrows", "vi", "guideline", "onehand"];
+
+/// The actions a cluster of keys binds, in the order of its keys.
+const CLUSTER: [Action; 9] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateCcw,
+    Action::RotateCw,
+    Action::Rotate180,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::Hold,
+    Action::Pause,
+];
+
+/// The one-handed preset's cluster: every action around WASD, in reach
+/// of the left hand.
+const ONE_HAND: &str = "adqexswcf";
+
+impl Keymap {
+    /// One of the built-in presets, by name.
+    pub fn preset(name: &str) -> Option<Self> {
+        use KeyCode::*;
+        let bindings = match name {
+            "wasd" => vec![
+                (Char('a'), Action::MoveLeft),
+                (Char('d'), Action::MoveRight),
+                (Char('q'), Action::RotateCcw),
+                (Char('e'), Action::RotateCw),
+            ],
+            "arrows" => vec![
+                (Left, Action::MoveLeft),
+                (Right, Action::MoveRight),
+                (Down, Action::RotateCcw),
+                (Up, Action::RotateCw),
+            ],
+            "vi" => vec![
+                (Char('h'), Action::MoveLeft),
+                (Char('l'), Action::MoveRight),
+                (Char('j'), Action::RotateCcw),
+                (Char('k'), Action::RotateCw),
+            ],
+            "guideline" => vec![
+                (Left, Action::MoveLeft),
+                (Right, Action::MoveRight),
+                (Char('z'), Action::RotateCcw),
+                (Up, Action::RotateCw),
+                (Char('x'), Action::RotateCw),
+            ],
+            "onehand" => return Self::cluster(ONE_HAND).ok(),
+            _ => return None,
+        };
+        Some(Self {
+            bindings,
+            macros: Vec::new(),
+        })
+    }
+
+    /// A keymap with a key for each action, in the order left, right,
+    /// counterclockwise, clockwise, 180, soft drop, hard drop, hold and
+    /// pause. `_` leaves an action unbound, and the actions after the
+    /// last key are too.
+    pub fn cluster(keys: &str) -> Result<Self, String> {
+        if keys.chars().count() > CLUSTER.len() {
+            return Err(format!(
+                "too many keys in cluster {:?}, expected at most {}",
+                keys,
+                CLUSTER.len()
+            ));
+        }
+        let mut bindings: Vec<(KeyCode, Action)> = Vec::new();
+        for (key, action) in keys.chars().zip(CLUSTER) {
+            let key = KeyCode::Char(key);
+            if key == KeyCode::Char('_') {
+                continue;
+            }
+            if bindings.iter().any(|(bound, _)| *bound == key) {
+                return Err(format!("key {:?} is in cluster {:?} twice", key, keys));
+            }
+            bindings.push((key, action));
+        }
+        Ok(Self {
+            bindings,
+            macros: Vec::new(),
+        })
+    }
+
+    /// The cluster of a `--cluster=<keys>` command line argument, or
+    /// else the preset named by `--keys=<preset>`, defaulting to WASD,
+    /// with the macros of every `--macro=` option.
+    pub fn from_args() -> Result<Self, String> {
+        let cluster =
+            profile::args().find_map(|arg| arg.strip_prefix("--cluster=").map(String::from));
+        let preset = profile::args().find_map(|arg| arg.strip_prefix("--keys=").map(String::from));
+        let keymap = match (cluster, preset) {
+            (Some(keys), _) => Self::cluster(&keys)?,
+            (None, Some(name)) => Self::preset(&name).ok_or_else(|| {
+                format!(
+                    "unknown key preset {:?}, expected one of: {}",
+                    name,
+                    PRESETS.join(", ")
+                )
+            })?,
+            (None, None) => Self::default(),
+        };
+        let keymap = profile::args()
+            .filter_map(|arg| arg.strip_prefix("--macro=").map(String::from))
+            .try_fold(keymap, |keymap, spec| keymap.with_macro(&spec))?;
+        if profile::args().any(|arg| arg == "--mirror") {
+            Ok(keymap.mirrored())
+        } else {
+            Ok(keymap)
+        }
+    }
+
+    /// Bind a key to a sequence of actions, given as the key's name, a
+    /// colon and the actions' names from movie files separated by
+    /// commas, e.g. `w:cw,cw,hard`. A key bound twice keeps its first
+    /// macro, as options go by first match. Macros can't pause or
+    /// restart the game.
+    pub fn with_macro(mut self, spec: &str) -> Result<Self, String> {
+        let (key, actions) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("invalid macro {:?}, expected <key>:<actions>", spec))?;
+        let key = KeyCode::from_name(key)
+            .ok_or_else(|| format!("unknown key {:?} in macro {:?}", key, spec))?;
+        let actions = actions
+            .split(',')
+            .map(|name| match action_named(name.trim()) {
+                Some(Action::Pause) | Some(Action::Restart) => Err(format!(
+                    "action {:?} is not allowed in macro {:?}",
+                    name, spec
+                )),
+                Some(action) => Ok(action),
+                None => Err(format!("unknown action {:?} in macro {:?}", name, spec)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if self.sequence(key).is_none() {
+            self.macros.push((key, actions));
+        }
+        Ok(self)
+    }
+
+    /// The keymap for a mirrored board: left and right swap, and so do
+    /// the two turns, so every key still does what it shows.
+    pub fn mirrored(self) -> Self {
+        let bindings = self
+            .bindings
+            .into_iter()
+            .map(|(key, action)| (key, mirror(action)))
+            .collect();
+        let macros = self
+            .macros
+            .into_iter()
+            .map(|(key, actions)| (key, actions.into_iter().map(mirror).collect()))
+            .collect();
+        Self { bindings, macros }
+    }
+
+    /// The game event bound to a key, if any. Keys with a macro have
+    /// none.
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        if self.sequence(key).is_some() {
+            return None;
+        }
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, evt)| *evt)
+    }
+
+    /// The game events a key's macro sends, in order, if it has one.
+    pub fn sequence(&self, key: KeyCode) -> Option<&[Action]> {
+        self.macros
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, actions)| actions.as_slice())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset("wasd").unwrap()
+    }
+}
+
+/// A key transition reported through the kitty keyboard protocol.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum KeyTransition {
+    Press(KeyCode),
+    Repeat(KeyCode),
+    Release(KeyCode),
+}
+
+/// Function key numbers for the `CSI code ~` form, indexed by code.
+fn function_key(code: u32) -> Option<u8> {
+    match code {
+        11..=15 => Some(code as u8 - 10),
+        17..=21 => Some(code as u8 - 11),
+        23 | 24 => Some(code as u8 - 12),
+        _ => None,
+    }
+}
+
+/// Parse a focus report: `CSI I` when the terminal gains focus, and
+/// `CSI O` when it loses it.
+pub fn parse_focus(bytes: &[u8]) -> Option<bool> {
+    match bytes {
+        b"\x1b[I" => Some(true),
+        b"\x1b[O" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse a `CSI code[:alternates] [; modifiers[:event]] u` sequence, or
+/// the `CSI 1; modifiers[:event] {A,B,C,D,P,Q,S}` and `CSI code ~`
+/// forms used for arrow and function keys.
+pub fn parse_kitty(bytes: &[u8]) -> Option<KeyTransition> {
+    let body = std::str::from_utf8(bytes).ok()?.strip_prefix("\x1b[")?;
+    let last = body.chars().last()?;
+    let body = &body[..body.len() - last.len_utf8()];
+    let mut params = body.split(';');
+    let code = params.next()?.split(':').next()?;
+    let mut modifiers = params.next().unwrap_or("1").split(':');
+    // The modifier parameter is one more than a bitmask in which 4 is
+    // ctrl.
+    let ctrl = modifiers.next()?.parse::<u32>().ok()?.saturating_sub(1) & 4 != 0;
+    let key = match last {
+        'u' => match code.parse().ok()? {
+            27 => KeyCode::Esc,
+            127 => KeyCode::Backspace,
+            code if ctrl => KeyCode::Ctrl(std::char::from_u32(code)?),
+            code => KeyCode::Char(std::char::from_u32(code)?),
+        },
+        'A' => KeyCode::Up,
+        'B' => KeyCode::Down,
+        'C' => KeyCode::Right,
+        'D' => KeyCode::Left,
+        'P' => KeyCode::F(1),
+        'Q' => KeyCode::F(2),
+        'S' => KeyCode::F(4),
+        '~' => KeyCode::F(function_key(code.parse().ok()?)?),
+        _ => return None,
+    };
+    match modifiers.next().unwrap_or("1") {
+        "1" => Some(KeyTransition::Press(key)),
+        "2" => Some(KeyTransition::Repeat(key)),
+        "3" => Some(KeyTransition::Release(key)),
+        _ => None,
+    }
+}
+
+/// Tracks which actions are held down so that movement can auto-repeat
+/// independently of the terminal's key repeat, and so that holding one
+/// key doesn't interrupt another.
+#[derive(Debug, Default)]
+pub struct KeyState {
+    handling: Handling,
+    /// Held actions and when each should next fire, if ever again
+    held: Vec<(Action, Option<Instant>)>,
+    /// How many pieces had been placed when the falling piece spawned,
+    /// and when that was
+    spawned: Option<(u32, Instant)>,
+}
+
+impl KeyState {
+    pub fn new(handling: Handling) -> Self {
+        Self {
+            handling,
+            held: Vec::new(),
+            spawned: None,
+        }
+    }
+
+    /// Take note of how many pieces the game has placed: when the count
+    /// changes, a new piece spawned.
+    pub fn placed(&mut self, pieces: u32, now: Instant) {
+        if self.spawned.map(|(placed, _)| placed) != Some(pieces) {
+            self.spawned = Some((pieces, now));
+        }
+    }
+
+    /// Whether `evt` goes through to the game, rather than being a hard
+    /// drop within the misdrop guard.
+    pub fn allows(&self, evt: Action, now: Instant) -> bool {
+        match self.spawned {
+            Some((_, at)) if evt == Action::HardDrop => now >= at + self.handling.misdrop_guard,
+            _ => true,
+        }
+    }
+
+    /// Record a key press. Returns false if the action was already held,
+    /// i.e. this is not a new press.
+    pub fn press(&mut self, evt: Action, now: Instant) -> bool {
+        if self.held.iter().any(|(held, _)| *held == evt) {
+            return false;
+        }
+        self.held.push((evt, Some(now + self.handling.das)));
+        true
+    }
+
+    pub fn release(&mut self, evt: Action) {
+        self.held.retain(|(held, _)| *held != evt);
+    }
+
+    /// Held movement actions whose repeat is due, rescheduling them.
+    /// With an ARR of zero, see `shifts_to_wall`, each fires only once
+    /// per press.
+    pub fn due(&mut self, now: Instant) -> Vec<Action> {
+        let mut fired = Vec::new();
+        let to_wall = self.shifts_to_wall();
+        for (evt, next) in self.held.iter_mut() {
+            if repeats(*evt) && next.is_some_and(|next| next <= now) {
+                fired.push(*evt);
+                *next = Some(now + self.handling.arr).filter(|_| !to_wall);
+            }
+        }
+        fired
+    }
+
+    /// Whether a repeat from `due` stands for moving the piece as far as
+    /// it goes, rather than by one column.
+    pub fn shifts_to_wall(&self) -> bool {
+        self.handling.arr.is_zero()
+    }
+
+    /// The earliest instant at which a held action repeats.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.held
+            .iter()
+            .filter(|(evt, _)| repeats(*evt))
+            .filter_map(|(_, next)| *next)
+            .min()
+    }
+}
+
+/// Only movement auto-repeats; rotations fire once per press.
+fn repeats(evt: Action) -> bool {
+    matches!(evt, Action::MoveLeft | Action::MoveRight)
+}
+
+/// Keys pressed per piece placed (KPP), the measure of how efficiently
+/// pieces are put in place: at best one key per move, turn and drop.
+/// Auto-repeats aren't presses, though terminals without the kitty
+/// protocol can't tell them apart.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeysPerPiece {
+    keys: u32,
+    pieces: u32,
+}
+
+impl KeysPerPiece {
+    /// Count a key pressed for `evt`. Pausing and restarting don't place
+    /// pieces, so they don't count.
+    pub fn press(&mut self, evt: Action) {
+        if !matches!(evt, Action::Pause | Action::Restart) {
+            self.keys += 1;
+        }
+    }
+
+    /// Follow the game's count of pieces placed. When it goes back, as
+    /// on a restart or a rewind, counting starts over.
+    pub fn placed(&mut self, pieces: u32) {
+        if pieces < self.pieces {
+            self.keys = 0;
+        }
+        self.pieces = pieces;
+    }
+
+    /// The keys per piece so far, once a piece has been placed.
+    pub fn value(&self) -> Option<f64> {
+        Some(self.pieces)
+            .filter(|pieces| *pieces > 0)
+            .map(|pieces| self.keys as f64 / pieces as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kitty() {
+        use KeyCode::*;
+        assert_eq!(
+            parse_kitty(b"\x1b[97u"),
+            Some(KeyTransition::Press(Char('a')))
+        );
+        assert_eq!(
+            parse_kitty(b"\x1b[97;1:2u"),
+            Some(KeyTransition::Repeat(Char('a')))
+        );
+        assert_eq!(
+            parse_kitty(b"\x1b[100:68;2:3u"),
+            Some(KeyTransition::Release(Char('d')))
+        );
+        assert_eq!(parse_kitty(b"\x1b[27u"), Some(KeyTransition::Press(Esc)));
+        assert_eq!(
+            parse_kitty(b"\x1b[122;5u"),
+            Some(KeyTransition::Press(Ctrl('z')))
+        );
+        assert_eq!(
+            parse_kitty(b"\x1b[1;1:3D"),
+            Some(KeyTransition::Release(Left))
+        );
+        assert_eq!(parse_kitty(b"\x1b[2~"), None);
+        assert_eq!(parse_kitty(b"\x1b[13~"), Some(KeyTransition::Press(F(3))));
+        assert_eq!(
+            parse_kitty(b"\x1b[1;1:3S"),
+            Some(KeyTransition::Release(F(4)))
+        );
+        assert_eq!(parse_kitty(b"\x1b[24~"), Some(KeyTransition::Press(F(12))));
+        assert_eq!(
+            parse_kitty(b"\x1b[127;1:2u"),
+            Some(KeyTransition::Repeat(Backspace))
+        );
+        assert_eq!(parse_kitty("\x1b[1;\u{e9}".as_bytes()), None);
+    }
+
+    #[test]
+    fn test_parse_focus() {
+        assert_eq!(parse_focus(b"\x1b[I"), Some(true));
+        assert_eq!(parse_focus(b"\x1b[O"), Some(false));
+        assert_eq!(parse_focus(b"\x1b[97u"), None);
+    }
+
+    #[test]
+    fn test_keymap_presets() {
+        for name in PRESETS.iter() {
+            assert!(Keymap::preset(name).is_some());
+        }
+        assert!(Keymap::preset("emacs").is_none());
+
+        let keys = Keymap::preset("guideline").unwrap();
+        assert_eq!(keys.action(KeyCode::Char('x')), Some(Action::RotateCw));
+        assert_eq!(keys.action(KeyCode::Up), Some(Action::RotateCw));
+        assert_eq!(keys.action(KeyCode::Char('a')), None);
+
+        let mirrored = keys.mirrored();
+        assert_eq!(mirrored.action(KeyCode::Char('x')), Some(Action::RotateCcw));
+        assert_eq!(mirrored.action(KeyCode::Left), Some(Action::MoveRight));
+
+        // The one-handed preset has a key for every action but restart.
+        let keys = Keymap::preset("onehand").unwrap();
+        for action in CLUSTER {
+            assert!(keys.bindings.iter().any(|(_, bound)| *bound == action));
+        }
+        assert_eq!(keys.action(KeyCode::Char('w')), Some(Action::HardDrop));
+
+        let keys = Keymap::cluster("jl_i").unwrap();
+        assert_eq!(keys.action(KeyCode::Char('l')), Some(Action::MoveRight));
+        assert_eq!(keys.action(KeyCode::Char('i')), Some(Action::RotateCw));
+        assert_eq!(keys.bindings.len(), 3);
+        assert!(Keymap::cluster("jlj").is_err());
+        assert!(Keymap::cluster("adqexswcfg").is_err());
+    }
+
+    #[test]
+    fn test_macros() {
+        let keys = Keymap::default()
+            .with_macro("w:cw, cw,hard")
+            .unwrap()
+            .with_macro("a:hold")
+            .unwrap()
+            .with_macro("w:ccw")
+            .unwrap();
+        use Action::*;
+        assert_eq!(
+            keys.sequence(KeyCode::Char('w')),
+            Some(&[RotateCw, RotateCw, HardDrop][..])
+        );
+        // A macro takes over the key's binding.
+        assert_eq!(keys.action(KeyCode::Char('a')), None);
+        assert_eq!(keys.action(KeyCode::Char('d')), Some(MoveRight));
+        assert_eq!(
+            keys.mirrored().sequence(KeyCode::Char('w')),
+            Some(&[RotateCcw, RotateCcw, HardDrop][..])
+        );
+
+        assert_eq!(KeyCode::from_name("f5"), Some(KeyCode::F(5)));
+        assert_eq!(KeyCode::from_name("up"), Some(KeyCode::Up));
+        assert_eq!(KeyCode::from_name("f13"), None);
+        assert!(Keymap::default().with_macro("w").is_err());
+        assert!(Keymap::default().with_macro("w:spin").is_err());
+        assert!(Keymap::default().with_macro("w:cw,restart").is_err());
+        assert!(Keymap::default().with_macro("w:pause").is_err());
+        assert!(Keymap::default().with_macro("enter:hard").is_err());
+    }
+
+    #[test]
+    fn test_keys_per_piece() {
+        let mut kpp = KeysPerPiece::default();
+        for evt in [
+            Action::MoveLeft,
+            Action::RotateCw,
+            Action::Pause,
+            Action::HardDrop,
+        ] {
+            kpp.press(evt);
+        }
+        assert_eq!(kpp.value(), None);
+        kpp.placed(1);
+        kpp.press(Action::HardDrop);
+        kpp.placed(2);
+        assert_eq!(kpp.value(), Some(2.0));
+        kpp.placed(0);
+        assert_eq!(kpp.value(), None);
+        kpp.press(Action::HardDrop);
+        kpp.placed(1);
+        assert_eq!(kpp.value(), Some(1.0));
+    }
+
+    #[test]
+    fn test_key_state_repeat() {
+        let start = Instant::now();
+        let mut keys = KeyState::default();
+        assert!(keys.press(Action::MoveLeft, start));
+        assert!(!keys.press(Action::MoveLeft, start));
+        assert!(keys.press(Action::RotateCw, start));
+        assert_eq!(keys.deadline(), Some(start + DAS));
+
+        assert!(keys.due(start).is_empty());
+        assert_eq!(keys.due(start + DAS), vec![Action::MoveLeft]);
+        assert!(keys.due(start + DAS).is_empty());
+        assert_eq!(keys.due(start + DAS + ARR), vec![Action::MoveLeft]);
+
+        keys.release(Action::MoveLeft);
+        assert_eq!(keys.deadline(), None);
+
+        let fast = Handling {
+            das: Duration::from_millis(80),
+            arr: Duration::ZERO,
+            misdrop_guard: Duration::ZERO,
+        };
+        let mut keys = KeyState::new(fast);
+        assert!(keys.shifts_to_wall());
+        keys.press(Action::MoveRight, start);
+        assert_eq!(keys.deadline(), Some(start + fast.das));
+        assert_eq!(keys.due(start + fast.das), vec![Action::MoveRight]);
+        // Once at the wall, the key waits to be pressed again.
+        assert_eq!(keys.deadline(), None);
+        assert!(keys.due(start + fast.das * 2).is_empty());
+        keys.release(Action::MoveRight);
+        keys.press(Action::MoveRight, start + fast.das * 2);
+        assert_eq!(keys.deadline(), Some(start + fast.das * 3));
+    }
+
+    #[test]
+    fn test_misdrop_guard() {
+        let guard = Duration::from_millis(100);
+        let mut keys = KeyState::new(Handling {
+            misdrop_guard: guard,
+            ..Handling::default()
+        });
+        let start = Instant::now();
+        keys.placed(0, start);
+        assert!(!keys.allows(Action::HardDrop, start));
+        assert!(keys.allows(Action::MoveLeft, start));
+        assert!(keys.allows(Action::HardDrop, start + guard));
+        // Only a new piece starts the guard over.
+        keys.placed(0, start + guard);
+        assert!(keys.allows(Action::HardDrop, start + guard));
+        keys.placed(1, start + guard);
+        assert!(!keys.allows(Action::HardDrop, start + guard));
+        assert!(KeyState::new(Handling::default()).allows(Action::HardDrop, start));
+    }
+}
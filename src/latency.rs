@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Only the most recent samples are kept.
+const MAX_SAMPLES: usize = 1000;
+
+/// Input latency: the time from a key arriving to the frame that shows
+/// its effect being on its way to the terminal. Keys that arrive
+/// between two frames are measured from the first of them.
+#[derive(Debug, Default)]
+pub struct Latency {
+    samples: VecDeque<Duration>,
+    /// When the earliest key not yet drawn arrived
+    pending: Option<Instant>,
+}
+
+impl Latency {
+    /// A key arrived `at`.
+    pub fn received(&mut self, at: Instant) {
+        self.pending.get_or_insert(at);
+    }
+
+    /// A frame was drawn `at`.
+    pub fn rendered(&mut self, at: Instant) {
+        if let Some(received) = self.pending.take() {
+            if self.samples.len() == MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+            self.samples
+                .push_back(at.saturating_duration_since(received));
+        }
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.samples.iter().sum();
+        total.checked_div(self.samples.len() as u32)
+    }
+
+    /// The latency `percent` of samples were at or under.
+    pub fn percentile(&self, percent: usize) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * percent).div_ceil(100).max(1);
+        sorted.get(rank - 1).copied()
+    }
+
+    /// The mean and 95th percentile, or `None` before the first sample.
+    pub fn summary(&self) -> Option<String> {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        Some(format!(
+            "avg {:.1}ms, p95 {:.1}ms",
+            ms(self.mean()?),
+            ms(self.percentile(95)?)
+        ))
+    }
+
+    /// Write the summary to the log, if there is one.
+    pub fn log(&self) {
+        if let Some(summary) = self.summary() {
+            info!(samples = self.samples.len(), %summary, "input latency");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency() {
+        let mut latency = Latency::default();
+        assert_eq!(latency.summary(), None);
+        let start = Instant::now();
+        for ms in 1..=20 {
+            latency.received(start);
+            // Only the first of the keys before a frame counts.
+            latency.received(start + Duration::from_millis(1));
+            latency.rendered(start + Duration::from_millis(ms));
+        }
+        // A frame without a key is no sample.
+        latency.rendered(start + Duration::from_secs(1));
+        assert_eq!(latency.mean(), Some(Duration::from_micros(10_500)));
+        assert_eq!(latency.percentile(95), Some(Duration::from_millis(19)));
+        assert_eq!(latency.percentile(0), Some(Duration::from_millis(1)));
+        assert_eq!(latency.summary().as_deref(), Some("avg 10.5ms, p95 19.0ms"));
+
+        for _ in 0..MAX_SAMPLES {
+            latency.received(start);
+            latency.rendered(start);
+        }
+        assert_eq!(latency.mean(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,80 @@
+//! The tetris-rs engine and frontends, shared by the game and its
+//! tools.
+//!
+//! Without the default `std` feature only the engine is built, with
+//! `#![no_std]` and `alloc`, so that it can be ported to embedded
+//! targets.
+//!
+//! Programs that only drive the engine can `use tetris_rs::prelude::*`
+//! rather than reach into the modules.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod achievements;
+pub mod ai;
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod announce;
+pub mod board;
+#[cfg(feature = "std")]
+pub mod chat;
+pub mod config;
+pub mod console;
+#[cfg(feature = "std")]
+pub mod crash;
+#[cfg(feature = "discord")]
+pub mod discord;
+#[cfg(feature = "std")]
+pub mod editor;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+#[cfg(feature = "std")]
+pub mod eval;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod flags;
+pub mod game_state;
+pub mod garbage;
+pub mod ghost;
+#[cfg(feature = "std")]
+pub mod graphics;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "std")]
+pub mod input;
+#[cfg(feature = "std")]
+pub mod latency;
+#[cfg(feature = "std")]
+pub mod logging;
+pub mod objective;
+pub mod prelude;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod puzzle;
+pub mod randomizer;
+pub mod replay;
+pub mod rewind;
+pub mod rotation;
+pub mod savestate;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod sprint;
+pub mod tas;
+#[cfg(feature = "std")]
+pub mod theme;
+#[cfg(feature = "std")]
+pub mod title;
+#[cfg(feature = "std")]
+pub mod toast;
+#[cfg(feature = "std")]
+pub mod tournament;
+pub mod trainer;
+#[cfg(feature = "std")]
+pub mod ui;
+#[cfg(feature = "std")]
+pub mod wall;
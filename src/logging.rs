@@ -0,0 +1,32 @@
+use super::profile;
+use std::fs::File;
+use std::sync::Mutex;
+use tracing::Level;
+
+/// Install a subscriber writing to the file given by `--log-file=<path>`,
+/// at the level given by `--log-level=<level>` (default `info`). Logging
+/// is disabled when no log file is given, since stdout belongs to the
+/// game.
+pub fn init() -> Result<(), String> {
+    let arg =
+        |prefix: &str| profile::args().find_map(|arg| arg.strip_prefix(prefix).map(String::from));
+
+    let path = match arg("--log-file=") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let level: Level = match arg("--log-level=") {
+        Some(level) => level
+            .parse()
+            .map_err(|_| format!("unknown log level {:?}", level))?,
+        None => Level::INFO,
+    };
+    let file = File::create(&path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_max_level(level)
+        .with_ansi(false)
+        .init();
+    Ok(())
+}
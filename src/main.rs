@@ -1,98 +1,2216 @@
-mod game_state;
-mod ui;
+#[cfg(feature = "discord")]
+use tetris_rs::discord;
+#[cfg(feature = "gui")]
+use tetris_rs::gui;
+use tetris_rs::{
+    achievements, ai, analysis, announce, chat, config, console, crash, editor, eval, export,
+    game_state, garbage, ghost, graphics, input, latency, logging, objective, profile, puzzle,
+    replay, rewind, savestate, schedule, sprint, tas, theme, title, toast, tournament, trainer, ui,
+    wall,
+};
 
 extern crate tui;
 
-use game_state::{Event, Tetris};
+use config::GameConfig;
+use game_state::{Action, GameEvent, GameView, Tetris, NCOLS, NROWS};
+use input::{KeyCode, KeyState, KeyTransition, Keymap};
+use schedule::TickSchedule;
 use ui::*;
 
 use std::io;
-use std::sync::mpsc;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, RwLock};
 use std::thread;
-use termion::event::Key;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use termion::event::Event as TermEvent;
 use termion::input::TermRead;
-use termion::raw::IntoRawMode;
-use tui::backend::TermionBackend;
+use termion::raw::{IntoRawMode, RawTerminal};
+use tracing::{debug, info, trace, warn};
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Alignment, Rect};
+use tui::text::Spans;
+use tui::widgets::{Paragraph, Wrap};
 use tui::Terminal;
 
 pub enum Iteration {
     /// A key press event to be handled
-    Event(Event),
+    Event(Action),
+    /// A key with a macro went down, sending these events in order, all
+    /// before the next tick
+    Macro(Vec<Action>),
+    /// A key bound to an event went down, on terminals that report
+    /// key releases
+    Press(Action),
+    /// A key bound to an event went up
+    Release(Action),
     /// A clock tick
     Tick,
+    /// The player asked to move on, e.g. past a game over screen
+    Continue,
+    /// The player asked to watch the replay of a finished game
+    Replay,
+    /// The player asked to quit
+    Quit,
+    /// The player asked to suspend the game to the shell (Ctrl-Z)
+    Suspend,
+    /// Show or hide the developer overlay
+    ToggleDebug,
+    /// Switch between timed gravity and frame-step mode
+    ToggleStepping,
+    /// Advance gravity by one tick in frame-step mode
+    Step,
+    /// Step a practice game back one tick; sent again while held
+    Rewind,
+    /// Put a practice game aside in a savestate slot
+    SaveState(usize),
+    /// Bring back the practice game in a savestate slot
+    LoadState(usize),
+    /// Open the developer console
+    Console,
+    /// A key typed into the open console
+    Type(KeyCode),
+    /// Run a practice game slower
+    Slower,
+    /// Run a practice game faster
+    Faster,
+    /// The profile file changed
+    Reload,
+    /// The terminal gained (`true`) or lost focus
+    Focus(bool),
+    /// Any other key went down
+    Key,
 }
 
+/// Keys that control the application rather than the game.
+fn command(key: KeyCode) -> Option<Iteration> {
+    match key {
+        KeyCode::Esc | KeyCode::Ctrl('c') => Some(Iteration::Quit),
+        // Terminals report Enter as a line feed, or as a carriage return
+        // with the kitty keyboard protocol.
+        KeyCode::Char('\n') | KeyCode::Char('\r') => Some(Iteration::Continue),
+        KeyCode::Ctrl('z') => Some(Iteration::Suspend),
+        KeyCode::F(3) => Some(Iteration::ToggleDebug),
+        KeyCode::F(4) => Some(Iteration::ToggleStepping),
+        KeyCode::Char('.') => Some(Iteration::Step),
+        KeyCode::Char(':') => Some(Iteration::Console),
+        KeyCode::F(n @ 5..=8) => Some(Iteration::SaveState(n as usize - 5)),
+        KeyCode::Char(n @ '1'..='4') => Some(Iteration::LoadState(n as usize - '1' as usize)),
+        KeyCode::Char('r') => Some(Iteration::Replay),
+        KeyCode::Backspace => Some(Iteration::Rewind),
+        KeyCode::Char('-') => Some(Iteration::Slower),
+        KeyCode::Char('=') | KeyCode::Char('+') => Some(Iteration::Faster),
+        _ => None,
+    }
+}
+
+/// Speeds offered by the replay viewer, and by practice mode for the
+/// game itself.
+const SPEEDS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// The index of normal speed in `SPEEDS`.
+const NORMAL_SPEED: usize = 2;
+
+/// How long to count down before play resumes after a suspend.
+const RESUME_COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// The widest the side panel gets left of the board, in columns.
+const PANEL_WIDTH: u16 = 40;
+
+/// How long the board flashes when a move or turn is blocked.
+const BUMP_FLASH: Duration = Duration::from_millis(100);
+
+/// Hand the terminal back to the shell and stop the process, as the
+/// terminal would have done on Ctrl-Z outside of raw mode. Returns once
+/// the shell resumes us, with the terminal set up for the game again.
+fn suspend<B: Backend + Write>(
+    raw: &RawTerminal<io::Stdout>,
+    terminal: &mut Terminal<B>,
+) -> io::Result<()> {
+    let backend = terminal.backend_mut();
+    write!(
+        backend,
+        "{}{}{}{}{}{}",
+        input::DISABLE_KEY_EVENTS,
+        input::DISABLE_FOCUS_EVENTS,
+        title::restore(),
+        termion::clear::All,
+        termion::cursor::Goto(1, 1),
+        termion::cursor::Show
+    )?;
+    Write::flush(backend)?;
+    raw.suspend_raw_mode()?;
+
+    // SAFETY: raising a signal on ourselves has no memory safety
+    // implications; SIGTSTP's default action stops the process until
+    // SIGCONT.
+    unsafe {
+        libc::raise(libc::SIGTSTP);
+    }
+
+    raw.activate_raw_mode()?;
+    let backend = terminal.backend_mut();
+    write!(
+        backend,
+        "{}{}{}{}",
+        input::ENABLE_KEY_EVENTS,
+        input::ENABLE_FOCUS_EVENTS,
+        title::save(),
+        termion::cursor::Hide
+    )?;
+    terminal.clear()
+}
+
+/// What a board frame is drawn from: the game, a ghost's game, the side
+/// panel, the word over the board, whether the board is flashing and the
+/// terminal size.
+type Frame = (
+    GameView,
+    Option<GameView>,
+    Vec<String>,
+    Option<String>,
+    bool,
+    Rect,
+);
+
 struct Driver {
-    rx: mpsc::Receiver<Iteration>,
-    input_thread: thread::JoinHandle<()>,
-    tick_thread: thread::JoinHandle<()>,
+    /// Iterations, with when they were sent
+    rx: mpsc::Receiver<(Iteration, Instant)>,
+    /// When the iteration last returned by `next` was sent
+    received: Instant,
+    /// Sets the time between clock ticks, or stops them with `None`
+    clock: mpsc::Sender<Option<Duration>>,
+    /// Time between ticks at normal speed
+    gravity: Duration,
+    /// Multiplier on how often the clock ticks
+    speed: f64,
+    ticking: bool,
+    /// While set, every key goes to the developer console
+    typing: Arc<AtomicBool>,
+    /// Shared with the input thread, to take effect on the next key
+    keymap: Arc<RwLock<Keymap>>,
+    /// Sends `Iteration::Reload` when the profile changes
+    _profile_watcher: Option<notify::RecommendedWatcher>,
+    _input_thread: thread::JoinHandle<()>,
+    _tick_thread: thread::JoinHandle<()>,
+    _chat_thread: Option<thread::JoinHandle<()>>,
 }
 
 impl Driver {
-    pub fn new() -> Self {
+    /// Keys are read from `input`, the terminal but for tests. With
+    /// `console`, `:` opens the developer console, and keys go to it
+    /// until typing is switched off.
+    pub fn new(
+        input: impl io::Read + Send + 'static,
+        keymap: Keymap,
+        gravity: Duration,
+        chat: Option<chat::Chat>,
+        console: bool,
+    ) -> Self {
         let (tx, rx) = mpsc::channel();
+        let typing = Arc::new(AtomicBool::new(false));
+        let keymap = Arc::new(RwLock::new(keymap));
+        // A game goes on without live reloading if the profile can't be
+        // watched.
+        let profile_watcher = {
+            let tx = tx.clone();
+            profile::watch(move || {
+                let _ = tx.send((Iteration::Reload, Instant::now()));
+            })
+            .unwrap_or_else(|e| {
+                warn!(%e, "not watching the profile");
+                None
+            })
+        };
+        // Moves voted for in chat arrive as if typed on the keyboard.
+        let chat_thread = chat.map(|chat| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                chat.run(|event| tx.send((Iteration::Event(event), Instant::now())).is_ok())
+            })
+        });
         let input_thread = {
             let tx = tx.clone();
+            let typing = Arc::clone(&typing);
+            let keymap = Arc::clone(&keymap);
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    match evt {
-                        Ok(Key::Char('a')) => tx.send(Iteration::Event(Event::Left)).unwrap(),
-                        Ok(Key::Char('d')) => tx.send(Iteration::Event(Event::Right)).unwrap(),
-                        Ok(Key::Char('q')) => {
-                            tx.send(Iteration::Event(Event::CounterClock)).unwrap()
+                for evt in input.events() {
+                    let keymap = keymap.read().unwrap();
+                    // A key with a macro sends all of its events at once.
+                    let pressed = match &evt {
+                        Ok(TermEvent::Key(key)) => KeyCode::from_termion(*key),
+                        Ok(TermEvent::Unsupported(bytes)) => match input::parse_kitty(bytes) {
+                            Some(KeyTransition::Press(key)) => Some(key),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    let sequence = pressed
+                        .filter(|key| !typing.load(Ordering::Relaxed) && command(*key).is_none())
+                        .and_then(|key| keymap.sequence(key));
+                    if let Some(actions) = sequence {
+                        if tx
+                            .send((Iteration::Macro(actions.to_vec()), Instant::now()))
+                            .is_err()
+                        {
+                            break;
+                        }
+                        continue;
+                    }
+                    let focus = match &evt {
+                        Ok(TermEvent::Unsupported(bytes)) => input::parse_focus(bytes),
+                        _ => None,
+                    };
+                    let iteration = if let Some(focused) = focus {
+                        Some(Iteration::Focus(focused))
+                    } else if typing.load(Ordering::Relaxed) {
+                        match evt {
+                            Ok(TermEvent::Key(key)) => {
+                                KeyCode::from_termion(key).map(Iteration::Type)
+                            }
+                            Ok(TermEvent::Unsupported(bytes)) => match input::parse_kitty(&bytes) {
+                                Some(KeyTransition::Press(key))
+                                | Some(KeyTransition::Repeat(key)) => Some(Iteration::Type(key)),
+                                _ => None,
+                            },
+                            _ => None,
+                        }
+                    } else {
+                        match evt {
+                            Ok(TermEvent::Key(key)) => match KeyCode::from_termion(key) {
+                                Some(key) if command(key).is_some() => command(key),
+                                Some(key) => Some(
+                                    keymap.action(key).map_or(Iteration::Key, Iteration::Event),
+                                ),
+                                None => Some(Iteration::Key),
+                            },
+                            Ok(TermEvent::Unsupported(bytes)) => match input::parse_kitty(&bytes) {
+                                Some(KeyTransition::Press(key)) if command(key).is_some() => {
+                                    command(key)
+                                }
+                                Some(KeyTransition::Press(key)) => Some(
+                                    keymap.action(key).map_or(Iteration::Key, Iteration::Press),
+                                ),
+                                Some(KeyTransition::Release(key)) => {
+                                    keymap.action(key).map(Iteration::Release)
+                                }
+                                // Holding rewind keeps rewinding.
+                                Some(KeyTransition::Repeat(key))
+                                    if matches!(command(key), Some(Iteration::Rewind)) =>
+                                {
+                                    command(key)
+                                }
+                                _ => None,
+                            },
+                            _ => None,
+                        }
+                    };
+                    // Switched here rather than by the game, so that no
+                    // key typed right after the `:` is taken as a move.
+                    if console && matches!(iteration, Some(Iteration::Console)) {
+                        typing.store(true, Ordering::Relaxed);
+                    }
+                    if let Some(iteration) = iteration {
+                        if tx.send((iteration, Instant::now())).is_err() {
+                            break;
                         }
-                        Ok(Key::Char('e')) => tx.send(Iteration::Event(Event::Clock)).unwrap(),
-                        Ok(Key::Esc) => std::process::exit(0),
-                        _ => (),
                     }
                 }
             })
         };
+        let (clock_tx, clock_rx) = mpsc::channel::<Option<Duration>>();
         let tick_thread = {
-            thread::spawn(move || loop {
-                tx.send(Iteration::Tick).unwrap();
-                thread::sleep(std::time::Duration::from_millis(1000 / 6));
+            thread::spawn(move || {
+                let mut schedule = Some(TickSchedule::new(gravity, Instant::now()));
+                loop {
+                    // Sleep until the next tick, or for good while
+                    // stopped, waking early when switched.
+                    let switched = match &schedule {
+                        Some(schedule) => clock_rx.recv_timeout(
+                            schedule
+                                .deadline()
+                                .saturating_duration_since(Instant::now()),
+                        ),
+                        None => clock_rx
+                            .recv()
+                            .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                    };
+                    match switched {
+                        Ok(switched) => {
+                            schedule =
+                                switched.map(|interval| TickSchedule::new(interval, Instant::now()))
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            let now = Instant::now();
+                            let due = schedule.as_mut().map_or(0, |s| s.due(now));
+                            if (0..due).any(|_| tx.send((Iteration::Tick, now)).is_err()) {
+                                break;
+                            }
+                        }
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+                }
             })
         };
 
         Self {
             rx,
-            input_thread,
-            tick_thread,
+            received: Instant::now(),
+            clock: clock_tx,
+            gravity,
+            speed: 1.0,
+            ticking: true,
+            typing,
+            keymap,
+            _profile_watcher: profile_watcher,
+            _input_thread: input_thread,
+            _tick_thread: tick_thread,
+            _chat_thread: chat_thread,
+        }
+    }
+
+    /// Start or stop the clock ticks, so that nothing wakes up while
+    /// there is nothing to animate.
+    fn set_ticking(&mut self, ticking: bool) {
+        if ticking != self.ticking {
+            self.ticking = ticking;
+            self.reset_clock();
+        }
+    }
+
+    /// Tick every `gravity` at normal speed, following the game as it
+    /// speeds up.
+    fn set_gravity(&mut self, gravity: Duration) {
+        if gravity != self.gravity {
+            self.gravity = gravity;
+            if self.ticking {
+                self.reset_clock();
+            }
+        }
+    }
+
+    /// Tick `speed` times as often as gravity says, for slow motion.
+    fn set_speed(&mut self, speed: f64) {
+        if speed != self.speed {
+            self.speed = speed;
+            if self.ticking {
+                self.reset_clock();
+            }
         }
     }
 
-    fn next(&self) -> Iteration {
-        self.rx.recv().unwrap()
+    /// While typing, every key arrives as `Iteration::Type`, for the
+    /// console and the board editor, rather than as a move or command.
+    fn set_typing(&self, typing: bool) {
+        self.typing.store(typing, Ordering::Relaxed);
+    }
+
+    fn set_keymap(&self, keymap: Keymap) {
+        *self.keymap.write().unwrap() = keymap;
+    }
+
+    fn reset_clock(&self) {
+        let interval = Some(self.gravity.div_f64(self.speed)).filter(|_| self.ticking);
+        // The tick thread only goes away with the driver.
+        self.clock.send(interval).unwrap();
+    }
+
+    /// Wait for the next iteration, or until the deadline passes, in
+    /// which case `None` is returned.
+    fn next(&mut self, deadline: Option<Instant>) -> Option<Iteration> {
+        let (iteration, received) = match deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.rx.recv_timeout(timeout) {
+                    Ok(next) => next,
+                    Err(mpsc::RecvTimeoutError::Timeout) => return None,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+            None => self.rx.recv().unwrap(),
+        };
+        self.received = received;
+        Some(iteration)
+    }
+}
+
+/// The terminal and the settings shared by every game in a session.
+struct Session<B: Backend + Write = TermionBackend<io::Stdout>> {
+    /// In raw mode while the session lasts; `None` when drawing to
+    /// anything but the real terminal.
+    raw: Option<RawTerminal<io::Stdout>>,
+    terminal: Terminal<B>,
+    driver: Driver,
+    protocol: Option<graphics::Protocol>,
+    theme: theme::Theme,
+    /// DAS and ARR for held movement keys
+    handling: input::Handling,
+    half_blocks: bool,
+    /// With `--layout=left`, the side panel goes left of the board.
+    panel_left: bool,
+    /// With `--mirror`, boards are drawn right to left, and the keymap
+    /// mirrored to match.
+    mirror: bool,
+    /// Keep output small for slow connections, such as SSH over a
+    /// high-latency link
+    low_bandwidth: bool,
+    bell: bool,
+    show_debug: bool,
+    /// Show how many of each piece have been dealt.
+    show_stats: bool,
+    /// In frame-step mode gravity only advances on an explicit step.
+    stepping: bool,
+    /// Announce the game as text, and also out loud when `Some(true)`.
+    announce: Option<bool>,
+    /// In practice mode, the recent past of the game being played, to
+    /// rewind into.
+    rewind: Option<rewind::Rewind>,
+    /// In practice mode, games put aside to come back to.
+    savestates: Option<savestate::Savestates>,
+    /// In a garbage climb, the pieces used count rather than the time.
+    climb: bool,
+    /// In the rising floor challenge, when the next garbage row comes.
+    floor: Option<garbage::RisingFloor>,
+    /// With `--trainer`, the speed trainer's levels.
+    trainer: Option<trainer::SpeedTrainer>,
+    /// In TAS mode, the movie file keeping the game's inputs frame by
+    /// frame.
+    tas: Option<String>,
+    /// With `--ghost=<file>`, a past run to race, played beside the
+    /// game.
+    ghost: Option<tas::Movie>,
+    /// With `--save-ghost=<file>`, where to keep each game's run to race
+    /// later.
+    save_ghost: Option<String>,
+    /// With `--idle-pause=<seconds>`, how long a game goes without input
+    /// before pausing itself.
+    idle: Option<Duration>,
+    /// With `--focus-pause`, pause a game when the terminal loses focus.
+    focus_pause: bool,
+    /// With `--bump-flash`, flash the board when a move or turn is
+    /// blocked.
+    bump_flash: bool,
+    /// With `--reduced-motion`, toasts stay put instead of blinking away,
+    /// and nothing flashes.
+    reduced_motion: bool,
+    /// With `--dev`, the console for debug commands, opened with `:`.
+    console: Option<console::Console>,
+    /// Where to save a summary of each game once it is over.
+    export: Option<String>,
+    /// The options a friend needs to play the same game on its seed.
+    race: Vec<String>,
+    #[cfg(feature = "discord")]
+    presence: Option<discord::Presence>,
+    shown_title: String,
+    /// The last frame drawn and the terminal size it was drawn at, to
+    /// skip drawing it again.
+    drawn: Option<Frame>,
+}
+
+impl<B: Backend + Write> Session<B> {
+    /// In TAS mode, write the inputs of `replay` to the movie file.
+    fn save_movie(&self, replay: &replay::Replay) -> Result<(), String> {
+        match self.tas.as_deref() {
+            Some(path) => {
+                info!(path, "saving movie");
+                tas::Movie::from_steps(replay.steps()).save(path)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn set_title(&mut self, window_title: String) -> io::Result<()> {
+        if window_title != self.shown_title {
+            write!(self.terminal.backend_mut(), "{}", title::set(&window_title))?;
+            self.shown_title = window_title;
+        }
+        Ok(())
+    }
+
+    /// A game's board as it is shown, mirrored with `--mirror`. The
+    /// board editor always shows boards as they are saved.
+    fn oriented(&self, view: GameView) -> GameView {
+        if self.mirror {
+            view.mirrored()
+        } else {
+            view
+        }
+    }
+
+    /// Suspend to the shell, and redraw everything once back.
+    fn suspend(&mut self) -> io::Result<()> {
+        if let Some(raw) = self.raw.as_ref() {
+            suspend(raw, &mut self.terminal)?;
+        }
+        self.shown_title.clear();
+        self.drawn = None;
+        Ok(())
+    }
+
+    /// Show `lines` on an otherwise empty screen until the player
+    /// presses Enter, returning false if they asked to quit instead.
+    fn interstitial(&mut self, lines: &[String]) -> io::Result<bool> {
+        self.driver.set_ticking(false);
+        self.drawn = None;
+        loop {
+            let text: Vec<Spans> = lines.iter().cloned().map(Spans::from).collect();
+            self.terminal.draw(|f| {
+                f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), f.size())
+            })?;
+            match self.driver.next(None) {
+                Some(Iteration::Continue) => return Ok(true),
+                Some(Iteration::Quit) => return Ok(false),
+                Some(Iteration::Suspend) => self.suspend()?,
+                _ => (),
+            }
+        }
+    }
+
+    /// Play `tetris` until the game is over and the player presses
+    /// Enter, or until they quit, in which case `None` is returned. A
+    /// `sprint` is over once `SPRINT_LINES` are cleared, and its split
+    /// times are compared against the `personal_best`, if given.
+    fn play(
+        &mut self,
+        tetris: &mut Tetris,
+        mut bot: Option<&mut ai::Bot>,
+        mut achievements: Option<&mut achievements::Achievements>,
+        sprint: bool,
+        mut personal_best: Option<&mut sprint::PersonalBest>,
+        over_hint: &str,
+    ) -> io::Result<Option<tournament::Run>> {
+        let mut announcer = self.announce.map(announce::Announcer::new);
+        let mut toasts = if self.reduced_motion {
+            toast::Toasts::steady()
+        } else {
+            toast::Toasts::default()
+        };
+        if let Some(achievements) = achievements.as_mut() {
+            for unlocked in achievements.start_game() {
+                toasts.push(format!("Achievement: {}", unlocked.name()), Instant::now());
+            }
+            achievements.save().map_err(io::Error::other)?;
+        }
+        let mut started = Instant::now();
+        let mut splits = sprint::Splits::default();
+        // A game that starts on garbage is a cheese race.
+        let cheese = tetris.garbage_rows() > 0;
+        if sprint {
+            tetris.set_objective(objective::TargetLines(sprint::SPRINT_LINES));
+        }
+        let mut finished: Option<Duration> = None;
+        // The timer reading once the game is over.
+        let mut stopped: Option<Duration> = None;
+        // How the game went, once it is over.
+        let mut breakdown: Option<analysis::Analysis> = None;
+        let mut keys = KeyState::new(self.handling);
+        let mut latency = latency::Latency::default();
+        let mut kpp = input::KeysPerPiece::default();
+        // When set, the game is frozen until this instant.
+        let mut resume_at: Option<Instant> = None;
+        // What has been typed into the developer console, while open.
+        let mut typed: Option<String> = None;
+        // The game pauses itself after this long without a key, unless
+        // the bot or frame stepping is playing it.
+        let idle = self.idle.filter(|_| bot.is_none() && !self.stepping);
+        let mut last_input = Instant::now();
+        // When and why the game paused itself, while the player is away.
+        let mut away: Option<(Instant, &str)> = None;
+        // When the player paused the game with its pause key, while it is
+        // paused.
+        let mut held: Option<Instant> = None;
+        // Only practice games change speed.
+        let mut speed = NORMAL_SPEED;
+        self.driver.set_speed(SPEEDS[speed]);
+        let mut was_in_danger = false;
+        // Until when the board flashes for a blocked move.
+        let mut flash_until: Option<Instant> = None;
+        tetris.record_inputs(true);
+        let mut replay = replay::Replay::new(tetris);
+        crash::start(tetris);
+        let mut ghost = self
+            .ghost
+            .clone()
+            .map(|movie| ghost::Ghost::new(tetris.clone(), movie));
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+        if let Some(floor) = self.floor.as_mut() {
+            floor.restart();
+        }
+        if let Some(trainer) = self.trainer.as_mut() {
+            trainer.restart();
+            tetris.set_gravity(trainer.gravity());
+        }
+        // A TAS picks up where its movie left off.
+        if let Some(path) = self.tas.as_deref().filter(|path| Path::new(path).exists()) {
+            let movie = tas::Movie::load(path).map_err(io::Error::other)?;
+            info!(path, frames = movie.frames.len(), "playing movie");
+            movie.play(tetris);
+            let inputs = tetris.take_inputs();
+            crash::record(&inputs);
+            replay.extend(inputs, Duration::ZERO);
+        }
+
+        info!("game started");
+        let mut last_tick = Instant::now();
+
+        loop {
+            self.driver.set_gravity(tetris.gravity());
+            // Once the game is over, only keys and expiring toasts change
+            // the screen.
+            self.driver.set_ticking(
+                tetris.top_out().is_none()
+                    && finished.is_none()
+                    && away.is_none()
+                    && held.is_none(),
+            );
+            let deadline = keys
+                .deadline()
+                .into_iter()
+                .chain(toasts.deadline())
+                .chain(flash_until)
+                .min();
+            let mut iteration = self.driver.next(deadline);
+            if resume_at.is_some_and(|at| at <= Instant::now()) {
+                resume_at = None;
+                last_input = Instant::now();
+            }
+            // Coming back to the terminal counts as input.
+            if !matches!(
+                iteration,
+                None | Some(Iteration::Tick)
+                    | Some(Iteration::Reload)
+                    | Some(Iteration::Focus(false))
+            ) {
+                last_input = Instant::now();
+                if let Some((at, _)) = away.take() {
+                    // The time away doesn't count, and the key that
+                    // brought the player back only starts the countdown.
+                    info!(away = ?at.elapsed(), "player is back");
+                    started += at.elapsed();
+                    resume_at = Some(Instant::now() + RESUME_COUNTDOWN);
+                    keys = KeyState::new(self.handling);
+                    if !matches!(iteration, Some(Iteration::Quit) | Some(Iteration::Suspend)) {
+                        iteration = None;
+                    }
+                }
+            }
+            let over = tetris.top_out().is_some() || finished.is_some();
+            let reason = if self.focus_pause && matches!(iteration, Some(Iteration::Focus(false))) {
+                Some("Paused while the terminal is in the background.")
+            } else if idle.is_some_and(|idle| last_input.elapsed() >= idle) && resume_at.is_none() {
+                Some("Paused due to inactivity.")
+            } else {
+                None
+            };
+            if let Some(reason) =
+                reason.filter(|_| away.is_none() && held.is_none() && typed.is_none() && !over)
+            {
+                info!(reason, "player is away");
+                away = Some((Instant::now(), reason));
+                resume_at = None;
+            }
+            // Nothing moves while counting down after a suspend, while
+            // away, while the console is open, or once the game is over.
+            let paused = resume_at.is_some() || away.is_some() || typed.is_some() || over;
+
+            match iteration {
+                Some(Iteration::Tick) => {
+                    trace!(since_last = ?last_tick.elapsed(), "tick");
+                    last_tick = Instant::now();
+                    if !paused && !self.stepping {
+                        self.advance(
+                            tetris,
+                            &replay,
+                            bot.as_deref_mut(),
+                            ghost.as_mut(),
+                            &mut toasts,
+                        );
+                    }
+                }
+                Some(Iteration::Step) => {
+                    debug!(stepping = self.stepping, "step");
+                    if !paused && self.stepping {
+                        self.advance(
+                            tetris,
+                            &replay,
+                            bot.as_deref_mut(),
+                            ghost.as_mut(),
+                            &mut toasts,
+                        );
+                    }
+                }
+                Some(Iteration::Event(evt)) => {
+                    debug!(?evt, paused, "key event");
+                    if !paused && keys.allows(evt, Instant::now()) {
+                        latency.received(self.driver.received);
+                        kpp.press(evt);
+                        tetris.event(evt);
+                    }
+                }
+                Some(Iteration::Macro(actions)) => {
+                    debug!(?actions, paused, "macro");
+                    // One key, however many events it sends.
+                    if let Some(first) = actions.first().filter(|_| !paused) {
+                        latency.received(self.driver.received);
+                        kpp.press(*first);
+                        for evt in actions {
+                            if keys.allows(evt, Instant::now()) {
+                                tetris.event(evt);
+                            }
+                        }
+                    }
+                }
+                Some(Iteration::Press(evt)) => {
+                    debug!(?evt, paused, "key press");
+                    let now = Instant::now();
+                    if keys.press(evt, now) && !paused && keys.allows(evt, now) {
+                        latency.received(self.driver.received);
+                        kpp.press(evt);
+                        tetris.event(evt);
+                    }
+                }
+                Some(Iteration::Release(evt)) => {
+                    debug!(?evt, "key release");
+                    keys.release(evt);
+                }
+                Some(Iteration::Continue) => {
+                    if over {
+                        return Ok(Some(tournament::Run {
+                            lines: tetris.lines(),
+                            finished,
+                        }));
+                    }
+                }
+                Some(Iteration::Replay) => {
+                    if over {
+                        self.watch(&replay)?;
+                    }
+                }
+                Some(Iteration::Quit) => {
+                    info!("quit requested");
+                    if stopped.is_none() {
+                        latency.log();
+                    }
+                    self.save_movie(&replay).map_err(io::Error::other)?;
+                    return Ok(None);
+                }
+                Some(Iteration::ToggleDebug) => self.show_debug = !self.show_debug,
+                Some(Iteration::ToggleStepping) => self.stepping = !self.stepping,
+                // A top out can be taken back too, but not a finished
+                // sprint.
+                Some(Iteration::Rewind) if resume_at.is_none() && finished.is_none() => {
+                    if let Some((snapshot, replay_len)) =
+                        self.rewind.as_mut().and_then(rewind::Rewind::back)
+                    {
+                        debug!(replay_len, "rewind");
+                        *tetris = snapshot;
+                        replay.truncate(replay_len);
+                        crash::truncate(replay_len);
+                        stopped = None;
+                        breakdown = None;
+                    }
+                }
+                Some(Iteration::SaveState(slot)) if !over => {
+                    if let Some(savestates) = self.savestates.as_mut() {
+                        let state = savestate::Savestate {
+                            tetris: tetris.clone(),
+                            floor: self.floor.clone(),
+                        };
+                        savestates.save(slot, state);
+                        info!(slot, "state saved");
+                        toasts.push(format!("Saved to slot {}", slot + 1), Instant::now());
+                    }
+                }
+                // Like a rewind, loading can undo a top out but not a
+                // finished sprint.
+                Some(Iteration::LoadState(slot)) if resume_at.is_none() && finished.is_none() => {
+                    if let Some(state) = self.savestates.as_ref().and_then(|s| s.load(slot)) {
+                        info!(slot, "state loaded");
+                        *tetris = state.tetris;
+                        self.floor = state.floor;
+                        replay = replay::Replay::new(tetris);
+                        crash::start(tetris);
+                        if let Some(rewind) = self.rewind.as_mut() {
+                            rewind.clear();
+                        }
+                        stopped = None;
+                        breakdown = None;
+                        toasts.push(format!("Loaded slot {}", slot + 1), Instant::now());
+                    }
+                }
+                Some(Iteration::Slower) if self.rewind.is_some() => {
+                    speed = speed.saturating_sub(1);
+                    self.driver.set_speed(SPEEDS[speed]);
+                }
+                Some(Iteration::Faster) if self.rewind.is_some() => {
+                    speed = (speed + 1).min(SPEEDS.len() - 1);
+                    self.driver.set_speed(SPEEDS[speed]);
+                }
+                Some(Iteration::Rewind)
+                | Some(Iteration::SaveState(_))
+                | Some(Iteration::LoadState(_))
+                | Some(Iteration::Slower)
+                | Some(Iteration::Faster)
+                | Some(Iteration::Key)
+                | Some(Iteration::Focus(_)) => (),
+                Some(Iteration::Console) if self.console.is_some() && !over => {
+                    typed = Some(String::new());
+                }
+                Some(Iteration::Console) => self.driver.set_typing(false),
+                Some(Iteration::Type(KeyCode::Char('\n')))
+                | Some(Iteration::Type(KeyCode::Char('\r'))) => {
+                    self.driver.set_typing(false);
+                    let line = typed.take().unwrap_or_default();
+                    if let Some(console) = self.console.as_mut() {
+                        let message = console.run(tetris, &line).unwrap_or_else(|e| e);
+                        info!(line = line.as_str(), message = message.as_str(), "console");
+                        if !message.is_empty() {
+                            toasts.push(message, Instant::now());
+                        }
+                    }
+                }
+                Some(Iteration::Type(KeyCode::Esc)) => {
+                    self.driver.set_typing(false);
+                    typed = None;
+                }
+                Some(Iteration::Type(KeyCode::Backspace)) => {
+                    if let Some(typed) = typed.as_mut() {
+                        typed.pop();
+                    }
+                }
+                Some(Iteration::Type(KeyCode::Char(c))) => {
+                    if let Some(typed) = typed.as_mut() {
+                        typed.push(c);
+                    }
+                }
+                Some(Iteration::Type(_)) => (),
+                Some(Iteration::Suspend) => {
+                    info!("suspending");
+                    self.suspend()?;
+                    keys = KeyState::new(self.handling);
+                    resume_at = Some(Instant::now() + RESUME_COUNTDOWN);
+                }
+                Some(Iteration::Reload) => {
+                    let reloaded = profile::reload(|| {
+                        Ok((
+                            Keymap::from_args()?,
+                            input::Handling::from_args()?,
+                            theme::Theme::detect()?,
+                        ))
+                    });
+                    let toast = match reloaded {
+                        Ok((keymap, handling, theme)) => {
+                            info!("profile reloaded");
+                            self.driver.set_keymap(keymap);
+                            self.handling = handling;
+                            keys = KeyState::new(handling);
+                            self.theme = theme;
+                            self.drawn = None;
+                            String::from("Settings reloaded")
+                        }
+                        Err(e) => {
+                            warn!(%e, "profile not reloaded");
+                            format!("Settings not reloaded: {}", e)
+                        }
+                    };
+                    toasts.push(toast, Instant::now());
+                }
+                None => {
+                    for evt in keys.due(Instant::now()) {
+                        trace!(?evt, "auto-repeat");
+                        if !paused {
+                            tetris.event(evt);
+                            // Keep moving until the piece stops.
+                            let mut last = None;
+                            while keys.shifts_to_wall() && last != Some(tetris.anchor()) {
+                                last = Some(tetris.anchor());
+                                tetris.event(evt);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Like time away, time paused doesn't count.
+            match (tetris.is_paused(), held) {
+                (true, None) => held = Some(Instant::now()),
+                (false, Some(at)) => {
+                    started += at.elapsed();
+                    held = None;
+                }
+                _ => (),
+            }
+
+            let inputs = tetris.take_inputs();
+            crash::record(&inputs);
+            replay.extend(inputs, started.elapsed());
+            kpp.placed(tetris.pieces());
+            keys.placed(tetris.pieces(), Instant::now());
+            if sprint && finished.is_none() {
+                splits.record(tetris.lines(), started.elapsed());
+                finished = splits.finished();
+                let new_best = match personal_best.as_mut() {
+                    Some(best) if finished.is_some() => best.submit(&splits),
+                    _ => false,
+                };
+                if new_best {
+                    toasts.push(String::from("NEW PERSONAL BEST"), Instant::now());
+                    personal_best
+                        .as_ref()
+                        .map_or(Ok(()), |best| best.save())
+                        .map_err(io::Error::other)?;
+                }
+            }
+            // Games with other objectives end once the engine says so.
+            if finished.is_none() && tetris.completed() {
+                finished = Some(started.elapsed());
+            }
+            if stopped.is_none() && (finished.is_some() || tetris.top_out().is_some()) {
+                let time = finished.unwrap_or_else(|| started.elapsed());
+                stopped = Some(time);
+                let analysis = analysis::Analysis::of(&replay);
+                latency.log();
+                if let Err(e) = self.save_movie(&replay) {
+                    toasts.push(e, Instant::now());
+                }
+                if let Some(path) = self.save_ghost.as_deref() {
+                    info!(path, "saving ghost");
+                    let toast = match tas::Movie::from_steps(replay.steps()).save(path) {
+                        Ok(()) => format!("Saved ghost to {}", path),
+                        Err(e) => e,
+                    };
+                    toasts.push(toast, Instant::now());
+                }
+                if let Some(path) = self.export.as_deref() {
+                    let result = match tetris.top_out() {
+                        Some(reason) => format!("Game over, {}.", reason),
+                        None if self.climb => {
+                            format!("Dug out in {} pieces!", tetris.pieces())
+                        }
+                        None if cheese => String::from("Cheese race finished!"),
+                        None if sprint => String::from("Sprint finished!"),
+                        None => String::from("Puzzle solved!"),
+                    };
+                    let mut stats = vec![
+                        String::from("tetris-rs"),
+                        String::new(),
+                        result,
+                        format!("Lines:  {}", tetris.lines()),
+                        format!("Pieces: {}", tetris.pieces()),
+                        format!("Time:   {}", sprint::format_time(time)),
+                    ];
+                    stats.extend(tetris.config().seed.map(|seed| format!("Seed:   {}", seed)));
+                    stats.extend(
+                        latency
+                            .summary()
+                            .map(|summary| format!("Input:  {}", summary)),
+                    );
+                    stats.extend(kpp.value().map(|kpp| format!("KPP:    {:.2}", kpp)));
+                    stats.extend(
+                        self.trainer
+                            .as_ref()
+                            .and_then(trainer::SpeedTrainer::survived)
+                            .map(|gravity| format!("Speed:  {} ms", gravity.as_millis())),
+                    );
+                    stats.push(String::new());
+                    stats.extend(analysis.report());
+                    let toast = match export::save(path, &tetris.view(), &self.theme, &stats) {
+                        Ok(()) => format!("Saved {}", path),
+                        Err(e) => e,
+                    };
+                    toasts.push(toast, Instant::now());
+                }
+                breakdown = Some(analysis);
+            }
+            if let Some(announcer) = announcer.as_mut() {
+                announcer.observe(tetris);
+            }
+            for event in tetris.take_events() {
+                crash::observe(event);
+                if self.bump_flash && matches!(event, GameEvent::Blocked(_)) {
+                    flash_until = Some(Instant::now() + BUMP_FLASH);
+                }
+                toasts.observe(event, Instant::now());
+                if let Some(achievements) = achievements.as_mut() {
+                    let unlocked = achievements.observe(event, tetris.lines(), started.elapsed());
+                    for achievement in unlocked.iter() {
+                        toasts.push(
+                            format!("Achievement: {}", achievement.name()),
+                            Instant::now(),
+                        );
+                    }
+                    if !unlocked.is_empty() {
+                        achievements.save().map_err(io::Error::other)?;
+                    }
+                }
+            }
+
+            self.set_title(format!("tetris-rs | {} lines", tetris.lines()))?;
+
+            #[cfg(feature = "discord")]
+            if let Some(presence) = self.presence.as_mut() {
+                let mode = if sprint {
+                    "40-line sprint"
+                } else if self.climb {
+                    "Garbage climb"
+                } else if cheese {
+                    "Cheese race"
+                } else if self.floor.is_some() {
+                    "Rising floor"
+                } else if self.trainer.is_some() {
+                    "Speed trainer"
+                } else if tetris.config().zen {
+                    "Zen mode"
+                } else if bot.is_some() {
+                    "Watching the bot"
+                } else {
+                    "Endless"
+                };
+                let state = match (tetris.top_out(), finished) {
+                    (Some(_), _) => format!("Game over at {} lines", tetris.lines()),
+                    (None, Some(_)) if self.climb => {
+                        format!("Dug out in {} pieces", tetris.pieces())
+                    }
+                    (None, Some(time)) => format!("Finished in {}", sprint::format_time(time)),
+                    (None, None) => format!("{} lines", tetris.lines()),
+                };
+                presence.update(mode, &state);
+            }
+
+            let view = tetris.view();
+            if view.in_danger() && !was_in_danger && self.bell {
+                write!(self.terminal.backend_mut(), "\x07")?;
+            }
+            was_in_danger = view.in_danger();
+            let time =
+                match stopped.or_else(|| away.map(|(at, _)| at).or(held).map(|at| at - started)) {
+                    Some(time) => time,
+                    // Ticking over once a second rather than every frame
+                    // saves redrawing the side panel.
+                    None if self.low_bandwidth => Duration::from_secs(started.elapsed().as_secs()),
+                    None => started.elapsed(),
+                };
+            let best = personal_best
+                .as_deref()
+                .and_then(sprint::PersonalBest::splits);
+            let mut side_panel: Vec<String> = Vec::new();
+            if !view.queue.is_empty() {
+                let names: Vec<String> = view
+                    .queue
+                    .iter()
+                    .map(|piece| format!("{:?}", piece))
+                    .collect();
+                side_panel.push(format!("Next: {}", names.join(" ")));
+            }
+            if let Some(piece) = view.hold.filter(|_| tetris.config().hold) {
+                side_panel.push(format!("Hold: {:?}", piece));
+            }
+            side_panel.push(format!("Time: {}", sprint::format_time(time)));
+            let seed = tetris.config().seed;
+            if let Some(seed) = seed {
+                side_panel.push(format!("Seed: {}", seed));
+            }
+            if let Some(ghost) = ghost.as_ref() {
+                let lines = ghost.tetris().lines();
+                let ahead = tetris.lines() as i64 - lines as i64;
+                side_panel.push(format!("Ghost: {} lines ({:+})", lines, ahead));
+            }
+            if cheese {
+                side_panel.push(format!("Garbage: {} rows", tetris.garbage_rows()));
+            }
+            if self.climb {
+                side_panel.push(format!("Pieces: {}", tetris.pieces()));
+            }
+            if let Some(floor) = self.floor.as_ref().filter(|_| !over) {
+                side_panel.push(format!(
+                    "Floor rises in {:.1}s",
+                    floor.until_next().as_secs_f64()
+                ));
+            }
+            if let Some(trainer) = self.trainer.as_ref() {
+                side_panel.push(format!(
+                    "Speed: level {} ({} ms)",
+                    trainer.level() + 1,
+                    trainer.gravity().as_millis()
+                ));
+                if !over {
+                    side_panel.push(format!(
+                        "Faster in {:.1}s",
+                        trainer.until_next().as_secs_f64()
+                    ));
+                }
+                if let Some(survived) = trainer.survived() {
+                    side_panel.push(format!("Survived: {} ms", survived.as_millis()));
+                }
+            }
+            if let Some(rewind) = self.rewind.as_ref() {
+                side_panel.push(format!(
+                    "Rewind: {:.1}s (Backspace)",
+                    rewind.available().as_secs_f64()
+                ));
+                side_panel.push(format!("Speed: {}x (-/+)", SPEEDS[speed]));
+            }
+            if let Some(savestates) = self.savestates.as_ref() {
+                let slots: Vec<String> = (0..savestate::SLOTS)
+                    .map(|slot| {
+                        if savestates.is_filled(slot) {
+                            (slot + 1).to_string()
+                        } else {
+                            String::from("-")
+                        }
+                    })
+                    .collect();
+                side_panel.push(format!("Slots: {} (F5-F8/1-4)", slots.join(" ")));
+            }
+            if let Some(pb) = best.and_then(sprint::Splits::finished) {
+                side_panel.push(format!("Best: {}", sprint::format_time(pb)));
+            }
+            if let Some(best) = best.filter(|_| sprint && finished.is_none()) {
+                let pace = best.lines_at(time);
+                let ahead = tetris.lines() as i64 - pace as i64;
+                side_panel.push(format!("Best pace: {} lines ({:+})", pace, ahead));
+            }
+            if sprint {
+                for split in 0..sprint::SPRINT_LINES / sprint::SPLIT_LINES {
+                    let lines = (split + 1) * sprint::SPLIT_LINES;
+                    let reached = splits.times().get(split as usize).copied();
+                    let pb = best.and_then(|best| best.times().get(split as usize));
+                    side_panel.push(match (reached, pb) {
+                        (Some(at), Some(pb)) => format!(
+                            "{:>2}: {} {}",
+                            lines,
+                            sprint::format_time(at),
+                            sprint::format_delta(at, *pb)
+                        ),
+                        (Some(at), None) => format!("{:>2}: {}", lines, sprint::format_time(at)),
+                        (None, Some(pb)) => {
+                            format!("{:>2}: (best {})", lines, sprint::format_time(*pb))
+                        }
+                        (None, None) => format!("{:>2}: -", lines),
+                    });
+                }
+            }
+            if self.show_stats {
+                side_panel.push(String::new());
+                if let Some(kpp) = kpp.value() {
+                    side_panel.push(format!("KPP: {:.2}", kpp));
+                }
+                side_panel.extend(statistics_lines(tetris.piece_counts(), &self.theme));
+            }
+            side_panel.push(String::new());
+            if let Some(typed) = typed.as_ref() {
+                side_panel.push(format!(":{}_", typed));
+            }
+            if let Some((_, reason)) = away {
+                side_panel.push(String::from(reason));
+                side_panel.push(String::from("Press any key to resume."));
+                side_panel.push(String::new());
+            }
+            let visible = toasts.visible(Instant::now());
+            if !visible.is_empty() {
+                side_panel.extend(visible.into_iter().map(String::from));
+                side_panel.push(String::new());
+            }
+            if finished.is_some() && self.climb {
+                side_panel.push(String::from("FINISHED"));
+                side_panel.push(format!("Dug out in {} pieces.", tetris.pieces()));
+            } else if let Some(time) = finished {
+                side_panel.push(String::from("FINISHED"));
+                side_panel.push(format!(
+                    "{} lines in {:.2}s.",
+                    tetris.lines(),
+                    time.as_secs_f64()
+                ));
+            } else if let Some(reason) = tetris.top_out() {
+                side_panel.push(String::from("GAME OVER"));
+                side_panel.push(format!("{}.", reason));
+                side_panel.push(format!("Lines: {}", tetris.lines()));
+            }
+            if let Some(breakdown) = breakdown.as_ref() {
+                side_panel.extend(breakdown.report());
+            }
+            if over {
+                if let Some(seed) = seed {
+                    side_panel.push(String::from("Race this seed:"));
+                    side_panel.push(race_command(seed, &self.race));
+                }
+                side_panel.push(String::from("Press R to watch the replay."));
+                side_panel.push(String::from(over_hint));
+                side_panel.push(String::new());
+            }
+            if self.show_debug {
+                side_panel.extend(debug_lines(
+                    tetris,
+                    Some(tetris.gravity()).filter(|_| !self.stepping),
+                ));
+                if let Some(summary) = latency.summary() {
+                    side_panel.push(format!("latency:  {}", summary));
+                }
+                side_panel.push(String::new());
+            }
+            if let Some(announcer) = announcer.as_ref() {
+                side_panel.extend(announcer.history().iter().cloned());
+            }
+            let overlay = match resume_at {
+                Some(at) => {
+                    Some((at.saturating_duration_since(Instant::now()).as_secs() + 1).to_string())
+                }
+                None if away.is_some() || held.is_some() => Some(String::from("PAUSED")),
+                None => None,
+            };
+            let view = self.oriented(view);
+            let ghost_view = ghost
+                .as_ref()
+                .map(|ghost| self.oriented(ghost.tetris().view()));
+            flash_until = flash_until.filter(|until| *until > Instant::now());
+            self.draw(
+                &view,
+                ghost_view.as_ref(),
+                side_panel,
+                overlay,
+                flash_until.is_some(),
+            )?;
+            latency.rendered(Instant::now());
+        }
+    }
+
+    /// Move a game on by one gravity tick, along with everything that
+    /// keeps time with it. A paused game, and everything with it, stays
+    /// put.
+    fn advance(
+        &mut self,
+        tetris: &mut Tetris,
+        replay: &replay::Replay,
+        bot: Option<&mut ai::Bot>,
+        ghost: Option<&mut ghost::Ghost>,
+        toasts: &mut toast::Toasts,
+    ) {
+        if tetris.is_paused() {
+            return;
+        }
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.record(tetris, replay.len());
+        }
+        if let Some(bot) = bot {
+            bot.act(tetris);
+        }
+        tetris.tick();
+        if let Some(floor) = self.floor.as_mut() {
+            floor.tick(tetris);
+        }
+        if let Some(trainer) = self.trainer.as_mut() {
+            if trainer.tick() {
+                tetris.set_gravity(trainer.gravity());
+                toasts.push(format!("Level {}", trainer.level() + 1), Instant::now());
+            }
+        }
+        if let Some(ghost) = ghost {
+            ghost.tick();
+        }
+    }
+
+    /// Play back a finished game, with controls to pause, step and
+    /// change speed, until the player leaves with Esc.
+    fn watch(&mut self, replay: &replay::Replay) -> io::Result<()> {
+        // Playback keeps its own time.
+        self.driver.set_ticking(false);
+        let mut speed = NORMAL_SPEED;
+        let mut playing = true;
+        // Playback position, in game time.
+        let mut clock = Duration::ZERO;
+        let mut last_frame = Instant::now();
+        loop {
+            let now = Instant::now();
+            if playing {
+                clock += (now - last_frame).mul_f64(SPEEDS[speed]);
+            }
+            last_frame = now;
+            let end = replay.time(replay.len());
+            if clock >= end {
+                clock = end;
+                playing = false;
+            }
+            let position = replay.position_at(clock);
+
+            let tetris = replay.state(position);
+            let side_panel = vec![
+                String::from("REPLAY"),
+                format!(
+                    "{} / {}",
+                    sprint::format_time(clock),
+                    sprint::format_time(end)
+                ),
+                format!("Lines: {}", tetris.lines()),
+                format!("Speed: {}x", SPEEDS[speed]),
+                String::from(if playing { "Playing" } else { "Paused" }),
+                String::new(),
+                String::from("Enter: play/pause"),
+                String::from("Left/Right: step back/forward"),
+                String::from("Rotate: slower/faster"),
+                String::from("Esc: back"),
+            ];
+            let view = self.oriented(tetris.view());
+            self.draw(&view, None, side_panel, None, false)?;
+
+            // Wake up regularly for smooth playback.
+            let deadline = Some(Instant::now() + Duration::from_millis(50)).filter(|_| playing);
+            match self.driver.next(deadline) {
+                Some(Iteration::Continue) => {
+                    if clock >= end {
+                        clock = Duration::ZERO;
+                    }
+                    playing = !playing;
+                }
+                Some(Iteration::Event(evt)) | Some(Iteration::Press(evt)) => match evt {
+                    // Inputs handled in the same frame of the game are
+                    // stepped over together.
+                    Action::MoveLeft => {
+                        playing = false;
+                        clock = replay
+                            .time(position)
+                            .saturating_sub(Duration::from_nanos(1));
+                    }
+                    Action::MoveRight => {
+                        playing = false;
+                        clock = replay.time(position + 1);
+                    }
+                    Action::RotateCcw => speed = speed.saturating_sub(1),
+                    Action::RotateCw => speed = (speed + 1).min(SPEEDS.len() - 1),
+                    _ => (),
+                },
+                Some(Iteration::Quit) => return Ok(()),
+                Some(Iteration::Suspend) => {
+                    self.suspend()?;
+                    playing = false;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Run a wall of bot games until the viewer quits with Esc.
+    fn wall(&mut self, wall: &mut wall::Wall) -> io::Result<()> {
+        self.driver.set_ticking(true);
+        loop {
+            self.draw_wall(wall)?;
+            match self.driver.next(None) {
+                Some(Iteration::Tick) => wall.tick(),
+                Some(Iteration::Quit) => return Ok(()),
+                Some(Iteration::Suspend) => self.suspend()?,
+                _ => (),
+            }
+        }
+    }
+
+    /// Run the board editor until the player quits.
+    fn edit(&mut self, editor: &mut editor::Editor) -> io::Result<()> {
+        self.driver.set_ticking(false);
+        self.driver.set_typing(true);
+        let mut message = None;
+        loop {
+            let mut side_panel = editor.side_panel();
+            side_panel.extend(message.take());
+            self.draw(&editor.view(), None, side_panel, None, false)?;
+            let key = match self.driver.next(None) {
+                Some(Iteration::Type(key)) => key,
+                _ => continue,
+            };
+            match key {
+                KeyCode::Esc | KeyCode::Ctrl('c') => return Ok(()),
+                KeyCode::Ctrl('z') => self.suspend()?,
+                KeyCode::Left => editor.move_cursor(0, -1),
+                KeyCode::Right => editor.move_cursor(0, 1),
+                KeyCode::Up => editor.move_cursor(-1, 0),
+                KeyCode::Down => editor.move_cursor(1, 0),
+                KeyCode::Char(' ') => editor.toggle(),
+                KeyCode::Char('c') => editor.clear(),
+                KeyCode::Backspace => editor.pop(),
+                KeyCode::Char('\n') | KeyCode::Char('\r') => {
+                    message = Some(match editor.save() {
+                        Ok(()) => String::from("Saved."),
+                        Err(e) => e,
+                    });
+                }
+                KeyCode::Char(key) => {
+                    if let Some(piece) = editor::Editor::piece_for_key(key) {
+                        editor.push(piece);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Let the bot play one game after another, with nothing but the
+    /// board on screen, until any key is pressed.
+    fn screensaver(&mut self, demo: &mut wall::Wall) -> io::Result<()> {
+        self.driver.set_ticking(true);
+        loop {
+            if let Some(tetris) = demo.games().next() {
+                let view = self.oriented(tetris.view());
+                self.draw(&view, None, Vec::new(), None, false)?;
+            }
+            match self.driver.next(None) {
+                Some(Iteration::Tick) => demo.tick(),
+                // Letting go of the key that started it doesn't count,
+                // and neither do the terminal and the profile.
+                Some(Iteration::Release(_))
+                | Some(Iteration::Focus(_))
+                | Some(Iteration::Reload) => (),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Draw every game of a wall in its own cell, each labelled with its
+    /// lines, under a status line.
+    fn draw_wall(&mut self, wall: &wall::Wall) -> io::Result<()> {
+        // Wall frames bypass the single board cache.
+        self.drawn = None;
+        let (theme, half_blocks) = (&self.theme, self.half_blocks);
+        self.terminal.draw(|f| {
+            let size = f.size();
+            let status = Rect::new(0, 0, size.width, 1);
+            f.render_widget(Paragraph::new(wall.status()), status);
+            let area = Rect::new(0, 1, size.width, size.height.saturating_sub(1));
+            let cells = wall.layout(area);
+            let fits = |width: usize, height: usize| {
+                cells
+                    .iter()
+                    .all(|cell| cell.width as usize >= width && cell.height as usize > height)
+            };
+            let full_width = NCOLS * theme.cell_width();
+            let half = theme.half_block.is_some() && (half_blocks || !fits(full_width, NROWS));
+            let (width, height) = if half {
+                (NCOLS, NROWS.div_ceil(2))
+            } else {
+                (full_width, NROWS)
+            };
+            if !fits(width, height) {
+                let message = "Enlarge the terminal or shrink the wall.";
+                f.render_widget(Paragraph::new(message).wrap(Wrap { trim: true }), area);
+                return;
+            }
+            for (tetris, cell) in wall.games().zip(cells) {
+                let view = tetris.view();
+                let board = Rect::new(cell.x, cell.y, width as u16, height as u16);
+                if half {
+                    f.render_widget(HalfBlockGridWidget(&view, theme), board);
+                } else {
+                    f.render_widget(GridWidget(&view, theme), board);
+                }
+                let label = Rect::new(cell.x, cell.y + height as u16, cell.width, 1);
+                f.render_widget(Paragraph::new(format!("{} lines", tetris.lines())), label);
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Draw a board with text beside it, and optionally a word such as a
+    /// countdown over the board. A ghost's board goes between the two,
+    /// dimmed. A flashing board has its empty cells lit up.
+    fn draw(
+        &mut self,
+        view: &GameView,
+        ghost: Option<&GameView>,
+        side_panel: Vec<String>,
+        overlay: Option<String>,
+        flash: bool,
+    ) -> io::Result<()> {
+        let frame = (
+            view.clone(),
+            ghost.cloned(),
+            side_panel,
+            overlay.clone(),
+            flash,
+            self.terminal.size()?,
+        );
+        if self.drawn.as_ref() == Some(&frame) {
+            return Ok(());
+        }
+        let side_panel = frame.2.clone();
+        self.drawn = Some(frame);
+        let (protocol, half_blocks) = (self.protocol, self.half_blocks);
+        let panel_left = self.panel_left;
+        let ghost_theme = self.theme.clone().dimmed();
+        let theme = &if flash {
+            self.theme.clone().bumped()
+        } else {
+            self.theme.clone()
+        };
+        let mut board_x = 0;
+        self.terminal.draw(|f| {
+            let size = f.size();
+            // Fall back to half-height rendering when the terminal is
+            // too short to show one row per line.
+            let too_short = (size.height as usize) < NROWS;
+            let half = theme.half_block.is_some() && (half_blocks || too_short);
+            let cell_width = if half { 1 } else { theme.cell_width() as u16 };
+            let board_width = if protocol.is_some() {
+                2 * NCOLS as u16
+            } else {
+                NCOLS as u16 * cell_width
+            };
+            let ghost_width = NCOLS as u16 * cell_width;
+            let with_ghost = ghost.filter(|_| size.width >= board_width + 2 + ghost_width);
+            // Left of the board, the panel takes what the board and the
+            // ghost leave, up to a readable width.
+            let games_width = board_width + with_ghost.map_or(0, |_| ghost_width + 2);
+            let panel_width = if panel_left {
+                size.width.saturating_sub(games_width + 2).min(PANEL_WIDTH)
+            } else {
+                size.width.saturating_sub(games_width + 2)
+            };
+            let (panel_x, ghost_x) = if panel_left {
+                board_x = panel_width + 2;
+                (0, board_x + board_width + 2)
+            } else {
+                (games_width + 2, board_width + 2)
+            };
+            let area = Rect::new(board_x, 0, size.width.saturating_sub(board_x), size.height);
+            if protocol.is_some() {
+                // The board is drawn as an image after the frame.
+            } else if half {
+                f.render_widget(HalfBlockGridWidget(view, theme), area);
+            } else {
+                f.render_widget(GridWidget(view, theme), area);
+            }
+            // The ghost is drawn as text even next to an image board.
+            if let Some(ghost) = with_ghost {
+                let area = Rect::new(ghost_x, 0, ghost_width, size.height);
+                if half {
+                    f.render_widget(HalfBlockGridWidget(ghost, &ghost_theme), area);
+                } else {
+                    f.render_widget(GridWidget(ghost, &ghost_theme), area);
+                }
+            }
+            if !side_panel.is_empty() {
+                let text: Vec<Spans> = side_panel.into_iter().map(Spans::from).collect();
+                let area = Rect::new(panel_x, 0, panel_width, size.height);
+                f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), area);
+            }
+            if let Some(overlay) = overlay {
+                let row = size.height.min(NROWS as u16) / 2;
+                let area = Rect::new(board_x, row, board_width, 1);
+                f.render_widget(Paragraph::new(overlay).alignment(Alignment::Center), area);
+            }
+        })?;
+
+        if let Some(protocol) = protocol {
+            // Cells are roughly twice as tall as they are wide, so span
+            // two columns per grid cell to keep the blocks square.
+            let image = protocol.render(view, theme, 2 * NCOLS as u16, NROWS as u16);
+            let backend = self.terminal.backend_mut();
+            write!(
+                backend,
+                "{}{}",
+                termion::cursor::Goto(board_x + 1, 1),
+                image
+            )?;
+            Write::flush(backend)?;
+        }
+        Ok(())
+    }
+}
+
+/// The options that decide how a game started with `config` and these
+/// modes plays, passed on when sharing its seed.
+fn race_options(
+    config: &GameConfig,
+    cheese: Option<garbage::CheeseRace>,
+    floor: Option<&garbage::RisingFloor>,
+    training: bool,
+    sprint: bool,
+) -> Vec<String> {
+    let mut options = Vec::new();
+    // Kicks can only be named by their preset or the file they came from.
+    options.extend(profile::args().find(|arg| arg.starts_with("--preset=")));
+    options.extend(profile::args().find(|arg| arg.starts_with("--kicks=")));
+    options.push(format!("--gravity={}", config.gravity.as_millis()));
+    if config.curve != config::GravityCurve::Fixed {
+        options.push(format!("--gravity-curve={}", config.curve.name()));
+    }
+    if config.level > 0 {
+        options.push(format!("--level={}", config.level));
+    }
+    options.push(format!("--rotation={}", config.rotation.name()));
+    options.push(format!("--preview={}", config.preview));
+    if !config.hold {
+        options.push(String::from("--no-hold"));
+    }
+    if config.zen {
+        options.push(String::from("--zen"));
+    }
+    let mut messiness = None;
+    if let Some(cheese) = cheese {
+        if cheese.climb {
+            options.push(String::from("--climb"));
+        } else {
+            options.push(format!("--cheese={}", cheese.rows));
+        }
+        messiness = Some(cheese.messiness);
+    }
+    if let Some(floor) = floor {
+        options.push(format!("--rising={}", floor.interval().as_secs_f64()));
+        messiness = Some(floor.messiness());
+    }
+    if let Some(messiness) = messiness {
+        options.push(format!("--messiness={}", messiness));
+    }
+    if training {
+        options.push(String::from("--trainer"));
+    }
+    if sprint {
+        options.push(String::from("--sprint"));
+    }
+    options
+}
+
+/// The command line for a friend to play the same game, on `seed` with
+/// the `race_options` of this one, and compare.
+fn race_command(seed: u64, options: &[String]) -> String {
+    let mut command = format!("tetris-rs --seed={}", seed);
+    for option in options {
+        command.push(' ');
+        command.push_str(option);
+    }
+    command
+}
+
+/// Describe a sprint run for the match results.
+fn describe(run: &tournament::Run) -> String {
+    match run.finished {
+        Some(time) => format!("{} lines in {:.2}s", run.lines, time.as_secs_f64()),
+        None => format!("topped out at {} lines", run.lines),
+    }
+}
+
+/// Play a hot-seat sprint tournament through to the final standings, or
+/// until someone quits.
+fn run_tournament(
+    session: &mut Session,
+    config: &GameConfig,
+    mut bracket: tournament::Bracket,
+) -> io::Result<()> {
+    let base_seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    let mut matches = 0;
+    while let Some((a, b)) = bracket.next_match() {
+        // Both players of a match get the same pieces.
+        let round_config = GameConfig {
+            seed: Some(base_seed.wrapping_add(matches)),
+            ..config.clone()
+        };
+        matches += 1;
+        let heading = format!(
+            "Round {}: {} vs {}",
+            bracket.round(),
+            bracket.name(a),
+            bracket.name(b)
+        );
+        let mut runs = Vec::new();
+        for player in [a, b] {
+            let ready = session.interstitial(&[
+                heading.clone(),
+                String::new(),
+                format!(
+                    "{}, clear {} lines as fast as you can.",
+                    bracket.name(player),
+                    sprint::SPRINT_LINES
+                ),
+                String::from("Press Enter to start."),
+            ])?;
+            if !ready {
+                return Ok(());
+            }
+            session.set_title(format!("tetris-rs | {}", bracket.name(player)))?;
+            let run = session.play(
+                &mut round_config.clone().build(),
+                None,
+                None,
+                true,
+                None,
+                "Press Enter to continue.",
+            )?;
+            match run {
+                Some(run) => runs.push(run),
+                None => return Ok(()),
+            }
+        }
+        let winner = if runs[1].beats(&runs[0]) { b } else { a };
+        let results = [
+            heading,
+            String::new(),
+            format!("{}: {}", bracket.name(a), describe(&runs[0])),
+            format!("{}: {}", bracket.name(b), describe(&runs[1])),
+            String::new(),
+            format!("{} goes through.", bracket.name(winner)),
+            String::from("Press Enter to continue."),
+        ];
+        bracket.record(winner);
+        if !session.interstitial(&results)? {
+            return Ok(());
+        }
+    }
+
+    let mut standings = vec![String::from("FINAL STANDINGS"), String::new()];
+    for (place, name) in bracket.standings() {
+        standings.push(format!("{}. {}", place, name));
+    }
+    standings.push(String::new());
+    standings.push(String::from("Press Enter to exit."));
+    session.interstitial(&standings)?;
+    Ok(())
+}
+
+/// Run `tetris-rs eval` with the arguments after `eval`.
+fn run_eval(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let options = eval::Options::parse(args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, eval::USAGE);
+        std::process::exit(2);
+    });
+    let report = eval::report(&options, &eval::run(&options));
+    match &options.out {
+        Some(path) => std::fs::write(path, report),
+        None => io::stdout().write_all(report.as_bytes()),
     }
 }
 
 fn main() -> Result<(), io::Error> {
-    let stdout = io::stdout().into_raw_mode()?;
-    let backend = TermionBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    let mut tetris = Tetris::new();
-    let driver = Driver::new();
+    crash::install();
+    let result = std::panic::catch_unwind(run);
+    if let Some(crash) = crash::take() {
+        // The terminal was restored on the way out, but for the modes
+        // only turned on by escape sequences.
+        print!(
+            "{}{}{}",
+            input::DISABLE_KEY_EVENTS,
+            input::DISABLE_FOCUS_EVENTS,
+            title::restore()
+        );
+        eprintln!("{}", crash);
+        std::process::exit(101);
+    }
+    result.unwrap_or_else(|_| std::process::exit(101))
+}
+
+fn run() -> Result<(), io::Error> {
+    if std::env::args().nth(1).as_deref() == Some("eval") {
+        return run_eval(std::env::args().skip(2));
+    }
+    profile::init().map_err(io::Error::other)?;
+    logging::init().map_err(io::Error::other)?;
+    let config = GameConfig::from_args().map_err(io::Error::other)?;
+
+    #[cfg(feature = "gui")]
+    {
+        if profile::args().any(|arg| arg == "--gui") {
+            return gui::run(config).map_err(|e| io::Error::other(e.to_string()));
+        }
+    }
+
+    if profile::args().any(|arg| arg == "--achievements") {
+        let unlocked = achievements::Achievements::load().map_err(io::Error::other)?;
+        for achievement in achievements::ALL.iter() {
+            println!(
+                "[{}] {}: {}",
+                if unlocked.is_unlocked(*achievement) {
+                    "x"
+                } else {
+                    " "
+                },
+                achievement.name(),
+                achievement.description()
+            );
+        }
+        return Ok(());
+    }
 
-    loop {
-        match driver.next() {
-            Iteration::Tick => {
-                if !tetris.tick() {
-                    break;
+    let keymap = Keymap::from_args().map_err(io::Error::other)?;
+    let handling = input::Handling::from_args().map_err(io::Error::other)?;
+    let difficulty = ai::Difficulty::from_args().map_err(io::Error::other)?;
+    let weights = ai::Weights::from_args().map_err(io::Error::other)?;
+    let bracket = tournament::Bracket::from_args().map_err(io::Error::other)?;
+    let wall_size = wall::Size::from_args().map_err(io::Error::other)?;
+    let practice = rewind::Rewind::from_args(config.gravity).map_err(io::Error::other)?;
+    let tas = profile::args().find_map(|arg| arg.strip_prefix("--tas=").map(String::from));
+    let ghost = profile::args()
+        .find_map(|arg| arg.strip_prefix("--ghost=").map(String::from))
+        .map(|path| tas::Movie::load(&path))
+        .transpose()
+        .map_err(io::Error::other)?;
+    let idle = profile::args()
+        .find_map(|arg| arg.strip_prefix("--idle-pause=").map(String::from))
+        .map(|seconds| {
+            seconds
+                .parse::<f64>()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .filter(|idle| !idle.is_zero())
+                .ok_or_else(|| format!("invalid --idle-pause={:?}, expected seconds", seconds))
+        })
+        .transpose()
+        .map_err(io::Error::other)?;
+    let edit = profile::args()
+        .find_map(|arg| arg.strip_prefix("--edit=").map(String::from))
+        .map(|path| editor::Editor::open(&path))
+        .transpose()
+        .map_err(io::Error::other)?;
+    let puzzle = profile::args()
+        .find_map(|arg| arg.strip_prefix("--puzzle=").map(String::from))
+        .map(|path| puzzle::Puzzle::load(&path))
+        .transpose()
+        .map_err(io::Error::other)?;
+    let panel_left =
+        match profile::args().find_map(|arg| arg.strip_prefix("--layout=").map(String::from)) {
+            None => false,
+            Some(layout) => match layout.as_str() {
+                "right" => false,
+                "left" => true,
+                _ => {
+                    return Err(io::Error::other(format!(
+                        "unknown layout {:?}, expected right or left",
+                        layout
+                    )))
                 }
+            },
+        };
+    let practicing = practice.is_some();
+    let cheese = garbage::CheeseRace::from_args().map_err(io::Error::other)?;
+    let bot_seed = config.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    let floor =
+        garbage::RisingFloor::from_args(config.gravity, bot_seed).map_err(io::Error::other)?;
+    // Practice keeps its own speed, and the rising floor and the rewind
+    // window count gravity ticks at the starting speed.
+    let trainer = trainer::SpeedTrainer::from_args(config.gravity)
+        .filter(|_| bracket.is_none() && !practicing && floor.is_none());
+    let training = trainer.is_some();
+    let chat = chat::Chat::from_args().map_err(io::Error::other)?;
+    let chat_plays = chat.is_some();
+    // Tournament games stay clean of debug commands.
+    let dev = bracket.is_none() && profile::args().any(|arg| arg == "--dev");
+    // Movies only keep the moves, so a game these modes change in other
+    // ways wouldn't play back the same.
+    if tas.is_some() && bracket.is_none() {
+        let modes = [
+            ("--rising", floor.is_some()),
+            ("--trainer", training),
+            ("--dev", dev),
+        ];
+        if let Some((mode, _)) = modes.iter().find(|(_, on)| *on) {
+            return Err(io::Error::other(format!(
+                "--tas can't be used with {}, as movies only keep the moves",
+                mode
+            )));
+        }
+    }
+    let sprint = profile::args().any(|arg| arg == "--sprint");
+    let race = race_options(&config, cheese, floor.as_ref(), training, sprint);
+    let speak = profile::args().any(|arg| arg == "--speak");
+    let low_bandwidth = profile::args().any(|arg| arg == "--low-bandwidth");
+    let screensaver = profile::args().any(|arg| arg == "--screensaver");
+    let reduced_motion = profile::args().any(|arg| arg == "--reduced-motion");
+    let mut theme = theme::Theme::detect().map_err(io::Error::other)?;
+    if screensaver {
+        theme = theme.dimmed();
+    }
+    let mut session = Session {
+        // Raw mode applies to the whole terminal, so keep the handle that
+        // controls it separate from the one tui writes to.
+        raw: Some(io::stdout().into_raw_mode()?),
+        terminal: Terminal::new(TermionBackend::new(io::stdout()))?,
+        driver: Driver::new(io::stdin(), keymap, config.gravity, chat, dev),
+        // Images are sent whole on every frame, while the cell grid only
+        // sends the cells that changed.
+        protocol: graphics::Protocol::detect().filter(|_| !low_bandwidth),
+        theme,
+        handling,
+        half_blocks: profile::args().any(|arg| arg == "--half-blocks"),
+        panel_left,
+        mirror: profile::args().any(|arg| arg == "--mirror"),
+        low_bandwidth,
+        bell: profile::args().any(|arg| arg == "--bell"),
+        show_debug: profile::args().any(|arg| arg == "--debug"),
+        show_stats: profile::args().any(|arg| arg == "--stats"),
+        // TAS mode steps through the game frame by frame.
+        stepping: tas.is_some() || profile::args().any(|arg| arg == "--step"),
+        announce: Some(speak).filter(|_| speak || profile::args().any(|arg| arg == "--accessible")),
+        // Tournament players don't get to take moves back.
+        rewind: practice.filter(|_| bracket.is_none()),
+        savestates: Some(savestate::Savestates::default())
+            .filter(|_| practicing && bracket.is_none()),
+        climb: cheese.is_some_and(|cheese| cheese.climb),
+        floor: floor.filter(|_| bracket.is_none()),
+        trainer,
+        // Ghosts follow the clock, which practice, trainer and TAS games
+        // bend.
+        ghost: ghost.filter(|_| bracket.is_none() && !practicing && !training && tas.is_none()),
+        save_ghost: profile::args()
+            .find_map(|arg| arg.strip_prefix("--save-ghost=").map(String::from)),
+        tas: tas.filter(|_| bracket.is_none()),
+        // Chat votes don't come from the keyboard.
+        idle: idle.filter(|_| !chat_plays),
+        focus_pause: profile::args().any(|arg| arg == "--focus-pause"),
+        bump_flash: !reduced_motion && profile::args().any(|arg| arg == "--bump-flash"),
+        reduced_motion,
+        console: Some(console::Console::new(bot_seed)).filter(|_| dev),
+        export: profile::args().find_map(|arg| arg.strip_prefix("--export=").map(String::from)),
+        race,
+        // Only published when asked for, and a missing Discord client
+        // shouldn't stop anyone from playing.
+        #[cfg(feature = "discord")]
+        presence: profile::args()
+            .find_map(|arg| arg.strip_prefix("--discord=").map(String::from))
+            .and_then(|client_id| {
+                discord::Presence::connect(&client_id)
+                    .map_err(|e| warn!(%e, "no Discord presence"))
+                    .ok()
+            }),
+        shown_title: String::new(),
+        drawn: None,
+    };
+    write!(
+        session.terminal.backend_mut(),
+        "{}{}{}",
+        input::ENABLE_KEY_EVENTS,
+        input::ENABLE_FOCUS_EVENTS,
+        title::save()
+    )?;
+
+    // The bot of the demo modes, at full strength unless told otherwise.
+    let mut demo_strength = difficulty.unwrap_or(ai::Difficulty::Hard).strength();
+    if let Some(weights) = weights {
+        demo_strength.weights = weights;
+    }
+    if let Some(bracket) = bracket {
+        run_tournament(&mut session, &config, bracket)?;
+    } else if let Some(mut editor) = edit {
+        session.edit(&mut editor)?;
+    } else if let Some(size) = wall_size {
+        session.wall(&mut wall::Wall::new(size, &config, demo_strength, bot_seed))?;
+    } else if screensaver {
+        // A wall of one, for its endless stream of games.
+        let size = wall::Size {
+            columns: 1,
+            rows: 1,
+        };
+        session.screensaver(&mut wall::Wall::new(size, &config, demo_strength, bot_seed))?;
+    } else {
+        let mut bot = difficulty.map(|difficulty| {
+            let mut strength = difficulty.strength();
+            if let Some(weights) = weights {
+                strength.weights = weights;
             }
-            Iteration::Event(evt) => tetris.event(evt),
+            ai::Bot::new(strength, bot_seed)
+        });
+        // Games played by the bot or by chat, rewound in practice or
+        // set up from the console don't count towards achievements or
+        // personal bests.
+        let solo = bot.is_none() && !chat_plays && !practicing && !dev;
+        let mut achievements = if solo {
+            Some(achievements::Achievements::load().map_err(io::Error::other)?)
+        } else {
+            None
+        };
+        // Already checked when reading the config.
+        let preset = profile::args().find_map(|arg| {
+            arg.strip_prefix("--preset=")
+                .and_then(config::Preset::from_name)
+        });
+        let mut personal_best = if sprint && solo {
+            Some(sprint::PersonalBest::load(preset).map_err(io::Error::other)?)
+        } else {
+            None
+        };
+        let mut tetris = match puzzle {
+            Some(puzzle) => puzzle.start(config.clone()),
+            None => config.build(),
+        };
+        if let Some(cheese) = cheese {
+            cheese.setup(&mut tetris, bot_seed);
+        }
+        session.play(
+            &mut tetris,
+            bot.as_mut(),
+            achievements.as_mut(),
+            sprint,
+            personal_best.as_mut(),
+            "Press Esc to exit.",
+        )?;
+    }
+
+    write!(
+        session.terminal.backend_mut(),
+        "{}{}{}",
+        input::DISABLE_KEY_EVENTS,
+        input::DISABLE_FOCUS_EVENTS,
+        title::restore()
+    )?;
+    Write::flush(session.terminal.backend_mut())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::backend::TestBackend;
+    use tui::buffer::Cell;
+
+    /// A screen in memory to draw the game on. Escape sequences written
+    /// alongside the frames, such as the window title, are dropped.
+    struct Screen(TestBackend);
+
+    impl Backend for Screen {
+        fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+        where
+            I: Iterator<Item = (u16, u16, &'a Cell)>,
+        {
+            self.0.draw(content)
+        }
+        fn hide_cursor(&mut self) -> io::Result<()> {
+            self.0.hide_cursor()
+        }
+        fn show_cursor(&mut self) -> io::Result<()> {
+            self.0.show_cursor()
         }
+        fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
+            self.0.get_cursor()
+        }
+        fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+            self.0.set_cursor(x, y)
+        }
+        fn clear(&mut self) -> io::Result<()> {
+            self.0.clear()
+        }
+        fn size(&self) -> io::Result<Rect> {
+            self.0.size()
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Backend::flush(&mut self.0)
+        }
+    }
+
+    impl Write for Screen {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 
-        let render_grid = GridWidget(&tetris.grid());
-        terminal
-            .draw(|f| {
-                let size = f.size();
-                f.render_widget(render_grid, size);
+    /// A session on an 80x24 screen in frame-step mode, with `keys` typed
+    /// all at once.
+    fn session(keys: &'static [u8]) -> Session<Screen> {
+        Session {
+            raw: None,
+            terminal: Terminal::new(Screen(TestBackend::new(80, 24))).unwrap(),
+            driver: Driver::new(keys, Keymap::default(), Duration::from_secs(1), None, false),
+            protocol: None,
+            theme: theme::Theme::ascii(),
+            handling: input::Handling::default(),
+            half_blocks: false,
+            panel_left: false,
+            mirror: false,
+            low_bandwidth: false,
+            bell: false,
+            show_debug: false,
+            show_stats: false,
+            stepping: true,
+            announce: None,
+            rewind: None,
+            savestates: None,
+            climb: false,
+            floor: None,
+            trainer: None,
+            ghost: None,
+            save_ghost: None,
+            tas: None,
+            idle: None,
+            focus_pause: false,
+            bump_flash: false,
+            reduced_motion: false,
+            console: None,
+            export: None,
+            race: Vec::new(),
+            #[cfg(feature = "discord")]
+            presence: None,
+            shown_title: String::new(),
+            drawn: None,
+        }
+    }
+
+    /// The rows of the screen as text.
+    fn lines(session: &Session<Screen>) -> Vec<String> {
+        let buffer = session.terminal.backend().0.buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect()
             })
+            .collect()
+    }
+
+    #[test]
+    fn test_play_to_the_finish() {
+        // The O drops into the well, clearing both rows, and the I comes
+        // next. Enter leaves the game over screen.
+        let puzzle: puzzle::Puzzle = "queue OI\n####..####\n####..####\n".parse().unwrap();
+        let mut tetris = puzzle.start(GameConfig::new());
+        let keys = b"a.d........................\r";
+        let mut session = session(keys);
+        let run = session
+            .play(&mut tetris, None, None, false, None, "Press Enter.")
             .unwrap();
+        assert_eq!(run.map(|run| run.lines), Some(2));
+
+        let screen = lines(&session);
+        let board: Vec<&str> = screen.iter().map(|line| &line[..NCOLS]).collect();
+        assert!(board[..2].iter().any(|row| row.contains("####")));
+        assert!(board[2..NROWS].iter().all(|row| !row.contains('#')));
+        let panel = screen.join("\n");
+        for text in ["DOUBLE", "PERFECT CLEAR", "FINISHED", "Press Enter."] {
+            assert!(panel.contains(text), "no {:?} in\n{}", text, panel);
+        }
     }
 
-    Ok(())
+    #[test]
+    fn test_race_options() {
+        let config =
+            GameConfig::from_preset(config::Preset::Classic).gravity(Duration::from_secs(2));
+        let floor = garbage::RisingFloor::new(Duration::from_secs(10), config.gravity, 1, 50);
+        let options = race_options(&config, None, Some(&floor), false, true);
+        assert_eq!(
+            race_command(7, &options),
+            "tetris-rs --seed=7 --gravity=2000 --rotation=classic --preview=1 --no-hold \
+             --rising=10 --messiness=50 --sprint"
+        );
+        let cheese = garbage::CheeseRace {
+            rows: 4,
+            messiness: 0,
+            climb: false,
+        };
+        let options = race_options(
+            &GameConfig::new().zen(true),
+            Some(cheese),
+            None,
+            true,
+            false,
+        );
+        assert_eq!(
+            options[3..],
+            ["--zen", "--cheese=4", "--messiness=0", "--trainer"]
+        );
+    }
 }
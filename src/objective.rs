@@ -0,0 +1,215 @@
+//! What it takes to win or lose a game beyond topping out. The engine
+//! asks a game's objective for a verdict every time a piece locks, so a
+//! new mode is one `Objective` implementation rather than checks
+//! threaded through the frontends.
+
+use super::game_state::Tetris;
+use alloc::sync::Arc;
+use core::fmt;
+use core::time::Duration;
+
+/// How a game with an objective ended.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Verdict {
+    /// The objective was met; the game stops as completed.
+    Won,
+    /// The objective can't be met any more; the game ends as if the
+    /// player topped out, with `TopOut::Failed`.
+    Lost,
+}
+
+/// A win or lose condition, judged by the engine after each lock.
+pub trait Objective: Send + Sync {
+    /// Short description of the goal, for frontends to show.
+    fn name(&self) -> &str;
+
+    /// Whether `tetris` has won or lost, or `None` while it plays on.
+    fn judge(&self, tetris: &Tetris) -> Option<Verdict>;
+}
+
+/// Clear this many lines, as in a sprint.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TargetLines(pub u32);
+
+impl Objective for TargetLines {
+    fn name(&self) -> &str {
+        "clear the lines"
+    }
+
+    fn judge(&self, tetris: &Tetris) -> Option<Verdict> {
+        (tetris.lines() >= self.0).then_some(Verdict::Won)
+    }
+}
+
+/// Stay alive for this much game time, e.g. against a rising floor.
+/// Time is counted in gravity ticks, see `Tetris::time`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TargetTime(pub Duration);
+
+impl Objective for TargetTime {
+    fn name(&self) -> &str {
+        "survive"
+    }
+
+    fn judge(&self, tetris: &Tetris) -> Option<Verdict> {
+        (tetris.time() >= self.0).then_some(Verdict::Won)
+    }
+}
+
+/// Dig through all the garbage, as in a cheese race.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ClearGarbage;
+
+impl Objective for ClearGarbage {
+    fn name(&self) -> &str {
+        "clear the garbage"
+    }
+
+    fn judge(&self, tetris: &Tetris) -> Option<Verdict> {
+        (tetris.garbage_rows() == 0).then_some(Verdict::Won)
+    }
+}
+
+/// Clear everything off the board, as in a puzzle, within so many
+/// pieces if given.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ClearBoard {
+    pub pieces: Option<u32>,
+}
+
+impl Objective for ClearBoard {
+    fn name(&self) -> &str {
+        "clear the board"
+    }
+
+    fn judge(&self, tetris: &Tetris) -> Option<Verdict> {
+        if tetris.stack_height() == 0 {
+            Some(Verdict::Won)
+        } else if self.pieces.is_some_and(|pieces| tetris.pieces() >= pieces) {
+            Some(Verdict::Lost)
+        } else {
+            None
+        }
+    }
+}
+
+/// The objective a game carries. Games are cloned for the bot and for
+/// rewinding, so it is shared rather than copied, and two games only
+/// compare equal with the very same objective.
+#[derive(Clone)]
+pub(crate) struct Goal(pub(crate) Arc<dyn Objective>);
+
+impl PartialEq for Goal {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Goal {}
+
+impl fmt::Debug for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Goal").field(&self.0.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::{Cell, Piece, TopOut, NCOLS, NROWS};
+    use crate::garbage::CheeseRace;
+
+    /// Fill the bottom `rows` of the board with `cell` but for the two
+    /// middle columns, where the first piece of the fixed order, an O,
+    /// drops in to clear them.
+    fn well(tetris: &mut Tetris, rows: usize, cell: Cell) {
+        assert_eq!(tetris.piece(), Piece::O);
+        for row in NROWS - rows..NROWS {
+            for col in 0..NCOLS {
+                let middle = (4..=5).contains(&col);
+                tetris
+                    .board
+                    .set(row, col, if middle { Cell::Empty } else { cell });
+            }
+        }
+    }
+
+    #[test]
+    fn test_target_lines() {
+        let mut tetris = Tetris::new();
+        tetris.set_objective(TargetLines(3));
+        well(&mut tetris, 2, Cell::Locked(Piece::I));
+        assert!(tetris.hard_drop());
+        assert!(!tetris.completed());
+
+        let mut tetris = Tetris::new();
+        tetris.set_objective(TargetLines(2));
+        well(&mut tetris, 2, Cell::Locked(Piece::I));
+        assert!(!tetris.hard_drop());
+        assert!(tetris.completed());
+        assert_eq!(tetris.top_out(), None);
+        // A completed game is over.
+        assert!(!tetris.tick().continues());
+        assert_eq!(tetris.lines(), 2);
+        assert_eq!(
+            tetris.objective().map(Objective::name),
+            Some("clear the lines")
+        );
+    }
+
+    #[test]
+    fn test_target_time() {
+        let mut tetris = Tetris::new();
+        let gravity = tetris.config().gravity;
+        tetris.set_objective(TargetTime(gravity * 30));
+        while tetris.tick().continues() {}
+        assert!(tetris.completed());
+        assert!(tetris.time() >= gravity * 30);
+
+        // Hard drops take no time.
+        let mut tetris = Tetris::new();
+        tetris.set_objective(TargetTime(gravity));
+        assert!(tetris.hard_drop());
+        assert_eq!(tetris.time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clear_garbage() {
+        let mut tetris = Tetris::new();
+        let race = CheeseRace {
+            rows: 1,
+            messiness: 0,
+            climb: false,
+        };
+        race.setup(&mut tetris, 0);
+        assert_eq!(
+            tetris.objective().map(Objective::name),
+            Some("clear the garbage")
+        );
+        well(&mut tetris, 1, Cell::Garbage);
+        let mut restarted = tetris.clone();
+        restarted.restart();
+        assert!(restarted.objective().is_some());
+
+        assert!(!tetris.hard_drop());
+        assert!(tetris.completed());
+    }
+
+    #[test]
+    fn test_clear_board() {
+        let puzzle = ClearBoard { pieces: Some(1) };
+        let mut tetris = Tetris::new();
+        tetris.set_objective(puzzle);
+        well(&mut tetris, 2, Cell::Locked(Piece::I));
+        assert!(!tetris.hard_drop());
+        assert!(tetris.completed());
+
+        // Out of pieces with the board still full.
+        let mut tetris = Tetris::new();
+        tetris.set_objective(puzzle);
+        well(&mut tetris, 3, Cell::Locked(Piece::I));
+        assert!(!tetris.hard_drop());
+        assert_eq!(tetris.top_out(), Some(TopOut::Failed));
+        assert!(!tetris.completed());
+    }
+}
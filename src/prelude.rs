@@ -0,0 +1,26 @@
+//! The types most programs driving the engine need, in one import:
+//!
+//! ```
+//! use tetris_rs::prelude::*;
+//!
+//! let mut tetris = GameConfig::new().seed(7).build();
+//! tetris.event(Action::MoveLeft);
+//! tetris.hard_drop();
+//! let view: GameView = tetris.view();
+//! assert_eq!(view.lines, 0);
+//! assert!(matches!(tetris.take_events()[..], [GameEvent::Locked(_)]));
+//! assert_eq!(tetris.tick(), TickOutcome::Continued);
+//! ```
+//!
+//! Everything here stays put across minor releases; the module paths
+//! behind it may move. Player inputs are `Action`s, and a replay step is
+//! a `Step`.
+
+pub use crate::config::{GameConfig, Preset};
+#[allow(deprecated)]
+pub use crate::game_state::Event;
+pub use crate::game_state::{
+    Action, Cell, Ending, GameEvent, GameView, Piece, Step, Tetris, TickOutcome, TopOut, NCOLS,
+    NROWS,
+};
+pub use crate::objective::{Objective, Verdict};
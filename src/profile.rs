@@ -0,0 +1,176 @@
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::{PoisonError, RwLock};
+
+/// The effective command line, once a profile has been merged in.
+/// Replaced when the profile is reloaded.
+static ARGS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+thread_local! {
+    /// A reloaded command line being checked on this thread, before it
+    /// replaces `ARGS` for every thread.
+    static CHECKING: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// The command line arguments followed by the options saved in the
+/// profile selected with `--profile=<name>`. Options are looked up by
+/// first match, so the command line overrides the profile.
+pub fn args() -> impl Iterator<Item = String> {
+    CHECKING
+        .with(|checking| checking.borrow().clone())
+        .or_else(|| ARGS.read().unwrap_or_else(PoisonError::into_inner).clone())
+        .unwrap_or_else(|| std::env::args().collect())
+        .into_iter()
+}
+
+/// Where profiles are stored: `$XDG_CONFIG_HOME/tetris-rs/profiles`,
+/// falling back to `~/.config`.
+pub fn dir() -> Result<PathBuf, String> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").ok_or("$HOME is not set")?).join(".config"),
+    };
+    Ok(config.join("tetris-rs").join("profiles"))
+}
+
+/// A file for data kept per player, such as achievements:
+/// `<kind>` next to the profiles directory, or `<kind>-<profile>` when a
+/// profile is in use.
+pub fn data_path(kind: &str) -> Result<PathBuf, String> {
+    let name = args()
+        .find_map(|arg| arg.strip_prefix("--profile=").map(String::from))
+        .map_or_else(|| String::from(kind), |name| format!("{}-{}", kind, name));
+    let profiles = dir()?;
+    let root = profiles.parent().expect("profiles live in a directory");
+    Ok(root.join(name))
+}
+
+fn path(name: &str) -> Result<PathBuf, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if name.is_empty() || !name.chars().all(valid) {
+        return Err(format!(
+            "invalid profile name {:?}, use letters, digits, - and _",
+            name
+        ));
+    }
+    Ok(dir()?.join(name))
+}
+
+/// Options from a profile file: one per line, skipping blank lines and
+/// `#` comments.
+fn parse(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Handle `--save-profile=<name>`, which stores the other options on
+/// the command line under that name, and `--profile=<name>`, which
+/// loads them back. Call before anything reads `args`.
+pub fn init() -> Result<(), String> {
+    let cmdline: Vec<String> = std::env::args().collect();
+    let find = |prefix: &str| cmdline.iter().find_map(|arg| arg.strip_prefix(prefix));
+
+    if let Some(name) = find("--save-profile=") {
+        let path = path(name)?;
+        let options: Vec<&str> = cmdline
+            .iter()
+            .skip(1)
+            .map(String::as_str)
+            .filter(|arg| !arg.starts_with("--profile=") && !arg.starts_with("--save-profile="))
+            .collect();
+        std::fs::create_dir_all(dir()?)
+            .and_then(|_| std::fs::write(&path, options.join("\n") + "\n"))
+            .map_err(|e| format!("can't save profile {}: {}", path.display(), e))?;
+    }
+
+    let args = merge(cmdline)?;
+    let mut loaded = ARGS.write().unwrap_or_else(PoisonError::into_inner);
+    if loaded.is_some() {
+        return Err(String::from("profile already loaded"));
+    }
+    *loaded = Some(args);
+    Ok(())
+}
+
+/// The file of the profile selected with `--profile=<name>`, if any.
+pub fn current() -> Result<Option<PathBuf>, String> {
+    std::env::args()
+        .find_map(|arg| arg.strip_prefix("--profile=").map(path))
+        .transpose()
+}
+
+/// `cmdline` followed by the options in the current profile.
+fn merge(mut cmdline: Vec<String>) -> Result<Vec<String>, String> {
+    if let Some(path) = current()? {
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("can't read profile {}: {}", path.display(), e))?;
+        cmdline.extend(parse(&contents));
+    }
+    Ok(cmdline)
+}
+
+/// Read the profile again, for changes made while the game runs, and
+/// keep its options if `check` accepts them. `check` sees the new
+/// options through `args` on this thread only, and other threads keep
+/// the options from before until they are accepted. Otherwise those
+/// stay in effect and `check`'s error is returned.
+pub fn reload<T>(check: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let args = merge(std::env::args().collect())?;
+    CHECKING.with(|checking| *checking.borrow_mut() = Some(args));
+    let checked = check();
+    let args = CHECKING.with(|checking| checking.borrow_mut().take());
+    if checked.is_ok() {
+        *ARGS.write().unwrap_or_else(PoisonError::into_inner) = args;
+    }
+    checked
+}
+
+/// Call `changed` whenever the current profile is written to, until the
+/// returned watcher is dropped. Without a profile there is nothing to
+/// watch, and `None` is returned.
+pub fn watch(
+    changed: impl Fn() + Send + 'static,
+) -> Result<Option<notify::RecommendedWatcher>, String> {
+    let path = match current()? {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let watched = path.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // Editors often save by writing a new file and renaming it over
+        // the old one, so the directory is watched rather than the file.
+        if let Ok(event) = event {
+            let saved = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+            if saved && event.paths.contains(&watched) {
+                changed();
+            }
+        }
+    })
+    .map_err(|e| format!("can't watch profile {}: {}", path.display(), e))?;
+    let dir = path.parent().expect("profiles live in a directory");
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("can't watch profile {}: {}", path.display(), e))?;
+    Ok(Some(watcher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names_and_contents() {
+        assert!(path("alice_2").is_ok());
+        assert!(path("../etc").is_err());
+        assert!(path("").is_err());
+        assert_eq!(
+            parse("# alice\n--keys=vi\n\n  --ascii  \n"),
+            vec!["--keys=vi", "--ascii"]
+        );
+    }
+}
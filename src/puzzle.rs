@@ -0,0 +1,131 @@
+//! Puzzles: a board to clear with a given queue of pieces, as made in
+//! the board editor (`--edit`) and played with `--puzzle`.
+
+use super::board::{Board, Placement};
+use super::config::GameConfig;
+use super::game_state::{Cell, Piece, Tetris, NCOLS, NROWS};
+use super::objective::ClearBoard;
+use super::randomizer::Randomizer;
+use super::rotation::piece_from_name;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+/// A board to clear, and the pieces to clear it with.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    pub board: Board,
+    /// The pieces dealt, in order. The puzzle is failed once they are
+    /// all placed without clearing the board; without any, pieces come
+    /// in the usual fixed order and there is no limit.
+    pub queue: Vec<Piece>,
+}
+
+impl Puzzle {
+    /// Read a puzzle from a file, see `FromStr`.
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        contents.parse().map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Write the puzzle to a file, in its `Display` format.
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|e| format!("can't save {}: {}", path, e))
+    }
+
+    /// A game of the puzzle with the given settings, the board filled in
+    /// with garbage and clearing it as the objective. Cells where the
+    /// first piece spawns are left empty.
+    pub fn start(&self, config: GameConfig) -> Tetris {
+        let mut tetris = Tetris::with_randomizer(config, Randomizer::recorded(self.queue.clone()));
+        let falling = Placement::falling(&tetris).cells();
+        for row in 0..NROWS {
+            for col in 0..NCOLS {
+                if self.board.is_filled(row, col) && !falling.contains(&(row as i32, col as i32)) {
+                    tetris.board.set(row, col, Cell::Garbage);
+                }
+            }
+        }
+        let pieces = Some(self.queue.len() as u32).filter(|pieces| *pieces > 0);
+        tetris.set_objective(ClearBoard { pieces });
+        tetris
+    }
+}
+
+/// A `queue` line with the pieces' letters, then the board in its own
+/// text format.
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("queue ")?;
+        for piece in &self.queue {
+            write!(f, "{:?}", piece)?;
+        }
+        write!(f, "\n{}", self.board)
+    }
+}
+
+impl FromStr for Puzzle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (first, board) = s.split_once('\n').unwrap_or((s, ""));
+        let letters = first
+            .trim()
+            .strip_prefix("queue")
+            .ok_or_else(|| format!("expected a `queue` line, got {:?}", first))?;
+        let queue = letters
+            .trim()
+            .chars()
+            .map(|letter| {
+                piece_from_name(letter.encode_utf8(&mut [0; 4]))
+                    .ok_or_else(|| format!("unknown piece {:?} in the queue", letter))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            board: board.parse()?,
+            queue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::TopOut;
+
+    #[test]
+    fn test_text() {
+        let text = "queue OI\n####..####\n####..####\n";
+        let puzzle: Puzzle = text.parse().unwrap();
+        assert_eq!(puzzle.queue, vec![Piece::O, Piece::I]);
+        assert!(puzzle.board.is_filled(NROWS - 1, 0));
+        assert!(!puzzle.board.is_filled(NROWS - 1, 4));
+        assert_eq!(puzzle.to_string(), text);
+
+        assert_eq!(Puzzle::default().to_string(), "queue \n");
+        assert!("####..####".parse::<Puzzle>().is_err());
+        assert!("queue OX\n".parse::<Puzzle>().is_err());
+    }
+
+    #[test]
+    fn test_start() {
+        let puzzle: Puzzle = "queue OI\n####..####\n####..####\n".parse().unwrap();
+        let mut tetris = puzzle.start(GameConfig::new());
+        assert_eq!(tetris.garbage_rows(), 2);
+        assert_eq!(tetris.preview(), vec![Piece::I]);
+        tetris.hard_drop();
+        assert!(tetris.completed());
+
+        // Two pieces that don't fit the well run the queue out.
+        let puzzle: Puzzle = "queue IO\n####..####\n####..####\n".parse().unwrap();
+        let mut tetris = puzzle.start(GameConfig::new());
+        tetris.hard_drop();
+        tetris.hard_drop();
+        assert_eq!(tetris.top_out(), Some(TopOut::Failed));
+    }
+}
@@ -0,0 +1,155 @@
+use super::game_state::{Piece, StateHasher};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+/// A small, fast, seedable PRNG (SplitMix64). Good enough for shuffling
+/// pieces, and fully deterministic across platforms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Decides which piece comes next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Randomizer {
+    /// Deal the pieces in a fixed O, L, J, T, Z, S, I cycle.
+    Cycle { next: Piece },
+    /// Deal all seven pieces in a shuffled order, then reshuffle.
+    Bag { rng: Rng, bag: Vec<Piece> },
+    /// Deal the pieces another game was dealt, in the same order, then
+    /// carry on with the fixed cycle.
+    Recorded { pieces: Vec<Piece>, dealt: usize },
+}
+
+impl Randomizer {
+    pub fn cycle() -> Self {
+        Randomizer::Cycle { next: Piece::O }
+    }
+
+    pub fn bag(seed: u64) -> Self {
+        Randomizer::Bag {
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(7),
+        }
+    }
+
+    pub fn recorded(pieces: Vec<Piece>) -> Self {
+        Randomizer::Recorded { pieces, dealt: 0 }
+    }
+
+    /// Deal the next piece.
+    pub fn deal(&mut self) -> Piece {
+        match self {
+            Randomizer::Cycle { next } => {
+                let piece = *next;
+                *next = ((piece as i32 + 1) % 7).try_into().unwrap();
+                piece
+            }
+            Randomizer::Bag { rng, bag } => {
+                if bag.is_empty() {
+                    bag.extend((0..7).map(|p| -> Piece { p.try_into().unwrap() }));
+                    // Fisher-Yates; pieces are dealt from the back.
+                    for i in (1..bag.len()).rev() {
+                        bag.swap(i, rng.below(i as u64 + 1) as usize);
+                    }
+                }
+                bag.pop().unwrap()
+            }
+            Randomizer::Recorded { pieces, dealt } => {
+                let piece = match pieces.get(*dealt) {
+                    Some(piece) => *piece,
+                    None => (((*dealt - pieces.len()) % 7) as i32).try_into().unwrap(),
+                };
+                *dealt += 1;
+                piece
+            }
+        }
+    }
+
+    /// Feed the randomizer's state to `Tetris::state_hash`.
+    pub(crate) fn hash_into(&self, hasher: &mut StateHasher) {
+        match self {
+            Randomizer::Cycle { next } => hasher.write(&[0, *next as u8]),
+            Randomizer::Bag { rng, bag } => {
+                hasher.write(&[1]);
+                hasher.write(&rng.0.to_le_bytes());
+                hasher.write(&[bag.len() as u8]);
+                for piece in bag {
+                    hasher.write(&[*piece as u8]);
+                }
+            }
+            Randomizer::Recorded { pieces, dealt } => {
+                hasher.write(&[2]);
+                hasher.write(&(*dealt as u64).to_le_bytes());
+                hasher.write(&(pieces.len() as u64).to_le_bytes());
+                for piece in pieces {
+                    hasher.write(&[*piece as u8]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycle() {
+        let mut r = Randomizer::cycle();
+        let dealt: Vec<Piece> = (0..8).map(|_| r.deal()).collect();
+        use Piece::*;
+        assert_eq!(dealt, vec![O, L, J, T, Z, S, I, O]);
+    }
+
+    #[test]
+    fn test_recorded() {
+        use Piece::*;
+        let mut r = Randomizer::recorded(vec![T, T, S]);
+        let dealt: Vec<Piece> = (0..5).map(|_| r.deal()).collect();
+        assert_eq!(dealt, vec![T, T, S, O, L]);
+    }
+
+    #[test]
+    fn test_bag_deals_each_piece_once_per_bag() {
+        let mut r = Randomizer::bag(42);
+        for _ in 0..10 {
+            let mut dealt: Vec<Piece> = (0..7).map(|_| r.deal()).collect();
+            dealt.sort();
+            use Piece::*;
+            assert_eq!(dealt, vec![O, L, J, T, Z, S, I]);
+        }
+    }
+
+    #[test]
+    fn test_bag_is_deterministic() {
+        let deal = |seed| {
+            let mut r = Randomizer::bag(seed);
+            (0..21).map(|_| r.deal()).collect::<Vec<Piece>>()
+        };
+        assert_eq!(deal(7), deal(7));
+        assert_ne!(deal(7), deal(8));
+    }
+}
@@ -0,0 +1,279 @@
+use super::game_state::{Action, Piece, Step, Tetris};
+use super::randomizer::Randomizer;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// A snapshot is kept every this many steps, so that seeking never
+/// replays more than this many.
+const KEYFRAME_INTERVAL: usize = 64;
+
+/// A game's recorded inputs, with periodic snapshots of the game so
+/// that any point of it can be reconstructed quickly. Alongside them it
+/// keeps a trail of every piece dealt and every garbage row, so that the
+/// game can also be played back without its randomizer. Garbage needs no
+/// trail of its own, as `Step::Garbage` records each row's hole.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// Every input, and when it happened
+    steps: Vec<(Step, Duration)>,
+    /// Every piece drawn from the randomizer after the start, in order
+    drawn: Vec<Piece>,
+    /// How many pieces had been drawn before each step
+    drawn_before: Vec<usize>,
+    /// The game after `i * KEYFRAME_INTERVAL` steps, for each `i`
+    keyframes: Vec<Tetris>,
+    /// The game after all steps so far
+    last: Tetris,
+}
+
+impl Replay {
+    /// Start a replay of `start`, which should be recording its inputs
+    /// from here on.
+    pub fn new(start: &Tetris) -> Self {
+        let mut start = start.clone();
+        start.record_inputs(false);
+        start.take_inputs();
+        start.take_events();
+        Self {
+            steps: Vec::new(),
+            drawn: Vec::new(),
+            drawn_before: Vec::new(),
+            keyframes: vec![start.clone()],
+            last: start,
+        }
+    }
+
+    /// Add inputs collected with `Tetris::take_inputs`, which happened
+    /// `at` into the game.
+    pub fn extend(&mut self, steps: Vec<Step>, at: Duration) {
+        for step in steps {
+            self.push(step, at);
+        }
+    }
+
+    fn push(&mut self, step: Step, at: Duration) {
+        let draws = self.last.draws();
+        self.drawn_before.push(self.drawn.len());
+        self.last.apply(step);
+        self.last.take_events();
+        if step == Step::Event(Action::Restart) {
+            // A new falling piece and next piece, even if the old game
+            // had drawn no more than that.
+            self.drawn
+                .extend_from_slice(&[self.last.piece, self.last.next]);
+        } else if self.last.draws() > draws || matches!(step, Step::Reseed(_)) {
+            // A reseed deals the next piece again without spawning the
+            // one it replaces.
+            self.drawn.push(self.last.next);
+        }
+        self.steps.push((step, at));
+        if self.steps.len().is_multiple_of(KEYFRAME_INTERVAL) {
+            self.keyframes.push(self.last.clone());
+        }
+    }
+
+    /// The number of recorded steps.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Every recorded step, in order.
+    pub fn steps(&self) -> impl Iterator<Item = Step> + '_ {
+        self.steps.iter().map(|(step, _)| *step)
+    }
+
+    /// When the step bringing the game to `position` happened.
+    pub fn time(&self, position: usize) -> Duration {
+        match position.min(self.len()) {
+            0 => Duration::ZERO,
+            position => self.steps[position - 1].1,
+        }
+    }
+
+    /// How many steps had happened by `time`.
+    pub fn position_at(&self, time: Duration) -> usize {
+        self.steps.partition_point(|(_, at)| *at <= time)
+    }
+
+    /// The game after its first `position` steps.
+    pub fn state(&self, position: usize) -> Tetris {
+        let position = position.min(self.len());
+        let keyframe = position / KEYFRAME_INTERVAL;
+        let mut tetris = self.keyframes[keyframe].clone();
+        for (step, _) in &self.steps[keyframe * KEYFRAME_INTERVAL..position] {
+            tetris.apply(*step);
+        }
+        tetris
+    }
+
+    /// Every piece the game was dealt after the start, followed by the
+    /// ones its preview showed last: enough to deal the whole game again.
+    pub fn pieces(&self) -> Vec<Piece> {
+        let mut pieces = self.drawn.clone();
+        pieces.extend(self.last.preview().into_iter().skip(1));
+        pieces
+    }
+
+    /// The hole column of every garbage row that rose, in order.
+    pub fn garbage(&self) -> impl Iterator<Item = usize> + '_ {
+        self.steps().filter_map(|step| match step {
+            Step::Garbage(hole) => Some(hole),
+            _ => None,
+        })
+    }
+
+    /// The game after its first `position` steps, played from the start
+    /// on the recorded pieces rather than the game's randomizer. It comes
+    /// out the same as `state` even if the randomizer has changed since
+    /// the game was recorded, except for the preview shortly before a
+    /// restart, which showed pieces that were never dealt.
+    pub fn state_from_record(&self, position: usize) -> Tetris {
+        let mut tetris = self.keyframes[0].clone();
+        tetris.randomizer = Randomizer::recorded(self.pieces());
+        for (step, _) in &self.steps[..position.min(self.len())] {
+            tetris.apply(*step);
+        }
+        tetris
+    }
+
+    /// Forget every step after the first `position`, as when a practice
+    /// game is rewound.
+    pub fn truncate(&mut self, position: usize) {
+        if position >= self.len() {
+            return;
+        }
+        self.last = self.state(position);
+        self.drawn.truncate(self.drawn_before[position]);
+        self.drawn_before.truncate(position);
+        self.steps.truncate(position);
+        self.keyframes.truncate(position / KEYFRAME_INTERVAL + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::game_state::GameView;
+
+    #[test]
+    fn test_seek() {
+        let mut t = GameConfig::new().seed(3).build();
+        t.record_inputs(true);
+        let mut replay = Replay::new(&t);
+        let mut hashes = vec![t.state_hash()];
+        let events = [
+            Action::MoveLeft,
+            Action::RotateCw,
+            Action::MoveRight,
+            Action::RotateCcw,
+        ];
+        for i in 0..300 {
+            if i % 2 == 0 {
+                t.event(events[i as usize / 2 % events.len()]);
+            } else {
+                t.tick();
+            }
+            // Ticks after the game is over aren't logged.
+            let inputs = t.take_inputs();
+            if !inputs.is_empty() {
+                hashes.push(t.state_hash());
+            }
+            replay.extend(inputs, Duration::from_millis(replay.len() as u64));
+        }
+        assert!(replay.len() > 200);
+        for position in [0, 1, 63, 64, 65, 200, replay.len()] {
+            assert_eq!(replay.state(position).state_hash(), hashes[position]);
+        }
+        assert_eq!(replay.time(65), Duration::from_millis(64));
+        assert_eq!(replay.position_at(Duration::from_millis(64)), 65);
+
+        // Steps recorded after a truncation follow on from it.
+        replay.truncate(100);
+        let mut t = replay.state(100);
+        t.record_inputs(true);
+        t.event(Action::MoveLeft);
+        t.tick();
+        let inputs = t.take_inputs();
+        let added = inputs.len();
+        replay.extend(inputs, Duration::from_millis(500));
+        assert_eq!(replay.len(), 100 + added);
+        // Past the next keyframe, which is built from the truncated game.
+        for _ in 0..40 {
+            t.tick();
+            replay.extend(t.take_inputs(), Duration::from_millis(600));
+        }
+        assert!(replay.len() > 2 * KEYFRAME_INTERVAL);
+        assert_eq!(replay.state(replay.len()).state_hash(), t.state_hash());
+    }
+
+    #[test]
+    fn test_record_trail() {
+        use Action::*;
+        let mut t = GameConfig::new().seed(5).preview(3).build();
+        t.record_inputs(true);
+        let mut replay = Replay::new(&t);
+        // The game after each round, and how many steps it had taken.
+        let mut views = vec![(0, t.view())];
+        let actions = [Hold, MoveLeft, HardDrop, Hold, RotateCw, HardDrop, Restart];
+        for i in 0..120 {
+            t.event(actions[i % actions.len()]);
+            if i % 5 == 0 {
+                t.insert_garbage(&[i % 10]);
+            }
+            t.tick();
+            replay.extend(t.take_inputs(), Duration::ZERO);
+            views.push((replay.len(), t.view()));
+        }
+        assert_eq!(replay.garbage().count(), 24);
+
+        // A different randomizer at the start makes no difference.
+        replay.keyframes[0].randomizer = Randomizer::cycle();
+        // Only the next piece, as the pieces previewed before a restart
+        // are never dealt.
+        let next_only = |mut view: GameView| {
+            view.queue.truncate(1);
+            view
+        };
+        for (position, view) in views.iter() {
+            assert_eq!(
+                next_only(replay.state_from_record(*position).view()),
+                next_only(view.clone())
+            );
+        }
+        let (position, view) = views.last().unwrap();
+        assert_eq!(replay.state_from_record(*position).view(), *view);
+
+        let (position, view) = &views[40];
+        replay.truncate(*position);
+        assert_eq!(
+            next_only(replay.state_from_record(*position).view()),
+            next_only(view.clone())
+        );
+        assert!(replay.garbage().count() < 24);
+    }
+
+    #[test]
+    fn test_restart_before_lock() {
+        let mut t = GameConfig::new().seed(8).build();
+        t.record_inputs(true);
+        let mut replay = Replay::new(&t);
+        // Nothing has been drawn since the start when the game restarts.
+        for action in [
+            Action::Restart,
+            Action::HardDrop,
+            Action::HardDrop,
+            Action::HardDrop,
+        ] {
+            t.event(action);
+            replay.extend(t.take_inputs(), Duration::ZERO);
+        }
+        replay.keyframes[0].randomizer = Randomizer::cycle();
+        assert_eq!(replay.state_from_record(replay.len()).view(), t.view());
+    }
+}
@@ -0,0 +1,123 @@
+use super::game_state::Tetris;
+#[cfg(feature = "std")]
+use super::profile;
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+/// How far back a practice game can rewind unless `--practice=<seconds>`
+/// says otherwise.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+
+/// The longest window `--practice=<seconds>` accepts.
+pub const MAX_WINDOW: Duration = Duration::from_secs(60);
+
+/// The last few seconds of a practice game, as a snapshot per gravity
+/// tick, so that a misdrop can be taken back. Once the window is full
+/// the oldest snapshot makes way for the newest, which keeps memory
+/// bounded however long the game goes on.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    /// The game before each tick, oldest first, with the length of its
+    /// replay at the time
+    snapshots: VecDeque<(Tetris, usize)>,
+    capacity: usize,
+    gravity: Duration,
+}
+
+impl Rewind {
+    /// Keep enough snapshots to go `window` back, up to `MAX_WINDOW`, in
+    /// a game ticking every `gravity`. Snapshots are only allocated as
+    /// the game goes on.
+    pub fn new(window: Duration, gravity: Duration) -> Self {
+        let window = window.min(MAX_WINDOW);
+        let capacity = (window.as_nanos() / gravity.as_nanos().max(1)).max(1) as usize;
+        Self {
+            snapshots: VecDeque::new(),
+            capacity,
+            gravity,
+        }
+    }
+
+    /// Practice mode, from `--practice` or `--practice=<seconds>`, if
+    /// given.
+    #[cfg(feature = "std")]
+    pub fn from_args(gravity: Duration) -> Result<Option<Self>, String> {
+        for arg in profile::args() {
+            if arg == "--practice" {
+                return Ok(Some(Self::new(DEFAULT_WINDOW, gravity)));
+            }
+            if let Some(seconds) = arg.strip_prefix("--practice=") {
+                return seconds
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .filter(|window| !window.is_zero() && *window <= MAX_WINDOW)
+                    .map(|window| Some(Self::new(window, gravity)))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid rewind window {:?}, expected up to {} seconds",
+                            seconds,
+                            MAX_WINDOW.as_secs()
+                        )
+                    });
+            }
+        }
+        Ok(None)
+    }
+
+    /// Remember `tetris` as it is before a tick, along with how many
+    /// steps its replay has.
+    pub fn record(&mut self, tetris: &Tetris, replay_len: usize) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((tetris.clone(), replay_len));
+    }
+
+    /// Go back one tick: the latest snapshot and its replay length,
+    /// which are then forgotten.
+    pub fn back(&mut self) -> Option<(Tetris, usize)> {
+        self.snapshots.pop_back()
+    }
+
+    /// How much game time can still be rewound.
+    pub fn available(&self) -> Duration {
+        self.gravity * self.snapshots.len() as u32
+    }
+
+    /// Forget every snapshot, for a new game.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+
+    #[test]
+    fn test_rewind() {
+        let gravity = Duration::from_millis(100);
+        let mut rewind = Rewind::new(Duration::from_millis(350), gravity);
+        let mut tetris = GameConfig::new().seed(1).build();
+        let mut hashes = Vec::new();
+        for tick in 0..10 {
+            rewind.record(&tetris, tick);
+            hashes.push(tetris.state_hash());
+            tetris.tick();
+        }
+        // Only the last three ticks are kept.
+        assert_eq!(rewind.available(), 3 * gravity);
+        for tick in (7..10).rev() {
+            let (snapshot, replay_len) = rewind.back().unwrap();
+            assert_eq!(snapshot.state_hash(), hashes[tick]);
+            assert_eq!(replay_len, tick);
+        }
+        assert!(rewind.back().is_none());
+
+        let longest = Rewind::new(Duration::from_secs(u64::MAX), Duration::from_nanos(1));
+        assert_eq!(longest.capacity, MAX_WINDOW.as_nanos() as usize);
+        assert_eq!(longest.snapshots.capacity(), 0);
+    }
+}
@@ -0,0 +1,227 @@
+use super::game_state::Piece;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Where and how a new piece enters the board.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Spawn {
+    /// Index into the piece's rotation table.
+    pub rotation: i32,
+    /// Anchor of the piece's 4x4 box.
+    pub row: i32,
+    pub col: i32,
+}
+
+/// The rule set deciding how pieces spawn.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum RotationSystem {
+    /// Guideline spawns: every piece lies flat side down, centered in
+    /// columns 3-6, with its box at the top of the board so that it can
+    /// turn right away. Pieces whose box has an empty top row in that
+    /// rotation spawn a row down.
+    #[default]
+    Guideline,
+    /// The original tetris-rs spawns: every piece in its first
+    /// rotation with the box anchored at `(0, 4)`.
+    Classic,
+}
+
+/// Names of the rotation systems, as accepted by
+/// `RotationSystem::from_name`.
+pub const ROTATION_SYSTEMS: [&str; 2] = ["guideline", "classic"];
+
+impl RotationSystem {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "guideline" => Some(RotationSystem::Guideline),
+            "classic" => Some(RotationSystem::Classic),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationSystem::Guideline => "guideline",
+            RotationSystem::Classic => "classic",
+        }
+    }
+
+    /// The spawn rotation and position of a piece.
+    pub fn spawn(self, piece: Piece) -> Spawn {
+        let (rotation, row, col) = match self {
+            RotationSystem::Classic => (0, 0, 4),
+            RotationSystem::Guideline => match piece {
+                Piece::O => (0, 0, 4),
+                Piece::L => (3, 0, 3),
+                Piece::J => (1, 0, 3),
+                Piece::T => (2, 0, 3),
+                Piece::Z | Piece::S => (0, 0, 3),
+                Piece::I => (1, 0, 3),
+            },
+        };
+        Spawn { rotation, row, col }
+    }
+}
+
+/// The most offsets a kick table may try per rotation.
+const MAX_KICKS: usize = 8;
+/// How far a kick may move a piece in any direction.
+const MAX_KICK_DISTANCE: i32 = 3;
+
+/// A kick table in the spirit of the guideline games, without their
+/// per-orientation detail: one cell either way, then up, and two cells
+/// either way for the I piece.
+const BASIC_KICKS: &str = "
+* cw  = -1,0 1,0 0,1 -1,1 1,1
+* ccw = 1,0 -1,0 0,1 1,1 -1,1
+I cw  = -1,0 1,0 -2,0 2,0 0,1
+I ccw = 1,0 -1,0 2,0 -2,0 0,1
+";
+
+/// Wall kicks: when a piece can't rotate in place, these offsets are
+/// tried in order and the piece moves to the first one where it fits.
+/// There are none by default.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Kicks {
+    tables: Vec<KickTable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KickTable {
+    /// `None` for every piece without a table of its own
+    piece: Option<Piece>,
+    clockwise: bool,
+    /// `(col, row)` offsets, in the order they are tried
+    offsets: Vec<(i32, i32)>,
+}
+
+impl Kicks {
+    /// Kicks off walls, the floor and the stack, as the guideline
+    /// presets use.
+    pub fn basic() -> Self {
+        Self::parse(BASIC_KICKS).expect("the basic kick table is valid")
+    }
+
+    /// Read a kick table from a file, see `parse`.
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Parse lines like `I cw = -2,0 1,0`: a piece letter or `*` for any
+    /// piece, `cw` or `ccw`, then the offsets to try as `right,up`, the
+    /// way kick tables are usually written. Blank lines and `#` comments
+    /// are skipped.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut kicks = Self::default();
+        let lines = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let (key, offsets) = line.split_once('=').ok_or_else(|| {
+                format!("expected `<piece> <cw|ccw> = <offsets>`, got {:?}", line)
+            })?;
+            let mut key = key.split_whitespace();
+            let piece = match key.next() {
+                Some("*") => None,
+                Some(name) => {
+                    Some(piece_from_name(name).ok_or_else(|| format!("unknown piece {:?}", name))?)
+                }
+                None => return Err(format!("missing piece in {:?}", line)),
+            };
+            let clockwise = match key.next() {
+                Some("cw") => true,
+                Some("ccw") => false,
+                other => return Err(format!("expected cw or ccw, got {:?}", other)),
+            };
+            if let Some(extra) = key.next() {
+                return Err(format!("unexpected {:?} before `=` in {:?}", extra, line));
+            }
+            if kicks
+                .tables
+                .iter()
+                .any(|t| t.piece == piece && t.clockwise == clockwise)
+            {
+                return Err(format!("duplicate entry {:?}", line));
+            }
+            let offsets = offsets
+                .split_whitespace()
+                .map(|offset| {
+                    let (right, up) = offset
+                        .split_once(',')
+                        .and_then(|(x, y)| Some((x.parse::<i32>().ok()?, y.parse::<i32>().ok()?)))
+                        .ok_or_else(|| format!("invalid offset {:?}, expected right,up", offset))?;
+                    if right.abs() > MAX_KICK_DISTANCE || up.abs() > MAX_KICK_DISTANCE {
+                        return Err(format!(
+                            "offset {:?} moves further than {} cells",
+                            offset, MAX_KICK_DISTANCE
+                        ));
+                    }
+                    Ok((right, -up))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            if offsets.len() > MAX_KICKS {
+                return Err(format!("more than {} offsets in {:?}", MAX_KICKS, line));
+            }
+            kicks.tables.push(KickTable {
+                piece,
+                clockwise,
+                offsets,
+            });
+        }
+        Ok(kicks)
+    }
+
+    /// The `(col, row)` offsets to try when turning `piece`.
+    pub fn offsets(&self, piece: Piece, clockwise: bool) -> &[(i32, i32)] {
+        let table = |want: Option<Piece>| {
+            self.tables
+                .iter()
+                .find(|t| t.piece == want && t.clockwise == clockwise)
+        };
+        table(Some(piece))
+            .or_else(|| table(None))
+            .map_or(&[], |t| t.offsets.as_slice())
+    }
+}
+
+/// The piece with the given letter, e.g. `T`.
+pub(crate) fn piece_from_name(name: &str) -> Option<Piece> {
+    match name {
+        "O" => Some(Piece::O),
+        "L" => Some(Piece::L),
+        "J" => Some(Piece::J),
+        "T" => Some(Piece::T),
+        "Z" => Some(Piece::Z),
+        "S" => Some(Piece::S),
+        "I" => Some(Piece::I),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kick_table() {
+        let kicks =
+            Kicks::parse("# symmetric I kicks\n* cw = -1,0 1,0\nI cw = -2,0 2,0 0,1\n").unwrap();
+        assert_eq!(kicks.offsets(Piece::T, true), &[(-1, 0), (1, 0)]);
+        assert_eq!(kicks.offsets(Piece::I, true), &[(-2, 0), (2, 0), (0, -1)]);
+        assert!(kicks.offsets(Piece::T, false).is_empty());
+
+        assert!(Kicks::parse("X cw = 1,0").is_err());
+        assert!(Kicks::parse("T left = 1,0").is_err());
+        assert!(Kicks::parse("I cw junk = 1,0").is_err());
+        assert!(Kicks::parse("T cw = 5,0").is_err());
+        assert!(Kicks::parse("T cw = 1").is_err());
+        assert!(Kicks::parse("T cw = 1,0\nT cw = 2,0").is_err());
+
+        assert_eq!(Kicks::basic().offsets(Piece::I, false)[2], (2, 0));
+    }
+}
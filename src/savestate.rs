@@ -0,0 +1,74 @@
+//! Savestates for practice mode: whole games put aside in numbered
+//! slots, to go back to the same situation as often as it takes.
+
+use super::game_state::Tetris;
+use super::garbage::RisingFloor;
+
+/// How many slots there are; F5 to F8 save into them.
+pub const SLOTS: usize = 4;
+
+/// Everything about a practice game that loading a slot brings back.
+#[derive(Debug, Clone)]
+pub struct Savestate {
+    pub tetris: Tetris,
+    /// The rising floor's countdown, in that challenge
+    pub floor: Option<RisingFloor>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Savestates {
+    slots: [Option<Savestate>; SLOTS],
+}
+
+impl Savestates {
+    /// Keep `state` in `slot`, replacing whatever was there. Inputs and
+    /// events the game hasn't handed out yet are left behind.
+    pub fn save(&mut self, slot: usize, mut state: Savestate) {
+        state.tetris.take_inputs();
+        state.tetris.take_events();
+        if let Some(saved) = self.slots.get_mut(slot) {
+            *saved = Some(state);
+        }
+    }
+
+    /// A copy of what `slot` holds, if anything; the slot keeps it for
+    /// next time.
+    pub fn load(&self, slot: usize) -> Option<Savestate> {
+        self.slots.get(slot).cloned().flatten()
+    }
+
+    pub fn is_filled(&self, slot: usize) -> bool {
+        self.slots.get(slot).is_some_and(Option::is_some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots() {
+        let mut savestates = Savestates::default();
+        let mut tetris = Tetris::new();
+        tetris.record_inputs(true);
+        tetris.tick();
+        let state = Savestate {
+            tetris: tetris.clone(),
+            floor: None,
+        };
+        savestates.save(1, state.clone());
+        savestates.save(SLOTS, state);
+        assert!(savestates.is_filled(1));
+        assert!(!savestates.is_filled(0));
+        assert!(!savestates.is_filled(SLOTS));
+
+        let hash = tetris.state_hash();
+        tetris.hard_drop();
+        let mut loaded = savestates.load(1).unwrap().tetris;
+        assert_eq!(loaded.state_hash(), hash);
+        assert!(loaded.take_inputs().is_empty());
+        // Loading again gives the same game.
+        assert_eq!(savestates.load(1).unwrap().tetris, loaded);
+        assert!(savestates.load(2).is_none());
+    }
+}
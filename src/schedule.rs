@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+/// The most missed ticks made up for at once. Beyond that the game was
+/// probably suspended rather than slow, and replaying the whole gap
+/// would drop pieces all at once.
+const MAX_CATCH_UP: u32 = 10;
+
+/// When gravity ticks are due. Ticks fall on fixed deadlines from the
+/// start rather than an interval after the previous one, so that time
+/// lost to a late wakeup isn't added to every tick after it, and ticks
+/// missed while the thread couldn't run are all made up.
+#[derive(Debug, Clone)]
+pub struct TickSchedule {
+    interval: Duration,
+    /// When the next tick is due
+    next: Instant,
+}
+
+impl TickSchedule {
+    /// A tick every `interval`, the first one `interval` after `now`.
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        Self {
+            interval,
+            next: now + interval,
+        }
+    }
+
+    /// When the next tick is due.
+    pub fn deadline(&self) -> Instant {
+        self.next
+    }
+
+    /// How many ticks are due by `now`, which are then done. Once more
+    /// than `MAX_CATCH_UP` are owed, the rest are skipped and the
+    /// schedule starts over from `now`.
+    pub fn due(&mut self, now: Instant) -> u32 {
+        if now < self.next {
+            return 0;
+        }
+        let late = now - self.next;
+        let missed = late.as_nanos() / self.interval.as_nanos().max(1);
+        if missed >= MAX_CATCH_UP as u128 {
+            self.next = now + self.interval;
+            return MAX_CATCH_UP;
+        }
+        self.next += self.interval * (missed as u32 + 1);
+        missed as u32 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut schedule = TickSchedule::new(ms(100), start);
+        assert_eq!(schedule.due(start + ms(99)), 0);
+        // Waking up late doesn't push back the ticks after.
+        assert_eq!(schedule.due(start + ms(130)), 1);
+        assert_eq!(schedule.deadline(), start + ms(200));
+        // A stall is caught up on.
+        assert_eq!(schedule.due(start + ms(450)), 3);
+        assert_eq!(schedule.deadline(), start + ms(500));
+        // A long stop isn't.
+        assert_eq!(schedule.due(start + ms(5000)), MAX_CATCH_UP);
+        assert_eq!(schedule.deadline(), start + ms(5100));
+    }
+}
@@ -0,0 +1,244 @@
+use super::config::Preset;
+use super::profile;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Lines to clear in a sprint.
+pub const SPRINT_LINES: u32 = 40;
+/// Lines between two split times.
+pub const SPLIT_LINES: u32 = 10;
+
+/// The times at which a sprint reached every `SPLIT_LINES` lines.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Splits {
+    times: Vec<Duration>,
+}
+
+impl Splits {
+    /// Note the line count at `elapsed`, taking a split for every
+    /// multiple of `SPLIT_LINES` it passed since the last call.
+    pub fn record(&mut self, lines: u32, elapsed: Duration) {
+        let reached = lines.min(SPRINT_LINES) / SPLIT_LINES;
+        while (self.times.len() as u32) < reached {
+            self.times.push(elapsed);
+        }
+    }
+
+    pub fn times(&self) -> &[Duration] {
+        &self.times
+    }
+
+    /// Roughly how many lines had been cleared at `elapsed`, assuming a
+    /// steady pace between splits.
+    pub fn lines_at(&self, elapsed: Duration) -> u32 {
+        let mut previous = (Duration::ZERO, 0);
+        for (split, time) in self.times.iter().enumerate() {
+            let lines = (split as u32 + 1) * SPLIT_LINES;
+            if elapsed < *time {
+                let (start, done) = previous;
+                let fraction = (elapsed - start).as_secs_f64() / (*time - start).as_secs_f64();
+                return done + (fraction * (lines - done) as f64) as u32;
+            }
+            previous = (*time, lines);
+        }
+        previous.1
+    }
+
+    /// The final time, once all of the sprint's lines are cleared.
+    pub fn finished(&self) -> Option<Duration> {
+        if self.times.len() as u32 == SPRINT_LINES / SPLIT_LINES {
+            self.times.last().copied()
+        } else {
+            None
+        }
+    }
+}
+
+/// The current player's fastest finished sprint, saved to a file per
+/// player and rule preset.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PersonalBest {
+    best: Option<Splits>,
+    /// Where to save, or `None` to keep it in memory.
+    path: Option<PathBuf>,
+}
+
+impl PersonalBest {
+    /// Load the current player's personal best under the rules of
+    /// `preset`, if they have one.
+    pub fn load(preset: Option<Preset>) -> Result<Self, String> {
+        let path = profile::data_path(&kind(preset))?;
+        let mut best = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("can't read {}: {}", path.display(), e)),
+        };
+        best.path = Some(path);
+        Ok(best)
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let mut splits = Splits::default();
+        for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("split", secs)) => {
+                    let time = secs
+                        .parse()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(|| format!("invalid split time {:?}", secs))?;
+                    splits.times.push(time);
+                }
+                _ => return Err(format!("unexpected line {:?}", line)),
+            }
+        }
+        if splits.times.is_empty() {
+            return Ok(Self::default());
+        }
+        if splits.finished().is_none() {
+            return Err(format!(
+                "expected {} split times, got {}",
+                SPRINT_LINES / SPLIT_LINES,
+                splits.times.len()
+            ));
+        }
+        Ok(Self {
+            best: Some(splits),
+            path: None,
+        })
+    }
+
+    /// Write the personal best back to its file, if it has one.
+    pub fn save(&self) -> Result<(), String> {
+        let (path, best) = match (&self.path, &self.best) {
+            (Some(path), Some(best)) => (path, best),
+            _ => return Ok(()),
+        };
+        let contents: String = best
+            .times
+            .iter()
+            .map(|time| format!("split = {:.3}\n", time.as_secs_f64()))
+            .collect();
+        path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, contents))
+            .map_err(|e| format!("can't save {}: {}", path.display(), e))
+    }
+
+    pub fn splits(&self) -> Option<&Splits> {
+        self.best.as_ref()
+    }
+
+    /// Keep `splits` if they finish the sprint faster than the personal
+    /// best. Returns whether they did.
+    pub fn submit(&mut self, splits: &Splits) -> bool {
+        let time = match splits.finished() {
+            Some(time) => time,
+            None => return false,
+        };
+        if self
+            .best
+            .as_ref()
+            .and_then(Splits::finished)
+            .is_some_and(|best| best <= time)
+        {
+            return false;
+        }
+        self.best = Some(splits.clone());
+        true
+    }
+}
+
+/// The name personal bests are kept under: `sprint` for the default
+/// rules, which were the only ones before presets, and `sprint-<preset>`
+/// for the others.
+fn kind(preset: Option<Preset>) -> String {
+    match preset {
+        Some(preset) => format!("sprint-{}", preset.name()),
+        None => String::from("sprint"),
+    }
+}
+
+/// A timer reading such as `1:05.20`.
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// How far `time` is ahead of (`-`) or behind (`+`) `reference`, such as
+/// `-0.52`.
+pub fn format_delta(time: Duration, reference: Duration) -> String {
+    if time < reference {
+        format!("-{:.2}", (reference - time).as_secs_f64())
+    } else {
+        format!("+{:.2}", (time - reference).as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splits_and_personal_best() {
+        let secs = Duration::from_secs;
+        let mut splits = Splits::default();
+        splits.record(8, secs(10));
+        // A tetris can pass a split and land well past it.
+        splits.record(12, secs(14));
+        splits.record(40, secs(50));
+        assert_eq!(splits.times(), &[secs(14), secs(50), secs(50), secs(50)]);
+        assert_eq!(splits.finished(), Some(secs(50)));
+
+        let mut pb = PersonalBest::default();
+        assert!(pb.submit(&splits));
+        assert!(!pb.submit(&splits));
+        assert!(!pb.submit(&Splits::default()));
+
+        let saved = "split = 14\nsplit = 50\nsplit = 50\nsplit = 50.000\n";
+        assert_eq!(PersonalBest::parse(saved), Ok(pb));
+        assert!(PersonalBest::parse("split = 14\n").is_err());
+        for time in ["1e30", "-1", "inf", "NaN"] {
+            let corrupt = PersonalBest::parse(&format!("split = {}\n", time));
+            assert_eq!(corrupt, Err(format!("invalid split time {:?}", time)));
+        }
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(kind(None), "sprint");
+        assert_eq!(kind(Some(Preset::Classic)), "sprint-classic");
+    }
+
+    #[test]
+    fn test_pace() {
+        let secs = Duration::from_secs;
+        let mut splits = Splits::default();
+        splits.record(10, secs(10));
+        splits.record(20, secs(30));
+        assert_eq!(splits.lines_at(secs(0)), 0);
+        assert_eq!(splits.lines_at(secs(5)), 5);
+        assert_eq!(splits.lines_at(secs(20)), 15);
+        assert_eq!(splits.lines_at(secs(60)), 20);
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_time(Duration::from_millis(65_204)), "1:05.20");
+        assert_eq!(
+            format_delta(Duration::from_millis(9_480), Duration::from_secs(10)),
+            "-0.52"
+        );
+        assert_eq!(
+            format_delta(Duration::from_secs(11), Duration::from_secs(10)),
+            "+1.00"
+        );
+    }
+}
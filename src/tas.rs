@@ -0,0 +1,224 @@
+//! Tool-assisted play: a game driven one gravity tick at a time, with
+//! its inputs kept per frame in a text file that can be edited and
+//! played back exactly.
+
+use super::game_state::{Action, Step, Tetris};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Every action, with its name in movie files.
+const ACTIONS: [(Action, &str); 10] = [
+    (Action::MoveLeft, "left"),
+    (Action::MoveRight, "right"),
+    (Action::RotateCcw, "ccw"),
+    (Action::RotateCw, "cw"),
+    (Action::Rotate180, "180"),
+    (Action::SoftDrop, "soft"),
+    (Action::HardDrop, "hard"),
+    (Action::Hold, "hold"),
+    (Action::Pause, "pause"),
+    (Action::Restart, "restart"),
+];
+
+/// The most frames a movie file can hold, over two days of play at the
+/// default gravity. Longer files are refused rather than filling memory.
+pub const MAX_FRAMES: usize = 1_000_000;
+
+/// The action with a name from movie files, which key macros use too.
+pub fn action_named(name: &str) -> Option<Action> {
+    ACTIONS
+        .iter()
+        .find(|(_, candidate)| *candidate == name)
+        .map(|(action, _)| *action)
+}
+
+/// A game's inputs frame by frame. A frame is the actions taken
+/// between two gravity ticks, and ends with the second one.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub frames: Vec<Vec<Action>>,
+}
+
+impl Movie {
+    /// The frames of recorded steps. Actions after the last tick make
+    /// no frame. Only moves are kept: garbage, speed changes and console
+    /// commands are left out, so games with any of them don't play back
+    /// the same.
+    pub fn from_steps(steps: impl IntoIterator<Item = Step>) -> Self {
+        let mut movie = Self::default();
+        let mut frame = Vec::new();
+        for step in steps {
+            match step {
+                Step::Tick => movie.frames.push(core::mem::take(&mut frame)),
+                Step::Event(action) => frame.push(action),
+                Step::Garbage(_)
+                | Step::Spawn(_)
+                | Step::ClearStack
+                | Step::Gravity(_)
+                | Step::SetLines(_)
+                | Step::SetPreview(_)
+                | Step::Reseed(_) => (),
+            }
+        }
+        movie
+    }
+
+    /// Play every frame into `tetris`, which should be the game the
+    /// movie was recorded from, set up the same way.
+    pub fn play(&self, tetris: &mut Tetris) {
+        for frame in &self.frames {
+            for action in frame {
+                tetris.event(*action);
+            }
+            tetris.tick();
+        }
+    }
+
+    /// Read a movie from a file, see `parse`.
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Write the movie to a file, in the format `parse` reads.
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|e| format!("can't save {}: {}", path, e))
+    }
+
+    /// Parse one frame per line: the names of its actions in order
+    /// (`left`, `right`, `ccw`, `cw`, `180`, `soft`, `hard`, `hold`,
+    /// `pause`, `restart`), or `.` for a frame without any. A trailing
+    /// `xN` repeats the line N times. Blank lines and `#` comments are
+    /// skipped. A movie can't go past `MAX_FRAMES`.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut movie = Self::default();
+        let lines = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty());
+        for line in lines {
+            let mut frame = Vec::new();
+            let mut repeat = 1;
+            for word in line.split_whitespace() {
+                if let Some(count) = word.strip_prefix('x') {
+                    repeat = count
+                        .parse()
+                        .ok()
+                        .filter(|count| *count <= MAX_FRAMES)
+                        .ok_or_else(|| format!("invalid repeat count {:?} in {:?}", word, line))?;
+                } else if word != "." {
+                    let action = action_named(word)
+                        .ok_or_else(|| format!("unknown action {:?} in {:?}", word, line))?;
+                    frame.push(action);
+                }
+            }
+            if movie.frames.len() + repeat > MAX_FRAMES {
+                return Err(format!("more than {} frames", MAX_FRAMES));
+            }
+            movie.frames.extend(core::iter::repeat_n(frame, repeat));
+        }
+        Ok(movie)
+    }
+}
+
+/// The `parse` format, with runs of the same frame folded into one
+/// line.
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("# tetris-rs movie: the actions of one gravity tick per line\n")?;
+        let mut frames = self.frames.iter().peekable();
+        while let Some(frame) = frames.next() {
+            let mut repeat = 1;
+            while frames.next_if_eq(&frame).is_some() {
+                repeat += 1;
+            }
+            if frame.is_empty() {
+                f.write_str(".")?;
+            }
+            for (i, action) in frame.iter().enumerate() {
+                let name = ACTIONS
+                    .iter()
+                    .find(|(a, _)| a == action)
+                    .expect("every action has a name")
+                    .1;
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                f.write_str(name)?;
+            }
+            if repeat > 1 {
+                write!(f, " x{}", repeat)?;
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GameConfig;
+    use crate::rotation::{Kicks, RotationSystem};
+
+    #[test]
+    fn test_parse() {
+        let movie = Movie::parse("# a T spin\n. x3\nleft cw # kick\n\nhard\n").unwrap();
+        assert_eq!(
+            movie.frames,
+            vec![
+                vec![],
+                vec![],
+                vec![],
+                vec![Action::MoveLeft, Action::RotateCw],
+                vec![Action::HardDrop],
+            ]
+        );
+        assert_eq!(Movie::parse(&movie.to_string()).unwrap(), movie);
+        assert!(movie.to_string().ends_with(". x3\nleft cw\nhard\n"));
+
+        assert!(Movie::parse("jump").is_err());
+        assert!(Movie::parse("left xx").is_err());
+        assert!(Movie::parse(". x18446744073709551615").is_err());
+        let longest = format!(". x{}", MAX_FRAMES);
+        assert_eq!(Movie::parse(&longest).unwrap().frames.len(), MAX_FRAMES);
+        assert!(Movie::parse(&format!("{}\nhard", longest)).is_err());
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let config = GameConfig::new()
+            .seed(5)
+            .rotation(RotationSystem::Guideline)
+            .kicks(Kicks::basic());
+        let mut tetris = config.clone().build();
+        let start = tetris.clone();
+        tetris.record_inputs(true);
+        let mut frames = 0;
+        while frames < 200 && !tetris.is_over() {
+            match frames % 7 {
+                0 => tetris.event(Action::RotateCw),
+                1 => tetris.event(Action::MoveLeft),
+                3 => tetris.event(Action::RotateCcw),
+                5 if frames % 3 == 0 => tetris.event(Action::HardDrop),
+                _ => (),
+            }
+            tetris.tick();
+            frames += 1;
+        }
+        // A last input with no tick after it isn't a frame yet.
+        let mut steps = tetris.take_inputs();
+        steps.push(Step::Event(Action::Hold));
+        let movie = Movie::from_steps(steps);
+        assert_eq!(movie.frames.len(), frames);
+
+        let mut played = start;
+        Movie::parse(&movie.to_string()).unwrap().play(&mut played);
+        assert_eq!(played.state_hash(), tetris.state_hash());
+    }
+}
@@ -0,0 +1,452 @@
+use super::game_state::{Cell, GameView, Piece, DANGER_ROWS, GARBAGE_COLOR, GUIDELINE_COLORS};
+use super::profile;
+use super::rotation::piece_from_name;
+use tui::style::Color;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A 24-bit color.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl From<(u8, u8, u8)> for Rgb {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Rgb(r, g, b)
+    }
+}
+
+/// How many colors the terminal can display.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+/// The standard 16 ANSI colors with their usual xterm values.
+const ANSI16: [(Color, Rgb); 16] = [
+    (Color::Black, Rgb(0, 0, 0)),
+    (Color::Red, Rgb(205, 0, 0)),
+    (Color::Green, Rgb(0, 205, 0)),
+    (Color::Yellow, Rgb(205, 205, 0)),
+    (Color::Blue, Rgb(0, 0, 238)),
+    (Color::Magenta, Rgb(205, 0, 205)),
+    (Color::Cyan, Rgb(0, 205, 205)),
+    (Color::Gray, Rgb(229, 229, 229)),
+    (Color::DarkGray, Rgb(127, 127, 127)),
+    (Color::LightRed, Rgb(255, 0, 0)),
+    (Color::LightGreen, Rgb(0, 255, 0)),
+    (Color::LightYellow, Rgb(255, 255, 0)),
+    (Color::LightBlue, Rgb(92, 92, 255)),
+    (Color::LightMagenta, Rgb(255, 0, 255)),
+    (Color::LightCyan, Rgb(0, 255, 255)),
+    (Color::White, Rgb(255, 255, 255)),
+];
+
+impl ColorDepth {
+    /// Guess the color support of the hosting terminal from
+    /// `$COLORTERM` and `$TERM`, honoring a `--colors=16|256|truecolor`
+    /// command line override. `--low-bandwidth` picks 16 colors, whose
+    /// escape sequences are the shortest, unless overridden.
+    pub fn detect() -> Self {
+        for arg in profile::args() {
+            match arg.as_str() {
+                "--colors=16" => return ColorDepth::Ansi16,
+                "--colors=256" => return ColorDepth::Ansi256,
+                "--colors=truecolor" => return ColorDepth::TrueColor,
+                _ => (),
+            }
+        }
+        if profile::args().any(|arg| arg == "--low-bandwidth") {
+            return ColorDepth::Ansi16;
+        }
+
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let colorterm = var("COLORTERM");
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if var("TERM").contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+
+    /// Map a 24-bit color to the closest color this depth can display.
+    pub fn color(self, rgb: Rgb) -> Color {
+        match self {
+            ColorDepth::TrueColor => Color::Rgb(rgb.0, rgb.1, rgb.2),
+            ColorDepth::Ansi256 => Color::Indexed(ansi256(rgb)),
+            ColorDepth::Ansi16 => {
+                ANSI16
+                    .iter()
+                    .min_by_key(|(_, candidate)| distance(rgb, *candidate))
+                    .expect("palette is non-empty")
+                    .0
+            }
+        }
+    }
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Index of the closest entry in the xterm 256 color palette, choosing
+/// between the 6x6x6 color cube and the 24 step grayscale ramp.
+fn ansi256(rgb: Rgb) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |v: u8| {
+        (0..6)
+            .min_by_key(|i| (LEVELS[*i] as i32 - v as i32).abs())
+            .unwrap()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = Rgb(LEVELS[r], LEVELS[g], LEVELS[b]);
+    let cube_index = 16 + 36 * r + 6 * g + b;
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * step;
+
+    if distance(rgb, Rgb(gray, gray, gray)) < distance(rgb, cube) {
+        232 + step
+    } else {
+        cube_index as u8
+    }
+}
+
+/// The visual appearance of the board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Symbol drawn for an occupied cell. Every symbol of a theme but
+    /// the half block spans as many columns as this one.
+    pub filled: String,
+    /// Symbols drawn instead of `filled` for each piece's cells, indexed
+    /// by `Piece as usize`.
+    pub piece_glyphs: [Option<String>; 7],
+    /// Symbol drawn instead of `filled` for garbage cells.
+    pub garbage_glyph: Option<String>,
+    /// Symbol drawn where the falling piece would land.
+    pub ghost: String,
+    /// Symbol used to pack two rows into one terminal cell in
+    /// half-height mode, if the glyph set has one.
+    pub half_block: Option<&'static str>,
+    /// Whether the glyph set has Braille patterns, for drawing pieces in
+    /// miniature.
+    pub braille: bool,
+    /// Color of each piece, indexed by `Piece as usize`.
+    pub pieces: [Rgb; 7],
+    /// Color of garbage cells.
+    pub garbage: Rgb,
+    /// Color of empty cells.
+    pub background: Rgb,
+    /// Color of empty cells in the top rows while the stack is close
+    /// to topping out.
+    pub danger: Rgb,
+    /// The color support colors are degraded to when rendering.
+    pub depth: ColorDepth,
+}
+
+impl Theme {
+    /// Glyphs for terminals that can display Unicode.
+    pub fn unicode() -> Self {
+        Self {
+            filled: String::from("\u{25a1}"),
+            piece_glyphs: Default::default(),
+            garbage_glyph: None,
+            ghost: String::from("\u{00b7}"),
+            half_block: Some("\u{2580}"),
+            braille: true,
+            pieces: GUIDELINE_COLORS.map(Rgb::from),
+            garbage: Rgb::from(GARBAGE_COLOR),
+            background: Rgb(0x10, 0x10, 0x10),
+            danger: Rgb(0x50, 0x00, 0x00),
+            depth: ColorDepth::TrueColor,
+        }
+    }
+
+    /// Plain ASCII glyphs that render everywhere.
+    pub fn ascii() -> Self {
+        Self {
+            filled: String::from("#"),
+            ghost: String::from("."),
+            half_block: None,
+            braille: false,
+            ..Self::unicode()
+        }
+    }
+
+    /// Pick a glyph set based on the locale, honoring the `--ascii` and
+    /// `--unicode` command line overrides and a `--skin=<file>`, and a
+    /// color depth based on the terminal. `--reduced-motion` leaves out
+    /// the danger tint.
+    pub fn detect() -> Result<Self, String> {
+        let has_arg = |flag: &str| profile::args().any(|arg| arg == flag);
+        let theme = if has_arg("--ascii") {
+            Self::ascii()
+        } else if has_arg("--unicode") || locale_is_utf8() {
+            Self::unicode()
+        } else {
+            Self::ascii()
+        };
+        let danger = if has_arg("--reduced-motion") {
+            theme.background
+        } else {
+            theme.danger
+        };
+        let theme = Self {
+            depth: ColorDepth::detect(),
+            danger,
+            ..theme
+        };
+        match profile::args().find_map(|arg| arg.strip_prefix("--skin=").map(String::from)) {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("can't read {}: {}", path, e))?;
+                theme
+                    .with_skin(&contents)
+                    .map_err(|e| format!("{}: {}", path, e))
+            }
+            None => Ok(theme),
+        }
+    }
+
+    /// The theme with the symbols of a skin file: `<name> = <symbol>`
+    /// lines, where the name is `filled`, `ghost`, `garbage` or a piece
+    /// letter, and the symbol may be put in double quotes to keep spaces
+    /// around it. Blank lines and `#` comments are skipped, and anything
+    /// the skin leaves out stays as it was.
+    pub fn with_skin(mut self, skin: &str) -> Result<Self, String> {
+        for (number, line) in skin.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let (name, glyph) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected <name> = <symbol>"))?;
+            let glyph = glyph.trim();
+            let glyph = glyph
+                .strip_prefix('"')
+                .and_then(|glyph| glyph.strip_suffix('"'))
+                .unwrap_or(glyph);
+            if glyph.is_empty() {
+                return Err(error("empty symbol"));
+            }
+            // Each character gets a terminal cell of its own, or two.
+            if glyph.chars().any(|c| c.width().unwrap_or(0) == 0) {
+                return Err(error("symbols can't have control or zero-width characters"));
+            }
+            let glyph = String::from(glyph);
+            match name.trim() {
+                "filled" => self.filled = glyph,
+                "ghost" => self.ghost = glyph,
+                "garbage" => self.garbage_glyph = Some(glyph),
+                name => match piece_from_name(name) {
+                    Some(piece) => self.piece_glyphs[piece as usize] = Some(glyph),
+                    None => return Err(error(&format!("unknown cell {:?}", name))),
+                },
+            }
+        }
+        let width = self.cell_width();
+        let glyphs = self
+            .piece_glyphs
+            .iter()
+            .chain([&self.garbage_glyph])
+            .flatten();
+        if glyphs
+            .chain([&self.ghost])
+            .any(|glyph| glyph.width() != width)
+        {
+            return Err(format!(
+                "every symbol should be as wide as `filled`, which takes {} columns",
+                width
+            ));
+        }
+        Ok(self)
+    }
+
+    /// How many columns a cell takes up on the board, as wide as its
+    /// symbols.
+    pub fn cell_width(&self) -> usize {
+        self.filled.width()
+    }
+
+    /// The symbol drawn for a filled cell.
+    pub fn glyph(&self, cell: Cell) -> &str {
+        let glyph = match cell {
+            Cell::Locked(piece) => self.piece_glyphs[piece as usize].as_ref(),
+            Cell::Garbage => self.garbage_glyph.as_ref(),
+            Cell::Empty => None,
+        };
+        glyph.unwrap_or(&self.filled)
+    }
+
+    /// The same theme at a fraction of the brightness, for the
+    /// screensaver. The stack never glows red either.
+    pub fn dimmed(self) -> Self {
+        let dim = |Rgb(r, g, b): Rgb| Rgb(r / 3, g / 3, b / 3);
+        Self {
+            pieces: self.pieces.map(dim),
+            garbage: dim(self.garbage),
+            background: dim(self.background),
+            danger: dim(self.background),
+            ..self
+        }
+    }
+
+    /// The same theme with the empty cells lit up, for the flash of a
+    /// blocked move.
+    pub fn bumped(self) -> Self {
+        let lift = |Rgb(r, g, b): Rgb| {
+            Rgb(
+                r.saturating_add(0x40),
+                g.saturating_add(0x40),
+                b.saturating_add(0x40),
+            )
+        };
+        Self {
+            background: lift(self.background),
+            danger: lift(self.danger),
+            ..self
+        }
+    }
+
+    /// The theme color of a grid cell.
+    pub fn cell_rgb(&self, cell: Cell) -> Rgb {
+        match cell {
+            Cell::Empty => self.background,
+            Cell::Locked(piece) => self.pieces[piece as usize],
+            Cell::Garbage => self.garbage,
+        }
+    }
+
+    /// The color of the empty cells in a row of a view, tinted as a
+    /// warning near the top when the stack is in danger.
+    pub fn background_rgb(&self, view: &GameView, row: usize) -> Rgb {
+        if row < DANGER_ROWS && view.in_danger() {
+            self.danger
+        } else {
+            self.background
+        }
+    }
+
+    /// The color of a piece's ghost: the piece color faded halfway into
+    /// the background.
+    pub fn ghost_rgb(&self, piece: Piece) -> Rgb {
+        let Rgb(r, g, b) = self.pieces[piece as usize];
+        let Rgb(br, bg, bb) = self.background;
+        let mix = |x: u8, y: u8| ((x as u16 + y as u16) / 2) as u8;
+        Rgb(mix(r, br), mix(g, bg), mix(b, bb))
+    }
+
+    /// The color of a cell in a view, for renderers that draw cells as
+    /// solid blocks.
+    pub fn view_rgb(&self, view: &GameView, row: usize, col: usize) -> Rgb {
+        if view.is_ghost(row, col) {
+            self.ghost_rgb(view.piece)
+        } else if view.cell(row, col).is_filled() {
+            self.cell_rgb(view.cell(row, col))
+        } else {
+            self.background_rgb(view, row)
+        }
+    }
+
+    /// Terminal color for a theme color at the theme's depth.
+    pub fn color(&self, rgb: Rgb) -> Color {
+        self.depth.color(rgb)
+    }
+}
+
+/// Checks whether the active locale uses UTF-8, following the usual
+/// `LC_ALL` > `LC_CTYPE` > `LANG` precedence.
+fn locale_is_utf8() -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimmed() {
+        let dimmed = Theme::unicode().dimmed();
+        assert_eq!(dimmed.pieces[Piece::I as usize], Rgb(0x00, 0x50, 0x50));
+        assert_eq!(dimmed.danger, dimmed.background);
+        assert_eq!(dimmed.filled, Theme::unicode().filled);
+
+        let bumped = Theme::unicode().bumped();
+        assert_ne!(bumped.background, Theme::unicode().background);
+        assert_eq!(bumped.pieces, Theme::unicode().pieces);
+    }
+
+    #[test]
+    fn test_skin() {
+        let skin = "# blocks\nfilled = \u{2588}\u{2588}\nghost = \"  \"\ngarbage=\u{2593}\u{2593}\nT = []\n";
+        let theme = Theme::unicode().with_skin(skin).unwrap();
+        assert_eq!(theme.cell_width(), 2);
+        assert_eq!(theme.glyph(Cell::Locked(Piece::T)), "[]");
+        assert_eq!(theme.glyph(Cell::Locked(Piece::I)), "\u{2588}\u{2588}");
+        assert_eq!(theme.glyph(Cell::Garbage), "\u{2593}\u{2593}");
+        assert_eq!(theme.ghost, "  ");
+
+        assert!(Theme::unicode().with_skin("T = []").is_err());
+        // Wide characters take two columns.
+        let theme = Theme::unicode()
+            .with_skin("filled = \u{25a0}\u{25a0}\nghost = \u{ff0b}\nT = \u{1f7ea}\nI = \u{1f7e6}")
+            .unwrap();
+        assert_eq!(theme.cell_width(), 2);
+        assert!(Theme::unicode()
+            .with_skin("filled = []\nT = \u{1f7ea}\u{1f7ea}")
+            .is_err());
+        assert!(Theme::unicode().with_skin("filled = e\u{301}").is_err());
+        assert!(Theme::unicode().with_skin("X = #").is_err());
+        assert!(Theme::unicode().with_skin("filled #").is_err());
+        assert_eq!(Theme::ascii().with_skin(""), Ok(Theme::ascii()));
+    }
+
+    #[test]
+    fn test_truecolor_passthrough() {
+        assert_eq!(
+            ColorDepth::TrueColor.color(Rgb(1, 2, 3)),
+            Color::Rgb(1, 2, 3)
+        );
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256(Rgb(0, 0, 0)), 16);
+        assert_eq!(ansi256(Rgb(255, 0, 0)), 196);
+        assert_eq!(ansi256(Rgb(255, 255, 255)), 231);
+        assert_eq!(ansi256(Rgb(0x80, 0x80, 0x80)), 244);
+    }
+
+    #[test]
+    fn test_ansi16() {
+        assert_eq!(ColorDepth::Ansi16.color(Rgb(250, 10, 10)), Color::LightRed);
+        assert_eq!(
+            ColorDepth::Ansi16.color(Rgb(0x10, 0x10, 0x10)),
+            Color::Black
+        );
+        assert_eq!(ColorDepth::Ansi16.color(Rgb(0xe0, 0xe0, 0xe0)), Color::Gray);
+    }
+
+    #[test]
+    fn test_danger_tint() {
+        let theme = Theme::unicode();
+        let mut view = crate::game_state::Tetris::new().view();
+        assert_eq!(theme.view_rgb(&view, 0, 0), theme.background);
+        view.stack_height = crate::game_state::NROWS;
+        assert_eq!(theme.view_rgb(&view, 0, 0), theme.danger);
+        assert_eq!(theme.view_rgb(&view, DANGER_ROWS, 0), theme.background);
+        assert_eq!(theme.view_rgb(&view, 0, 4), theme.pieces[Piece::O as usize]);
+    }
+}
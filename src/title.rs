@@ -0,0 +1,50 @@
+/// Save the current window title on the terminal's title stack.
+const PUSH: &str = "\x1b[22;0t";
+/// Restore the most recently saved window title.
+const POP: &str = "\x1b[23;0t";
+
+/// Wrap an escape sequence so that tmux forwards it to the outer
+/// terminal instead of interpreting it itself.
+fn passthrough(seq: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+}
+
+/// Emit a sequence both to tmux (if we run inside it) and to the
+/// terminal hosting it.
+fn emit(seq: &str) -> String {
+    if std::env::var_os("TMUX").is_some() {
+        format!("{}{}", seq, passthrough(seq))
+    } else {
+        seq.to_string()
+    }
+}
+
+/// Sequence that saves the title the terminal had before we started.
+pub fn save() -> String {
+    emit(PUSH)
+}
+
+/// Sequence that restores the title saved by `save`.
+pub fn restore() -> String {
+    emit(POP)
+}
+
+/// Sequence that sets the window title, stripping control characters
+/// that would terminate the escape sequence early.
+pub fn set(title: &str) -> String {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    emit(&format!("\x1b]2;{}\x07", title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passthrough() {
+        assert_eq!(
+            passthrough("\x1b]2;hi\x07"),
+            "\x1bPtmux;\x1b\x1b]2;hi\x07\x1b\\"
+        );
+    }
+}
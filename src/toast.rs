@@ -0,0 +1,106 @@
+use super::game_state::GameEvent;
+use std::time::{Duration, Instant};
+
+/// How long a toast stays on screen.
+const LIFETIME: Duration = Duration::from_secs(1);
+/// How many toasts stay up at once when they don't expire.
+const STEADY_COUNT: usize = 2;
+
+/// Short-lived messages floating over the board after notable events,
+/// such as "TETRIS" after clearing four rows.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    /// Visible messages and when each disappears, oldest first.
+    active: Vec<(String, Option<Instant>)>,
+    /// Whether messages stay up until newer ones push them out, rather
+    /// than blinking away after a second
+    steady: bool,
+}
+
+impl Toasts {
+    /// Toasts that don't expire, for `--reduced-motion`: the newest few
+    /// stay on screen until later ones replace them.
+    pub fn steady() -> Self {
+        Self {
+            active: Vec::new(),
+            steady: true,
+        }
+    }
+
+    /// Show a toast for an engine event, if it deserves one.
+    pub fn observe(&mut self, event: GameEvent, now: Instant) {
+        match event {
+            GameEvent::LinesCleared(lines) => self.push(clear_name(lines), now),
+            GameEvent::PerfectClear => self.push(String::from("PERFECT CLEAR"), now),
+            GameEvent::StackCleared => self.push(String::from("FRESH START"), now),
+            GameEvent::Locked(_) | GameEvent::Blocked(_) => (),
+        }
+    }
+
+    pub fn push(&mut self, text: String, now: Instant) {
+        if self.steady {
+            if self.active.len() == STEADY_COUNT {
+                self.active.remove(0);
+            }
+            self.active.push((text, None));
+        } else {
+            self.active.push((text, Some(now + LIFETIME)));
+        }
+    }
+
+    /// The messages still visible at `now`, dropping expired ones.
+    pub fn visible(&mut self, now: Instant) -> Vec<&str> {
+        self.active
+            .retain(|(_, until)| until.is_none_or(|until| until > now));
+        self.active.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    /// When the next toast disappears, if any are showing.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.active.iter().filter_map(|(_, until)| *until).min()
+    }
+}
+
+/// The conventional name of a line clear.
+fn clear_name(lines: u32) -> String {
+    match lines {
+        1 => String::from("SINGLE"),
+        2 => String::from("DOUBLE"),
+        3 => String::from("TRIPLE"),
+        4 => String::from("TETRIS"),
+        n => format!("{} LINES", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toasts_expire() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.observe(GameEvent::LinesCleared(4), start);
+        toasts.observe(GameEvent::LinesCleared(1), start + LIFETIME / 2);
+        assert_eq!(toasts.visible(start), vec!["TETRIS", "SINGLE"]);
+        assert_eq!(toasts.deadline(), Some(start + LIFETIME));
+        assert_eq!(toasts.visible(start + LIFETIME), vec!["SINGLE"]);
+        assert!(toasts.visible(start + 2 * LIFETIME).is_empty());
+        assert_eq!(toasts.deadline(), None);
+    }
+
+    #[test]
+    fn test_steady_toasts() {
+        let start = Instant::now();
+        let mut toasts = Toasts::steady();
+        toasts.observe(GameEvent::LinesCleared(4), start);
+        assert_eq!(toasts.deadline(), None);
+        assert_eq!(toasts.visible(start + 10 * LIFETIME), vec!["TETRIS"]);
+        toasts.observe(GameEvent::LinesCleared(1), start);
+        toasts.observe(GameEvent::PerfectClear, start);
+        assert_eq!(
+            toasts.visible(start + 10 * LIFETIME),
+            vec!["SINGLE", "PERFECT CLEAR"]
+        );
+    }
+}
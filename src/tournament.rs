@@ -0,0 +1,183 @@
+use super::profile;
+use std::cmp::Reverse;
+use std::time::Duration;
+
+const MIN_PLAYERS: usize = 3;
+const MAX_PLAYERS: usize = 8;
+
+/// How one player's sprint round went.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Run {
+    pub lines: u32,
+    /// Time taken to clear the sprint, or `None` if the player topped
+    /// out first.
+    pub finished: Option<Duration>,
+}
+
+impl Run {
+    /// Finishing beats not finishing, then the faster time wins, then
+    /// the most lines.
+    pub fn beats(&self, other: &Run) -> bool {
+        let key = |run: &Run| {
+            (
+                run.finished.is_some(),
+                Reverse(run.finished.unwrap_or_default()),
+                run.lines,
+            )
+        };
+        key(self) > key(other)
+    }
+}
+
+/// A single-elimination bracket for hot-seat play. Each round pairs off
+/// the remaining players in order; with an odd number left, the last one
+/// gets a bye into the next round.
+#[derive(Debug)]
+pub struct Bracket {
+    names: Vec<String>,
+    /// Players still to play in the current round, in bracket order
+    waiting: Vec<usize>,
+    /// Players through to the next round
+    advancing: Vec<usize>,
+    /// The round each player was knocked out in
+    eliminated: Vec<Option<u32>>,
+    round: u32,
+}
+
+impl Bracket {
+    pub fn new(names: Vec<String>) -> Result<Self, String> {
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&names.len()) {
+            return Err(format!(
+                "a tournament needs {} to {} players, got {}",
+                MIN_PLAYERS,
+                MAX_PLAYERS,
+                names.len()
+            ));
+        }
+        for (i, name) in names.iter().enumerate() {
+            if name.is_empty() {
+                return Err(String::from("player names can't be empty"));
+            }
+            if names[..i].contains(name) {
+                return Err(format!("player {:?} is entered twice", name));
+            }
+        }
+        Ok(Self {
+            waiting: (0..names.len()).collect(),
+            advancing: Vec::new(),
+            eliminated: vec![None; names.len()],
+            round: 1,
+            names,
+        })
+    }
+
+    /// Build a bracket from `--tournament=<name>,<name>,...`, if given.
+    pub fn from_args() -> Result<Option<Self>, String> {
+        match profile::args().find_map(|arg| arg.strip_prefix("--tournament=").map(String::from)) {
+            Some(names) => {
+                Self::new(names.split(',').map(|n| n.trim().to_string()).collect()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn name(&self, player: usize) -> &str {
+        &self.names[player]
+    }
+
+    /// The round the next match belongs to, counting from 1.
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    /// The two players who meet next, or `None` once only the champion
+    /// is left.
+    pub fn next_match(&mut self) -> Option<(usize, usize)> {
+        while self.waiting.len() < 2 {
+            // A player with a bye goes first in the next round, so that
+            // they don't get another one.
+            if let Some(bye) = self.waiting.pop() {
+                self.advancing.insert(0, bye);
+            }
+            if self.advancing.len() < 2 {
+                return None;
+            }
+            std::mem::swap(&mut self.waiting, &mut self.advancing);
+            self.round += 1;
+        }
+        Some((self.waiting[0], self.waiting[1]))
+    }
+
+    /// Record the result of the match returned by `next_match`.
+    pub fn record(&mut self, winner: usize) {
+        let (a, b) = self.next_match().expect("no match left to record");
+        assert!(winner == a || winner == b, "winner didn't play this match");
+        let loser = if winner == a { b } else { a };
+        self.waiting.drain(..2);
+        self.advancing.push(winner);
+        self.eliminated[loser] = Some(self.round);
+    }
+
+    /// Final places, best first. Players knocked out in the same round
+    /// share a place.
+    pub fn standings(&self) -> Vec<(usize, &str)> {
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        // The champion was never knocked out, so `None` sorts first.
+        order.sort_by_key(|&player| self.eliminated[player].map(Reverse));
+        order
+            .iter()
+            .map(|&player| {
+                let place = 1 + order
+                    .iter()
+                    .filter(|&&other| {
+                        self.eliminated[other].map(Reverse) < self.eliminated[player].map(Reverse)
+                    })
+                    .count();
+                (place, self.name(player))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_bracket() {
+        assert!(Bracket::new(names(&["a", "b"])).is_err());
+        assert!(Bracket::new(names(&["a", "b", "a"])).is_err());
+
+        let mut bracket = Bracket::new(names(&["a", "b", "c", "d", "e"])).unwrap();
+        assert_eq!(bracket.next_match(), Some((0, 1)));
+        bracket.record(1);
+        assert_eq!(bracket.next_match(), Some((2, 3)));
+        bracket.record(2);
+        // "e" had a bye through the first round.
+        assert_eq!(bracket.next_match(), Some((4, 1)));
+        assert_eq!(bracket.round(), 2);
+        bracket.record(4);
+        assert_eq!(bracket.next_match(), Some((2, 4)));
+        assert_eq!(bracket.round(), 3);
+        bracket.record(4);
+        assert_eq!(bracket.next_match(), None);
+        assert_eq!(
+            bracket.standings(),
+            vec![(1, "e"), (2, "c"), (3, "b"), (4, "a"), (4, "d")]
+        );
+    }
+
+    #[test]
+    fn test_run_order() {
+        let secs = Duration::from_secs;
+        let run = |lines, finished| Run { lines, finished };
+        assert!(run(40, Some(secs(50))).beats(&run(40, Some(secs(60)))));
+        assert!(run(40, Some(secs(90))).beats(&run(39, None)));
+        assert!(run(20, None).beats(&run(10, None)));
+        assert!(!run(20, None).beats(&run(20, None)));
+    }
+}
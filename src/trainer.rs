@@ -0,0 +1,122 @@
+//! The speed trainer: gravity that gets faster every so often however
+//! well the game is going, to practice playing at speeds not reached
+//! yet.
+
+#[cfg(feature = "std")]
+use super::profile;
+use core::time::Duration;
+
+/// How long each level lasts before the next, faster one.
+pub const LEVEL_TIME: Duration = Duration::from_secs(30);
+/// The fastest the trainer gets.
+pub const MIN_GRAVITY: Duration = Duration::from_millis(1);
+
+/// The levels of a trainer, each ticking a fifth faster than the last.
+/// Time is counted in gravity ticks, so it stops while the game does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeedTrainer {
+    /// Time between ticks at the first level
+    start: Duration,
+    /// Time between ticks at the current level
+    gravity: Duration,
+    level: u32,
+    /// Time spent at the current level
+    elapsed: Duration,
+    /// The fastest gravity played for a whole level
+    survived: Option<Duration>,
+}
+
+impl SpeedTrainer {
+    /// A trainer starting at the game's own `gravity`.
+    pub fn new(gravity: Duration) -> Self {
+        Self {
+            start: gravity,
+            gravity,
+            level: 0,
+            elapsed: Duration::ZERO,
+            survived: None,
+        }
+    }
+
+    /// The trainer, if `--trainer` is given.
+    #[cfg(feature = "std")]
+    pub fn from_args(gravity: Duration) -> Option<Self> {
+        profile::args()
+            .any(|arg| arg == "--trainer")
+            .then(|| Self::new(gravity))
+    }
+
+    /// Count a gravity tick. Returns true when it moves the game up to
+    /// the next level.
+    pub fn tick(&mut self) -> bool {
+        self.elapsed += self.gravity;
+        if self.elapsed < LEVEL_TIME {
+            return false;
+        }
+        self.survived = Some(self.gravity);
+        self.level += 1;
+        self.gravity = (self.gravity * 4 / 5).max(MIN_GRAVITY);
+        self.elapsed = Duration::ZERO;
+        true
+    }
+
+    /// The current level, from 0.
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Time between ticks at the current level.
+    pub fn gravity(&self) -> Duration {
+        self.gravity
+    }
+
+    /// How many times as often as at the start the current level ticks.
+    pub fn speed(&self) -> f64 {
+        self.start.as_secs_f64() / self.gravity.as_secs_f64()
+    }
+
+    /// Game time until the next level.
+    pub fn until_next(&self) -> Duration {
+        LEVEL_TIME.saturating_sub(self.elapsed)
+    }
+
+    /// The fastest gravity the player lasted a whole level at, if any.
+    pub fn survived(&self) -> Option<Duration> {
+        self.survived
+    }
+
+    /// Go back to the first level, for a new game.
+    pub fn restart(&mut self) {
+        *self = Self::new(self.start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let mut trainer = SpeedTrainer::new(Duration::from_millis(100));
+        for _ in 0..299 {
+            assert!(!trainer.tick());
+        }
+        assert_eq!(trainer.until_next(), Duration::from_millis(100));
+        assert_eq!(trainer.survived(), None);
+        assert!(trainer.tick());
+        assert_eq!(trainer.level(), 1);
+        assert_eq!(trainer.gravity(), Duration::from_millis(80));
+        assert_eq!(trainer.survived(), Some(Duration::from_millis(100)));
+        assert!((trainer.speed() - 1.25).abs() < 1e-9);
+
+        // Later levels last as long, in fewer ticks.
+        for _ in 0..374 {
+            assert!(!trainer.tick());
+        }
+        assert!(trainer.tick());
+        assert_eq!(trainer.gravity(), Duration::from_millis(64));
+
+        trainer.restart();
+        assert_eq!(trainer, SpeedTrainer::new(Duration::from_millis(100)));
+    }
+}
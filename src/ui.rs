@@ -1,39 +1,192 @@
-use super::game_state::{Grid, NCOLS, NROWS};
+use super::game_state::{piece_offsets, GameView, Piece, Tetris, NCOLS, NROWS};
+use super::theme::Theme;
 use tui::buffer::Buffer;
 use tui::layout::Rect;
-use tui::style::Color;
 use tui::widgets::Widget;
+use unicode_width::UnicodeWidthChar;
 
-/// A newtype wrapper around a grid for rendering as tui widget.
-pub struct GridWidget<'a>(pub &'a Grid);
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// A newtype wrapper around a game view for rendering as tui widget.
+/// Each cell takes up `Theme::cell_width` columns.
+pub struct GridWidget<'a>(pub &'a GameView, pub &'a Theme);
 
 impl<'a> Widget for GridWidget<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        if (area.width as usize) < NCOLS || (area.height as usize) < NROWS {
+        let width = self.1.cell_width();
+        if (area.width as usize) < NCOLS * width || (area.height as usize) < NROWS {
             panic!("Terminal UI area too small!");
         }
 
-        if (buf.area.width as usize) < NCOLS || (buf.area.height as usize) < NROWS {
-            panic!("Terminal UI buffer area too small!");
-        }
-
-        buf.reset();
-        let square = b"\xe2\x96\xa1";
-        let square_str = std::str::from_utf8(square).expect("square is invalid");
-
         for row in 0..NROWS {
+            let bg = self.1.color(self.1.background_rgb(self.0, row));
             for col in 0..NCOLS {
-                let idx = buf.index_of(col as u16, row as u16);
-                let cell_mut = &mut buf.content[idx];
-                if self.0[row][col] == 1 {
-                    cell_mut
-                        .set_symbol(square_str)
-                        .set_fg(Color::White)
-                        .set_bg(Color::Black);
+                let cell = self.0.cell(row, col);
+                let glyph = if cell.is_filled() {
+                    Some((self.1.glyph(cell), self.1.cell_rgb(cell)))
+                } else if self.0.is_ghost(row, col) {
+                    Some((self.1.ghost.as_str(), self.1.ghost_rgb(self.0.piece)))
                 } else {
-                    cell_mut.set_bg(Color::Black);
+                    None
+                };
+                // Each character of the symbol in its own column, or two
+                // for wide ones, so that the terminal knows where they
+                // end.
+                let x = area.x + (col * width) as u16;
+                let y = area.y + row as u16;
+                match glyph {
+                    Some((glyph, rgb)) => {
+                        let fg = self.1.color(rgb);
+                        let mut dx = 0;
+                        for c in glyph.chars() {
+                            buf.get_mut(x + dx, y)
+                                .set_symbol(c.encode_utf8(&mut [0; 4]))
+                                .set_fg(fg)
+                                .set_bg(bg);
+                            let columns = c.width().unwrap_or(1) as u16;
+                            // The column a wide character spills into
+                            // is left blank for it.
+                            for spill in dx + 1..dx + columns {
+                                buf.get_mut(x + spill, y).reset();
+                                buf.get_mut(x + spill, y).set_bg(bg);
+                            }
+                            dx += columns;
+                        }
+                    }
+                    None => {
+                        for dx in 0..width {
+                            buf.get_mut(x + dx as u16, y).set_bg(bg);
+                        }
+                    }
                 }
             }
         }
     }
 }
+
+/// Renders a grid at half height by packing two rows into each
+/// terminal cell with the upper half block character, so the game
+/// still fits in very small terminals. Requires a theme with a half
+/// block glyph.
+pub struct HalfBlockGridWidget<'a>(pub &'a GameView, pub &'a Theme);
+
+impl<'a> Widget for HalfBlockGridWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let height = NROWS.div_ceil(2);
+        if (area.width as usize) < NCOLS || (area.height as usize) < height {
+            panic!("Terminal UI area too small!");
+        }
+
+        let half_block = self.1.half_block.expect("theme has no half block glyph");
+
+        let color = |row: usize, col: usize| {
+            if row < NROWS {
+                self.1.color(self.1.view_rgb(self.0, row, col))
+            } else {
+                self.1.color(self.1.background)
+            }
+        };
+
+        for row in 0..height {
+            for col in 0..NCOLS {
+                let cell_mut = buf.get_mut(area.x + col as u16, area.y + row as u16);
+                cell_mut
+                    .set_symbol(half_block)
+                    .set_fg(color(2 * row, col))
+                    .set_bg(color(2 * row + 1, col));
+            }
+        }
+    }
+}
+
+/// Lines of internal engine state for the developer overlay. Gravity
+/// is `None` in frame-step mode.
+pub fn debug_lines(tetris: &Tetris, gravity: Option<Duration>) -> Vec<String> {
+    let (row, col) = tetris.anchor();
+    let gravity = match gravity {
+        Some(interval) => format!("{}ms", interval.as_millis()),
+        None => String::from("stepped (.)"),
+    };
+    vec![
+        format!("piece:    {:?}", tetris.piece()),
+        format!("rotation: {}", tetris.rotation()),
+        format!("anchor:   ({}, {})", row, col),
+        format!("gravity:  {}", gravity),
+        format!("next:     {:?}", tetris.preview()),
+        format!("seed:     {:?}", tetris.config().seed),
+        format!("lines:    {}", tetris.lines()),
+    ]
+}
+
+/// The longest bar of the piece statistics, in cells.
+const STATS_WIDTH: u32 = 10;
+
+/// A piece lying flat, as two Braille characters when the glyph set has
+/// them, or else as its letter.
+pub fn piece_glyph(piece: Piece, braille: bool) -> String {
+    if !braille {
+        return format!("{:?}", piece);
+    }
+    let flat = (0..4)
+        .map(|rotation| piece_offsets(piece, rotation))
+        .find(|cells| {
+            let rows = cells.iter().map(|&(row, _)| row);
+            rows.clone().max() <= rows.min().map(|top| top + 1)
+        })
+        .unwrap_or_else(|| piece_offsets(piece, 0));
+    let top = flat.iter().map(|&(row, _)| row).min().unwrap_or(0);
+    let left = flat.iter().map(|&(_, col)| col).min().unwrap_or(0);
+    // The dots of each Braille column, top to bottom.
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    let mut chars = [0x2800; 2];
+    for &(row, col) in &flat {
+        let col = (col - left) as usize;
+        // The middle two dot rows, so the piece sits centered.
+        chars[col / 2] |= DOTS[col % 2][(row - top) as usize + 1];
+    }
+    chars
+        .iter()
+        .filter_map(|&c| std::char::from_u32(c))
+        .collect()
+}
+
+/// A histogram of how many of each piece have been dealt, one line per
+/// piece, as on the statistics panel of the NES game.
+pub fn statistics_lines(counts: [u32; 7], theme: &Theme) -> Vec<String> {
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+    (0..7)
+        .filter_map(|index| Piece::try_from(index).ok())
+        .map(|piece| {
+            let count = counts[piece as usize];
+            let bar = (count * STATS_WIDTH).div_ceil(most);
+            format!(
+                "{} {:>3} {}",
+                piece_glyph(piece, theme.braille),
+                count,
+                theme.filled.repeat(bar as usize)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics() {
+        assert_eq!(piece_glyph(Piece::T, true), "\u{2832}\u{2802}");
+        assert_eq!(piece_glyph(Piece::I, true), "\u{2812}\u{2812}");
+        assert_eq!(piece_glyph(Piece::O, false), "O");
+
+        let mut counts = [0; 7];
+        counts[Piece::I as usize] = 4;
+        counts[Piece::O as usize] = 1;
+        let lines = statistics_lines(counts, &Theme::ascii());
+        assert_eq!(lines.len(), 7);
+        assert!(lines.contains(&format!("I   4 {}", "#".repeat(10))));
+        assert!(lines.contains(&String::from("O   1 ###")));
+        assert!(lines.contains(&String::from("T   0 ")));
+    }
+}
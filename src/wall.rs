@@ -0,0 +1,201 @@
+use super::ai::{Bot, Strength};
+use super::config::GameConfig;
+use super::game_state::Tetris;
+use super::profile;
+use std::time::{Duration, Instant};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+
+/// The most boards on either side of the wall.
+const MAX_SIDE: u16 = 8;
+
+/// How many boards across and down a wall has.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Size {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl Size {
+    /// Parse `<columns>x<rows>`, e.g. `3x3`.
+    pub fn parse(size: &str) -> Result<Self, String> {
+        let invalid = || format!("expected a wall size like 3x3, got {:?}", size);
+        let (columns, rows) = size.split_once('x').ok_or_else(invalid)?;
+        let side = |n: &str| {
+            n.parse()
+                .ok()
+                .filter(|n| (1..=MAX_SIDE).contains(n))
+                .ok_or_else(invalid)
+        };
+        Ok(Self {
+            columns: side(columns)?,
+            rows: side(rows)?,
+        })
+    }
+
+    /// The size given by `--wall` (3x3) or `--wall=<columns>x<rows>`, if
+    /// any.
+    pub fn from_args() -> Result<Option<Self>, String> {
+        for arg in profile::args() {
+            if arg == "--wall" {
+                return Ok(Some(Self {
+                    columns: 3,
+                    rows: 3,
+                }));
+            }
+            if let Some(size) = arg.strip_prefix("--wall=") {
+                return Self::parse(size).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A grid of games the bot plays side by side, all ticking together,
+/// as a showcase and a visual benchmark of the engine. A game that tops
+/// out starts over with the next seed.
+pub struct Wall {
+    size: Size,
+    config: GameConfig,
+    strength: Strength,
+    games: Vec<(Tetris, Bot)>,
+    /// Seed of the next game to start
+    next_seed: u64,
+    /// Pieces locked in games that have since started over
+    retired_pieces: u64,
+    started: Instant,
+    ticks: u64,
+    /// Time spent ticking the games
+    busy: Duration,
+}
+
+impl Wall {
+    pub fn new(size: Size, config: &GameConfig, strength: Strength, seed: u64) -> Self {
+        let mut wall = Self {
+            size,
+            config: config.clone(),
+            strength,
+            games: Vec::new(),
+            next_seed: seed,
+            retired_pieces: 0,
+            started: Instant::now(),
+            ticks: 0,
+            busy: Duration::ZERO,
+        };
+        for _ in 0..size.columns * size.rows {
+            let game = wall.new_game();
+            wall.games.push(game);
+        }
+        wall
+    }
+
+    fn new_game(&mut self) -> (Tetris, Bot) {
+        let seed = self.next_seed;
+        self.next_seed = self.next_seed.wrapping_add(1);
+        let config = GameConfig {
+            seed: Some(seed),
+            ..self.config.clone()
+        };
+        (config.build(), Bot::new(self.strength, seed))
+    }
+
+    /// Advance every game by one gravity tick.
+    pub fn tick(&mut self) {
+        let start = Instant::now();
+        for i in 0..self.games.len() {
+            if self.games[i].0.top_out().is_some() {
+                self.retired_pieces += self.games[i].0.pieces() as u64;
+                self.games[i] = self.new_game();
+            }
+            let (tetris, bot) = &mut self.games[i];
+            bot.act(tetris);
+            tetris.tick();
+        }
+        self.ticks += 1;
+        self.busy += start.elapsed();
+    }
+
+    pub fn games(&self) -> impl Iterator<Item = &Tetris> {
+        self.games.iter().map(|(tetris, _)| tetris)
+    }
+
+    /// Pieces locked across every game so far.
+    pub fn pieces(&self) -> u64 {
+        self.retired_pieces
+            + self
+                .games()
+                .map(|tetris| tetris.pieces() as u64)
+                .sum::<u64>()
+    }
+
+    /// Throughput so far, for the status line.
+    pub fn status(&self) -> String {
+        let elapsed = self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        let per_tick = self.busy.checked_div(self.ticks as u32).unwrap_or_default();
+        format!(
+            "{} games | {} pieces | {:.1} pieces/s | {:.2}ms/tick",
+            self.games.len(),
+            self.pieces(),
+            self.pieces() as f64 / elapsed,
+            per_tick.as_secs_f64() * 1000.0
+        )
+    }
+
+    /// Split `area` into a cell per game, row by row.
+    pub fn layout(&self, area: Rect) -> Vec<Rect> {
+        let split = |area: Rect, direction: Direction, count: u16| {
+            let constraints: Vec<Constraint> = (0..count)
+                .map(|_| Constraint::Ratio(1, count as u32))
+                .collect();
+            Layout::default()
+                .direction(direction)
+                .constraints(constraints)
+                .split(area)
+        };
+        split(area, Direction::Vertical, self.size.rows)
+            .into_iter()
+            .flat_map(|row| split(row, Direction::Horizontal, self.size.columns))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Difficulty;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            Size::parse("4x2"),
+            Ok(Size {
+                columns: 4,
+                rows: 2
+            })
+        );
+        assert!(Size::parse("0x2").is_err());
+        assert!(Size::parse("3").is_err());
+        assert!(Size::parse("9x1").is_err());
+    }
+
+    #[test]
+    fn test_wall() {
+        let size = Size {
+            columns: 3,
+            rows: 2,
+        };
+        let mut wall = Wall::new(size, &GameConfig::new(), Difficulty::Hard.strength(), 1);
+        assert_eq!(wall.games().count(), 6);
+        // Each game gets its own pieces.
+        let first: Vec<_> = wall.games().map(|tetris| tetris.preview()).collect();
+        assert!(first.iter().any(|preview| *preview != first[0]));
+        for _ in 0..200 {
+            wall.tick();
+        }
+        assert!(wall.pieces() >= 6 * 10);
+
+        let cells = wall.layout(Rect::new(0, 1, 90, 60));
+        assert_eq!(cells.len(), 6);
+        assert_eq!(cells[0], Rect::new(0, 1, 30, 30));
+        assert_eq!(cells[5], Rect::new(60, 31, 30, 30));
+    }
+}